@@ -0,0 +1,541 @@
+//! `#[derive(FromXml, ToXml)]` for `hxml`. Use them through `hxml` with the `derive` feature.
+//!
+//! ```ignore
+//! use hxml::{FromXml, ToXml};
+//!
+//! #[derive(FromXml, ToXml)]
+//! #[xml(rename = "config", ns = "urn:example")]
+//! struct Config {
+//!     #[xml(attr)]
+//!     version: u32,
+//!     #[xml(child = "server")]
+//!     servers: Vec<Server>,
+//!     #[xml(child)]
+//!     shape: Option<Shape>,
+//! }
+//! ```
+//!
+//! On structs and enums:
+//! - `rename = "name"`: the tag name (the name of the type by default)
+//! - `ns = "uri"`: the namespace URI. Elements are matched by their local names and namespaces, and it's declared when written.
+//!
+//! On fields:
+//! - `attr`, `attr = "name"`: an attribute. `Option<T>` is optional.
+//! - `text`: the text of the element itself, without its descendants.
+//! - `child`: child elements that the field type matches (by its own tag name). `Option<T>` is at most one, and `Vec<T>` is any number.
+//! - `child = "name"`: child elements named `name`. It can have `ns = "uri"`.
+//! - `inner_xml`: the contents as an XML string, for mixed content. It's written after the fields before it.
+//! - A field without `#[xml(...)]` is `child = "<the name of the field>"`.
+//!
+//! On variants:
+//! - `rename = "name"`: the tag name (the name of the variant by default). A variant is chosen by the tag name.
+//!   A unit variant is an empty element, a newtype variant is read and written by its field type, and a struct variant is like a struct.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Fields, LitStr, Type};
+
+#[proc_macro_derive(FromXml, attributes(xml))]
+pub fn derive_from_xml(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match impl_from_xml(&input) {
+        Ok(result) => result.into(),
+        Err(e) => e.to_compile_error().into()
+    }
+
+}
+
+#[proc_macro_derive(ToXml, attributes(xml))]
+pub fn derive_to_xml(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match impl_to_xml(&input) {
+        Ok(result) => result.into(),
+        Err(e) => e.to_compile_error().into()
+    }
+
+}
+
+// `#[xml(...)]` on structs, enums and variants
+#[derive(Default)]
+struct ContainerOptions {
+    rename: Option<String>,
+    ns: Option<String>,
+}
+
+enum FieldKind {
+    Attribute(String),
+    Text,
+
+    // None: the field type matches the elements
+    Child(Option<String>),
+    InnerXml,
+}
+
+// how many values a field has
+enum Arity {
+    One,
+    Optional,
+    Many,
+}
+
+struct Field {
+    ident: syn::Ident,
+    kind: FieldKind,
+    ns: Option<String>,
+    arity: Arity,
+
+    // `T` of `T`, `Option<T>` or `Vec<T>`
+    inner_type: Type,
+}
+
+fn parse_container_options(attrs: &[Attribute], allow_ns: bool) -> syn::Result<ContainerOptions> {
+    let mut result = ContainerOptions::default();
+
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("xml")) {
+
+        attr.parse_nested_meta(|meta| {
+
+            if meta.path.is_ident("rename") {
+                let name: LitStr = meta.value()?.parse()?;
+                check_name(&name)?;
+                result.rename = Some(name.value());
+            }
+
+            else if meta.path.is_ident("ns") && allow_ns {
+                let ns: LitStr = meta.value()?.parse()?;
+                result.ns = Some(ns.value());
+            }
+
+            else if meta.path.is_ident("ns") {
+                return Err(meta.error("`ns` of a variant is the one of the enum: put it on the enum"));
+            }
+
+            else {
+                return Err(meta.error(format!("unknown attribute `{}`: expected `rename` or `ns`", meta.path.to_token_stream())));
+            }
+
+            Ok(())
+        })?;
+
+    }
+
+    Ok(result)
+}
+
+fn parse_fields(fields: &Fields, span: &dyn ToTokens) -> syn::Result<Vec<Field>> {
+    let named = match fields {
+        Fields::Named(named) => named,
+        _ => {
+            return Err(syn::Error::new_spanned(span, "only structs and variants with named fields are supported"));
+        }
+    };
+
+    let mut result: Vec<Field> = vec![];
+
+    for field in named.named.iter() {
+        let ident = field.ident.clone().unwrap();
+        let name = ident.to_string().trim_start_matches("r#").to_string();
+        let mut kind = None;
+        let mut ns = None;
+
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("xml")) {
+
+            attr.parse_nested_meta(|meta| {
+                let new_kind = if meta.path.is_ident("attr") {
+                    FieldKind::Attribute(parse_optional_name(&meta)?.unwrap_or(name.clone()))
+                } else if meta.path.is_ident("text") {
+                    FieldKind::Text
+                } else if meta.path.is_ident("child") {
+                    FieldKind::Child(parse_optional_name(&meta)?)
+                } else if meta.path.is_ident("inner_xml") {
+                    FieldKind::InnerXml
+                } else if meta.path.is_ident("ns") {
+                    let value: LitStr = meta.value()?.parse()?;
+                    ns = Some(value.value());
+                    return Ok(());
+                } else {
+                    return Err(meta.error(format!(
+                        "unknown attribute `{}`: expected `attr`, `text`, `child`, `inner_xml` or `ns`",
+                        meta.path.to_token_stream()
+                    )));
+                };
+
+                if kind.is_some() {
+                    return Err(meta.error("a field can be only one of `attr`, `text`, `child` and `inner_xml`"));
+                }
+
+                kind = Some(new_kind);
+                Ok(())
+            })?;
+
+        }
+
+        let kind = kind.unwrap_or(FieldKind::Child(Some(name)));
+        let (arity, inner_type) = get_arity(&field.ty);
+
+        match (&kind, &arity) {
+            (FieldKind::Child(_), _) => {}
+            _ if ns.is_some() => {
+                return Err(syn::Error::new_spanned(field, "`ns` is only for child elements: use a prefixed name for an attribute, eg. `attr = \"xml:lang\"`"));
+            }
+            (FieldKind::Attribute(_), Arity::Many) => {
+                return Err(syn::Error::new_spanned(&field.ty, "an attribute cannot be repeated: `Vec` is only for child elements"));
+            }
+            (FieldKind::Text, Arity::One) | (FieldKind::InnerXml, Arity::One) => {}
+            (FieldKind::Text, _) | (FieldKind::InnerXml, _) => {
+                return Err(syn::Error::new_spanned(&field.ty, "a text is always there (maybe empty): `Option` and `Vec` are not allowed"));
+            }
+            _ => {}
+        }
+
+        let is_text = |kind: &FieldKind| matches!(kind, FieldKind::Text | FieldKind::InnerXml);
+
+        if is_text(&kind) && result.iter().any(|f| is_text(&f.kind)) {
+            return Err(syn::Error::new_spanned(field, "there can be only one `text` or `inner_xml` field"));
+        }
+
+        result.push(Field { ident, kind, ns, arity, inner_type });
+    }
+
+    Ok(result)
+}
+
+// `attr` or `attr = "name"`
+fn parse_optional_name(meta: &syn::meta::ParseNestedMeta) -> syn::Result<Option<String>> {
+
+    if meta.input.peek(syn::Token![=]) {
+        let name: LitStr = meta.value()?.parse()?;
+        check_name(&name)?;
+        Ok(Some(name.value()))
+    }
+
+    else {
+        Ok(None)
+    }
+
+}
+
+// https://www.w3.org/TR/xml/#NT-Name, roughly: the parser checks the rest when it's read again
+fn check_name(name: &LitStr) -> syn::Result<()> {
+    let value = name.value();
+    let is_valid = match value.chars().next() {
+        Some(c) => (c.is_alphabetic() || c == '_' || c == ':') && value.chars().all(
+            |c| c.is_alphanumeric() || c == '_' || c == ':' || c == '-' || c == '.'
+        ),
+        None => false
+    };
+
+    if is_valid {
+        Ok(())
+    }
+
+    else {
+        Err(syn::Error::new_spanned(name, format!("{:?} is not a valid XML name", value)))
+    }
+
+}
+
+fn get_arity(ty: &Type) -> (Arity, Type) {
+
+    match ty {
+        Type::Path(path) if path.qself.is_none() => {
+            let segment = path.path.segments.last().unwrap();
+
+            let arity = if segment.ident == "Option" {
+                Arity::Optional
+            } else if segment.ident == "Vec" {
+                Arity::Many
+            } else {
+                return (Arity::One, ty.clone());
+            };
+
+            match &segment.arguments {
+                syn::PathArguments::AngleBracketed(arguments) if arguments.args.len() == 1 => match &arguments.args[0] {
+                    syn::GenericArgument::Type(inner) => (arity, inner.clone()),
+                    _ => (Arity::One, ty.clone())
+                },
+                _ => (Arity::One, ty.clone())
+            }
+        }
+        _ => (Arity::One, ty.clone())
+    }
+
+}
+
+fn option_tokens(value: &Option<String>) -> TokenStream2 {
+
+    match value {
+        Some(value) => quote!(::std::option::Option::Some(#value)),
+        None => quote!(::std::option::Option::None)
+    }
+
+}
+
+// `field: expression` for each field, which reads `element`
+fn read_fields(fields: &[Field]) -> TokenStream2 {
+    let m = quote!(::hxml::mapping);
+
+    let initializers = fields.iter().map(|field| {
+        let ident = &field.ident;
+        let inner_type = &field.inner_type;
+
+        let expression = match &field.kind {
+            FieldKind::Attribute(name) => {
+                let what = format!("attribute `{}`", name);
+
+                match field.arity {
+                    Arity::Optional => quote!(#m::read_attribute::<#inner_type>(element, #name)?),
+                    _ => quote!(#m::require(element, #m::read_attribute::<#inner_type>(element, #name)?, #what)?),
+                }
+            }
+            FieldKind::Text => quote!(#m::read_text::<#inner_type>(element)?),
+            FieldKind::InnerXml => quote!(element.get_inner_string()),
+            FieldKind::Child(name) => {
+                let (children, what) = match name {
+                    Some(name) => {
+                        let ns = option_tokens(&field.ns);
+                        (quote!(#m::get_children(element, #name, #ns)), format!("<{}>", name))
+                    }
+                    None => (
+                        quote!(#m::get_matching_children::<#inner_type>(element)),
+                        format!("`{}` element", inner_type.to_token_stream().to_string().replace(' ', ""))
+                    ),
+                };
+
+                match field.arity {
+                    Arity::One => quote!(#m::read_one::<#inner_type>(element, #children, #what)?),
+                    Arity::Optional => quote!(#m::read_optional::<#inner_type>(element, #children, #what)?),
+                    Arity::Many => quote!(#m::read_many::<#inner_type>(#children)?),
+                }
+            }
+        };
+
+        quote!(#ident: #expression)
+    });
+
+    quote!(#(#initializers,)*)
+}
+
+// statements that write the fields to `element`
+// `access` is an expression of a reference to the field, eg. `&self.a`.
+fn write_fields(fields: &[Field], access: impl Fn(&syn::Ident) -> TokenStream2) -> TokenStream2 {
+    let m = quote!(::hxml::mapping);
+
+    let statements = fields.iter().map(|field| {
+        let value = access(&field.ident);
+
+        let write = |value: TokenStream2| match &field.kind {
+            FieldKind::Attribute(name) => quote!(#m::write_attribute(element, #name, #value);),
+            FieldKind::Text => quote!(#m::write_text(element, #value);),
+            FieldKind::InnerXml => quote!(element.insert_adjacent_xml(::hxml::AdjacentPosition::BeforeEnd, #value)?;),
+            FieldKind::Child(name) => {
+                let name = option_tokens(name);
+                let ns = option_tokens(&field.ns);
+                quote!(#m::write_child(element, #value, #name, #ns)?;)
+            }
+        };
+
+        match field.arity {
+            Arity::One => write(value),
+            Arity::Optional => {
+                let write_value = write(quote!(value));
+
+                quote! {
+                    match #value {
+                        ::std::option::Option::Some(value) => { #write_value }
+                        ::std::option::Option::None => {}
+                    }
+                }
+            }
+            Arity::Many => {
+                let write_item = write(quote!(item));
+
+                quote! {
+                    for item in (#value).iter() { #write_item }
+                }
+            }
+        }
+    });
+
+    quote!(#(#statements)*)
+}
+
+fn impl_from_xml(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let m = quote!(::hxml::mapping);
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let options = parse_container_options(&input.attrs, true)?;
+    let ns = option_tokens(&options.ns);
+
+    let (from_xml, matches) = match &input.data {
+        Data::Struct(data) => {
+            let fields = parse_fields(&data.fields, input)?;
+            let tag_name = options.rename.clone().unwrap_or(ident.to_string());
+            let initializers = read_fields(&fields);
+
+            (
+                quote!(::std::result::Result::Ok(#ident { #initializers })),
+                quote!(#m::matches_name(element, #tag_name, #ns)),
+            )
+        }
+        Data::Enum(data) => {
+            let mut branches = vec![];
+            let mut conditions = vec![];
+            let mut tag_names = vec![];
+
+            for variant in data.variants.iter() {
+                let variant_ident = &variant.ident;
+                let variant_options = parse_container_options(&variant.attrs, false)?;
+                let tag_name = variant_options.rename.unwrap_or(variant_ident.to_string());
+                let condition = quote!(#m::matches_name(element, #tag_name, #ns));
+
+                let value = match &variant.fields {
+                    Fields::Unit => quote!(#ident::#variant_ident),
+                    Fields::Unnamed(unnamed) if unnamed.unnamed.len() == 1 => {
+                        let ty = &unnamed.unnamed[0].ty;
+                        quote!(#ident::#variant_ident(<#ty as ::hxml::FromXml>::from_xml(element)?))
+                    }
+                    _ => {
+                        let fields = parse_fields(&variant.fields, variant)?;
+                        let initializers = read_fields(&fields);
+                        quote!(#ident::#variant_ident { #initializers })
+                    }
+                };
+
+                branches.push(quote! {
+                    if #condition {
+                        return ::std::result::Result::Ok(#value);
+                    }
+                });
+                conditions.push(condition);
+                tag_names.push(format!("<{}>", tag_name));
+            }
+
+            let expected = tag_names.join(", ");
+
+            (
+                quote! {
+                    #(#branches)*
+
+                    ::std::result::Result::Err(#m::error(
+                        element,
+                        "",
+                        &::std::format!("unexpected element <{}>, expected one of {}", element.get_tag_name(), #expected)
+                    ))
+                },
+                quote!(false #(|| #conditions)*),
+            )
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(input, "unions are not supported"));
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::hxml::FromXml for #ident #ty_generics #where_clause {
+
+            fn from_xml(element: ::hxml::ElementPtr) -> ::std::result::Result<Self, ::hxml::HxmlError> {
+                #from_xml
+            }
+
+            fn matches(element: ::hxml::ElementPtr) -> bool {
+                #matches
+            }
+
+        }
+    })
+}
+
+fn impl_to_xml(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let m = quote!(::hxml::mapping);
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let options = parse_container_options(&input.attrs, true)?;
+    let ns = option_tokens(&options.ns);
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let fields = parse_fields(&data.fields, input)?;
+            let tag_name = options.rename.clone().unwrap_or(ident.to_string());
+            let statements = write_fields(&fields, |field| quote!(&self.#field));
+
+            quote! {
+                let element = #m::new_element(tag_name.unwrap_or(#tag_name), #ns);
+
+                #m::build(element, || {
+                    #statements
+                    ::std::result::Result::Ok(())
+                })
+            }
+        }
+        Data::Enum(data) => {
+            let mut arms = vec![];
+
+            for variant in data.variants.iter() {
+                let variant_ident = &variant.ident;
+                let variant_options = parse_container_options(&variant.attrs, false)?;
+                let tag_name = variant_options.rename.unwrap_or(variant_ident.to_string());
+
+                arms.push(match &variant.fields {
+                    Fields::Unit => quote! {
+                        #ident::#variant_ident => ::std::result::Result::Ok(#m::new_element(#tag_name, #ns)),
+                    },
+                    Fields::Unnamed(unnamed) if unnamed.unnamed.len() == 1 => {
+                        let set_ns = match &options.ns {
+                            Some(ns) => quote!(element.set_attribute(::std::string::String::from("xmlns"), ::std::string::String::from(#ns));),
+                            None => quote!(),
+                        };
+
+                        quote! {
+                            #ident::#variant_ident(value) => {
+                                let element = ::hxml::ToXml::to_xml_element(value, ::std::option::Option::Some(#tag_name))?;
+                                #set_ns
+                                ::std::result::Result::Ok(element)
+                            }
+                        }
+                    }
+                    _ => {
+                        let fields = parse_fields(&variant.fields, variant)?;
+                        let idents = fields.iter().map(|field| &field.ident);
+                        let statements = write_fields(&fields, |field| quote!(#field));
+
+                        quote! {
+                            #ident::#variant_ident { #(#idents,)* } => {
+                                let element = #m::new_element(#tag_name, #ns);
+
+                                #m::build(element, || {
+                                    #statements
+                                    ::std::result::Result::Ok(())
+                                })
+                            }
+                        }
+                    }
+                });
+            }
+
+            quote! {
+                let _ = tag_name;
+
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(input, "unions are not supported"));
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::hxml::ToXml for #ident #ty_generics #where_clause {
+
+            fn to_xml_element(&self, tag_name: ::std::option::Option<&str>) -> ::std::result::Result<::hxml::ElementPtr, ::hxml::HxmlError> {
+                #body
+            }
+
+        }
+    })
+}
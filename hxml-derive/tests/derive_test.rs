@@ -0,0 +1,144 @@
+// hxml has global states, so everything is in one test.
+
+use hxml::{FromXml, ToXml};
+
+#[derive(Debug, PartialEq, FromXml, ToXml)]
+#[xml(rename = "config", ns = "urn:example:config")]
+struct Config {
+    #[xml(attr)]
+    version: u32,
+    #[xml(attr = "xml:lang")]
+    lang: Option<String>,
+    name: String,
+    #[xml(child = "server")]
+    servers: Vec<Server>,
+    #[xml(child)]
+    shape: Option<Shape>,
+}
+
+#[derive(Debug, PartialEq, FromXml, ToXml)]
+#[xml(rename = "server")]
+struct Server {
+    #[xml(attr)]
+    host: String,
+    #[xml(attr)]
+    port: u16,
+    #[xml(attr)]
+    secure: Option<bool>,
+    #[xml(child = "alias")]
+    aliases: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, FromXml, ToXml)]
+enum Shape {
+    #[xml(rename = "circle")]
+    Circle {
+        #[xml(attr)]
+        r: f64,
+    },
+    #[xml(rename = "label")]
+    Label(Label),
+    #[xml(rename = "none")]
+    Empty,
+}
+
+#[derive(Debug, PartialEq, FromXml, ToXml)]
+struct Label {
+    #[xml(text)]
+    text: String,
+}
+
+#[derive(Debug, PartialEq, FromXml, ToXml)]
+#[xml(rename = "p")]
+struct Paragraph {
+    #[xml(attr)]
+    class: Option<String>,
+    #[xml(inner_xml)]
+    contents: String,
+}
+
+#[derive(Debug, PartialEq, FromXml, ToXml)]
+#[xml(rename = "list")]
+struct List<T: FromXml + ToXml> {
+    #[xml(child = "item")]
+    items: Vec<T>,
+}
+
+#[test]
+fn derive_test() {
+    // elements live in the global arena, which `into_dom` initializes
+    hxml::into_dom(String::from("<doc/>")).unwrap();
+
+    let config = Config::from_xml_str("
+<config xmlns='urn:example:config' version='2' xml:lang='en'>
+    <name>main</name>
+    <server host='a.example.com' port='80'/>
+    <server host='b.example.com' port='443' secure='true'>
+        <alias>b</alias>
+        <alias>bb</alias>
+    </server>
+    <circle r='1.5'/>
+</config>").unwrap();
+
+    assert_eq!(config, Config {
+        version: 2,
+        lang: Some(String::from("en")),
+        name: String::from("main"),
+        servers: vec![
+            Server { host: String::from("a.example.com"), port: 80, secure: None, aliases: vec![] },
+            Server { host: String::from("b.example.com"), port: 443, secure: Some(true), aliases: vec![String::from("b"), String::from("bb")] },
+        ],
+        shape: Some(Shape::Circle { r: 1.5 }),
+    });
+
+    let s = config.to_xml_string().unwrap();
+    assert_eq!(
+        s,
+        "<config xmlns=\"urn:example:config\" version=\"2\" xml:lang=\"en\"><name>main</name><server host=\"a.example.com\" port=\"80\"/><server host=\"b.example.com\" port=\"443\" secure=\"true\"><alias>b</alias><alias>bb</alias></server><circle r=\"1.5\"/></config>"
+    );
+    assert_eq!(Config::from_xml_str(&s).unwrap(), config);
+
+    // enums
+    assert_eq!(Shape::from_xml_str("<label>hi</label>").unwrap(), Shape::Label(Label { text: String::from("hi") }));
+    assert_eq!(Shape::from_xml_str("<none/>").unwrap(), Shape::Empty);
+    assert_eq!(Shape::Label(Label { text: String::from("hi") }).to_xml_string().unwrap(), "<label>hi</label>");
+    assert_eq!(Shape::Empty.to_xml_string().unwrap(), "<none/>");
+    assert!(Shape::matches(hxml::Element::from_string(String::from("<circle/>")).unwrap()));
+
+    // mixed content
+    let paragraph = Paragraph::from_xml_str("<p class='a'>Hello, <b>world</b>!</p>").unwrap();
+    assert_eq!(paragraph.contents, "Hello, <b>world</b>!");
+    assert_eq!(paragraph.to_xml_string().unwrap(), "<p class=\"a\">Hello, <b>world</b>!</p>");
+
+    // generics
+    let list = List::<u32>::from_xml_str("<list><item>1</item><item> 2 </item></list>").unwrap();
+    assert_eq!(list.items, vec![1, 2]);
+    assert_eq!(list.to_xml_string().unwrap(), "<list><item>1</item><item>2</item></list>");
+
+    // wrong namespace
+    let e = Config::from_xml_str("<config version='1'><name>a</name></config>").unwrap_err();
+    assert!(e.to_string().contains("unexpected element <config>"), "{}", e);
+
+    // errors have paths and locations
+    let e = Config::from_xml_str("<config xmlns='urn:example:config' version='1'>
+<name>a</name>
+<server host='a' port='http'/>
+</config>").unwrap_err();
+    assert!(e.to_string().contains("cannot parse `http` as u16"), "{}", e);
+    assert!(e.to_string().contains("at `/config/server/@port` (line 3, column 1)"), "{}", e);
+
+    let e = Config::from_xml_str("<config xmlns='urn:example:config'><name>a</name></config>").unwrap_err();
+    assert!(e.to_string().contains("missing attribute `version` at `/config`"), "{}", e);
+
+    let e = Config::from_xml_str("<config xmlns='urn:example:config' version='1'/>").unwrap_err();
+    assert!(e.to_string().contains("expected one <name>, found 0"), "{}", e);
+
+    let e = Config::from_xml_str("<config xmlns='urn:example:config' version='1'><name>a</name><none/><circle r='1'/></config>").unwrap_err();
+    assert!(e.to_string().contains("expected at most one `Shape` element, found 2"), "{}", e);
+
+    let e = Shape::from_xml_str("<square/>").unwrap_err();
+    assert!(e.to_string().contains("unexpected element <square>"), "{}", e);
+
+    let e = Shape::from_xml(hxml::Element::from_string(String::from("<square/>")).unwrap()).unwrap_err();
+    assert!(e.to_string().contains("expected one of <circle>, <label>, <none>"), "{}", e);
+}
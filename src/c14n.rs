@@ -0,0 +1,484 @@
+use crate::node::element::{Content, Element};
+use crate::node::memory;
+use crate::parse::expand_reference;
+use crate::serialize::get_attributes;
+use crate::utils::{from_v16, into_v16};
+use std::collections::HashMap;
+
+const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum C14nMethod {
+
+    /// https://www.w3.org/TR/xml-c14n
+    Canonical10,
+
+    /// https://www.w3.org/TR/xml-c14n11
+    /// `xml:base` of the ancestors is not fixed up: it's just not inherited.
+    Canonical11,
+
+    /// https://www.w3.org/TR/xml-exc-c14n
+    Exclusive,
+}
+
+#[derive(Clone, Debug)]
+pub struct C14nOptions {
+    pub method: C14nMethod,
+    pub with_comments: bool,
+
+    /// InclusiveNamespaces PrefixList of Exclusive C14N. `#default` is the default namespace.
+    /// It's ignored by the other methods.
+    pub inclusive_namespaces: Vec<String>,
+}
+
+impl C14nOptions {
+
+    pub fn new(method: C14nMethod, with_comments: bool) -> Self {
+        C14nOptions { method, with_comments, inclusive_namespaces: vec![] }
+    }
+
+}
+
+/// If `element` is not the root, it's canonicalized as a document subset that consists of the subtree:
+/// namespace declarations (and `xml:*` attributes, for inclusive methods) of the ancestors are taken into account.
+///
+/// The DOM doesn't keep the DTD and processing instructions, so default attributes are not added and PIs are not written.
+/// References to entities that are not predefined are written as they are.
+pub fn canonicalize_element(element: &Element, options: &C14nOptions) -> String {
+    let mut in_scope = HashMap::new();
+    let mut inherited_attributes = vec![];
+    let mut ancestors = vec![];
+    let mut curr_element = element.get_parent();
+
+    while let Some(parent) = curr_element {
+        ancestors.push(parent);
+        curr_element = parent.get_parent();
+    }
+
+    // from the root to the parent, so that nearer declarations overwrite the farther ones
+    for ancestor in ancestors.iter().rev() {
+
+        for (name, value) in get_attributes(memory::get(ancestor.ptr)).into_iter() {
+
+            match get_declared_prefix(&name) {
+                Some(prefix) => {
+                    in_scope.insert(prefix, value);
+                }
+                None if is_inherited_attribute(&name, options.method) => {
+                    inherited_attributes.retain(|(n, _): &(String, String)| n != &name);
+                    inherited_attributes.push((name, value));
+                }
+                _ => {}
+            }
+
+        }
+
+    }
+
+    let mut result = String::new();
+    write_element(element, options, &HashMap::new(), in_scope, inherited_attributes, &mut result);
+
+    result
+}
+
+// `rendered`: namespace declarations that are written by the ancestors (prefix -> uri)
+// `in_scope`: namespace declarations of the ancestors, whether they're written or not
+fn write_element(
+    element: &Element,
+    options: &C14nOptions,
+    rendered: &HashMap<String, String>,
+    mut in_scope: HashMap<String, String>,
+    inherited_attributes: Vec<(String, String)>,
+    buffer: &mut String
+) {
+    let mut attributes = vec![];
+
+    for (name, value) in get_attributes(element).into_iter() {
+
+        match get_declared_prefix(&name) {
+            Some(prefix) => {
+                in_scope.insert(prefix, value);
+            }
+            None => {
+                attributes.push((name, value));
+            }
+        }
+
+    }
+
+    for (name, value) in inherited_attributes.into_iter() {
+
+        if attributes.iter().all(|(n, _)| n != &name) {
+            attributes.push((name, value));
+        }
+
+    }
+
+    let candidates = if options.method == C14nMethod::Exclusive {
+        let mut prefixes = vec![get_prefix(&element.tag_name)];
+
+        for (name, _) in attributes.iter() {
+
+            // unprefixed attributes are not in any namespace
+            if name.contains(':') {
+                prefixes.push(get_prefix(name));
+            }
+
+        }
+
+        for prefix in options.inclusive_namespaces.iter() {
+            prefixes.push(if prefix == "#default" { String::new() } else { prefix.clone() });
+        }
+
+        prefixes
+    } else {
+        in_scope.keys().cloned().collect()
+    };
+
+    let mut namespaces = vec![];
+    let mut rendered = rendered.clone();
+
+    for prefix in candidates.into_iter() {
+
+        if prefix == "xml" || namespaces.iter().any(|(p, _)| p == &prefix) {
+            continue;
+        }
+
+        let uri = match in_scope.get(&prefix) {
+            Some(uri) => uri.clone(),
+            None if prefix.is_empty() => String::new(),
+
+            // an undeclared prefix
+            None => { continue; }
+        };
+
+        let is_rendered = match rendered.get(&prefix) {
+            Some(rendered_uri) => rendered_uri == &uri,
+
+            // `xmlns=""` is written only when it undeclares a rendered default namespace
+            None => uri.is_empty()
+        };
+
+        if !is_rendered {
+            namespaces.push((prefix, uri));
+        }
+
+    }
+
+    for (prefix, uri) in namespaces.iter() {
+        rendered.insert(prefix.clone(), uri.clone());
+    }
+
+    namespaces.sort();
+
+    let mut attributes = attributes.into_iter().map(
+        |(name, value)| {
+            let uri = if name.contains(':') {
+                let prefix = get_prefix(&name);
+
+                if prefix == "xml" {
+                    XML_NAMESPACE.to_string()
+                } else {
+                    in_scope.get(&prefix).cloned().unwrap_or_default()
+                }
+
+            } else {
+                String::new()
+            };
+
+            ((uri, get_local_name(&name)), name, value)
+        }
+    ).collect::<Vec<((String, String), String, String)>>();
+
+    attributes.sort();
+
+    buffer.push('<');
+    buffer.push_str(&element.tag_name);
+
+    for (prefix, uri) in namespaces.iter() {
+
+        if prefix.is_empty() {
+            buffer.push_str(" xmlns=\"");
+        }
+
+        else {
+            buffer.push_str(&format!(" xmlns:{}=\"", prefix));
+        }
+
+        buffer.push_str(&escape_attribute_value(uri));
+        buffer.push('"');
+    }
+
+    for (_, name, value) in attributes.iter() {
+        buffer.push_str(&format!(" {}=\"{}\"", name, escape_attribute_value(value)));
+    }
+
+    buffer.push('>');
+
+    for content in element.contents.iter() {
+
+        match content {
+            Content::Element(pointer) => {
+                write_element(memory::get(pointer.ptr), options, &rendered, in_scope.clone(), vec![], buffer);
+            },
+            Content::CharData(text) | Content::CDSect(text) => {
+                buffer.push_str(&escape_text(text));
+            },
+            Content::Reference(reference) => match expand_reference(&into_v16(&format!("&{};", reference))) {
+                Some(expanded) => {
+                    buffer.push_str(&escape_text(&from_v16(&expanded)));
+                },
+                None => {
+                    buffer.push_str(&format!("&{};", reference));
+                }
+            },
+            Content::Comment(comment) => {
+
+                if options.with_comments {
+                    buffer.push_str(&format!("<!--{}-->", comment));
+                }
+
+            }
+        }
+
+    }
+
+    buffer.push_str(&format!("</{}>", element.tag_name));
+}
+
+// `xmlns` -> Some(""), `xmlns:a` -> Some("a"), otherwise None
+fn get_declared_prefix(attribute_name: &str) -> Option<String> {
+
+    if attribute_name == "xmlns" {
+        Some(String::new())
+    }
+
+    else {
+        attribute_name.strip_prefix("xmlns:").map(|prefix| prefix.to_string())
+    }
+
+}
+
+fn get_prefix(name: &str) -> String {
+
+    match name.find(':') {
+        Some(index) => name[..index].to_string(),
+        None => String::new()
+    }
+
+}
+
+fn get_local_name(name: &str) -> String {
+
+    match name.find(':') {
+        Some(index) => name[(index + 1)..].to_string(),
+        None => name.to_string()
+    }
+
+}
+
+// `xml:*` attributes of the ancestors that are copied to the apex of a document subset
+fn is_inherited_attribute(name: &str, method: C14nMethod) -> bool {
+
+    match method {
+        C14nMethod::Canonical10 => name.starts_with("xml:"),
+        C14nMethod::Canonical11 => name == "xml:lang" || name == "xml:space",
+        C14nMethod::Exclusive => false,
+    }
+
+}
+
+// https://www.w3.org/TR/xml-c14n#ProcessingModel
+fn escape_text(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+
+    for c in text.chars() {
+
+        match c {
+            '&' => { result.push_str("&amp;"); },
+            '<' => { result.push_str("&lt;"); },
+            '>' => { result.push_str("&gt;"); },
+            '\r' => { result.push_str("&#xD;"); },
+            _ => { result.push(c); }
+        }
+
+    }
+
+    result
+}
+
+fn escape_attribute_value(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+
+    for c in value.chars() {
+
+        match c {
+            '&' => { result.push_str("&amp;"); },
+            '<' => { result.push_str("&lt;"); },
+            '"' => { result.push_str("&quot;"); },
+            '\t' => { result.push_str("&#x9;"); },
+            '\n' => { result.push_str("&#xA;"); },
+            '\r' => { result.push_str("&#xD;"); },
+            _ => { result.push(c); }
+        }
+
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{dom, into_dom};
+    use crate::c14n::*;
+
+    // https://www.w3.org/TR/xml-c14n#Examples
+    // DTDs and PIs are removed from the examples, because the parser doesn't support them.
+    #[test]
+    fn w3c_examples_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        let test_cases = vec![
+            // 3.2 Whitespace in Document Content
+            (
+"<doc>
+   <clean>   </clean>
+   <dirty>   A   B   </dirty>
+   <mixed>
+      A
+      <clean>   </clean>
+      B
+      <dirty>   A   B   </dirty>
+      C
+   </mixed>
+</doc>",
+"<doc>
+   <clean>   </clean>
+   <dirty>   A   B   </dirty>
+   <mixed>
+      A
+      <clean>   </clean>
+      B
+      <dirty>   A   B   </dirty>
+      C
+   </mixed>
+</doc>"
+            ),
+            // 3.3 Start and End Tags
+            (
+"<doc>
+   <e1   />
+   <e2   ></e2>
+   <e3   name = \"elem3\"   id=\"elem3\"   />
+   <e4   name=\"elem4\"   id=\"elem4\"   ></e4>
+   <e5 a:attr=\"out\" b:attr=\"sorted\" attr2=\"all\" attr=\"I'm\"
+      xmlns:b=\"http://www.ietf.org\"
+      xmlns:a=\"http://www.w3.org\"
+      xmlns=\"http://example.org\"/>
+   <e6 xmlns=\"\" xmlns:a=\"http://www.w3.org\">
+      <e7 xmlns=\"http://www.ietf.org\">
+         <e8 xmlns=\"\" xmlns:a=\"http://www.w3.org\">
+            <e9 xmlns=\"\" xmlns:a=\"http://www.ietf.org\"/>
+         </e8>
+      </e7>
+   </e6>
+</doc>",
+"<doc>
+   <e1></e1>
+   <e2></e2>
+   <e3 id=\"elem3\" name=\"elem3\"></e3>
+   <e4 id=\"elem4\" name=\"elem4\"></e4>
+   <e5 xmlns=\"http://example.org\" xmlns:a=\"http://www.w3.org\" xmlns:b=\"http://www.ietf.org\" attr=\"I'm\" attr2=\"all\" b:attr=\"sorted\" a:attr=\"out\"></e5>
+   <e6 xmlns:a=\"http://www.w3.org\">
+      <e7 xmlns=\"http://www.ietf.org\">
+         <e8 xmlns=\"\">
+            <e9 xmlns:a=\"http://www.ietf.org\"></e9>
+         </e8>
+      </e7>
+   </e6>
+</doc>"
+            ),
+            // 3.4 Character Modifications and Character References
+            (
+"<doc>
+   <text>First line&#x0d;&#10;Second line</text>
+   <value>&#x32;</value>
+   <compute><![CDATA[value>\"0\" && value<\"10\" ?\"valid\":\"error\"]]></compute>
+   <compute expr='value>\"0\" &amp;&amp; value&lt;\"10\" ?\"valid\":\"error\"'>valid</compute>
+   <norm attr=' &apos;   &#x20;&#13;&#xa;&#9;   &apos; '/>
+</doc>",
+"<doc>
+   <text>First line&#xD;
+Second line</text>
+   <value>2</value>
+   <compute>value&gt;\"0\" &amp;&amp; value&lt;\"10\" ?\"valid\":\"error\"</compute>
+   <compute expr=\"value>&quot;0&quot; &amp;&amp; value&lt;&quot;10&quot; ?&quot;valid&quot;:&quot;error&quot;\">valid</compute>
+   <norm attr=\" '    &#xD;&#xA;&#x9;   ' \"></norm>
+</doc>"
+            ),
+        ];
+
+        for (document, answer) in test_cases.into_iter() {
+            into_dom(document.to_string()).unwrap();
+            assert_eq!(dom::canonicalize(&C14nOptions::new(C14nMethod::Canonical10, false)), answer);
+        }
+
+        into_dom("<doc><!-- comment --><a/></doc>".to_string()).unwrap();
+        assert_eq!(dom::canonicalize(&C14nOptions::new(C14nMethod::Canonical11, true)), "<doc><!-- comment --><a></a></doc>");
+        assert_eq!(dom::canonicalize(&C14nOptions::new(C14nMethod::Canonical11, false)), "<doc><a></a></doc>");
+
+        drop(lock);
+    }
+
+    // https://www.w3.org/TR/xml-exc-c14n#sec-Enveloping
+    #[test]
+    fn document_subset_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        into_dom(
+"<n0:local xmlns:n0=\"foo:bar\" xmlns:n3=\"ftp://example.org\" xml:space=\"preserve\" xml:base=\"http://example.com/\">
+   <n1:elem2 xmlns:n1=\"http://example.net\" xml:lang=\"en\">
+      <n3:stuff xmlns:n3=\"ftp://example.org\"/>
+   </n1:elem2>
+</n0:local>".to_string()
+        ).unwrap();
+
+        let elem2 = dom::get_element_by_tag_name(None, "n1:elem2".to_string()).unwrap();
+
+        assert_eq!(
+            elem2.canonicalize(&C14nOptions::new(C14nMethod::Canonical10, false)),
+"<n1:elem2 xmlns:n0=\"foo:bar\" xmlns:n1=\"http://example.net\" xmlns:n3=\"ftp://example.org\" xml:base=\"http://example.com/\" xml:lang=\"en\" xml:space=\"preserve\">
+      <n3:stuff></n3:stuff>
+   </n1:elem2>"
+        );
+
+        assert_eq!(
+            elem2.canonicalize(&C14nOptions::new(C14nMethod::Canonical11, false)),
+"<n1:elem2 xmlns:n0=\"foo:bar\" xmlns:n1=\"http://example.net\" xmlns:n3=\"ftp://example.org\" xml:lang=\"en\" xml:space=\"preserve\">
+      <n3:stuff></n3:stuff>
+   </n1:elem2>"
+        );
+
+        assert_eq!(
+            elem2.canonicalize(&C14nOptions::new(C14nMethod::Exclusive, false)),
+"<n1:elem2 xmlns:n1=\"http://example.net\" xml:lang=\"en\">
+      <n3:stuff xmlns:n3=\"ftp://example.org\"></n3:stuff>
+   </n1:elem2>"
+        );
+
+        let options = C14nOptions {
+            inclusive_namespaces: vec!["n0".to_string()],
+            ..C14nOptions::new(C14nMethod::Exclusive, false)
+        };
+
+        assert_eq!(
+            elem2.canonicalize(&options),
+"<n1:elem2 xmlns:n0=\"foo:bar\" xmlns:n1=\"http://example.net\" xml:lang=\"en\">
+      <n3:stuff xmlns:n3=\"ftp://example.org\"></n3:stuff>
+   </n1:elem2>"
+        );
+
+        drop(lock);
+    }
+
+}
@@ -0,0 +1,764 @@
+// Deserializing Rust values from elements, with serde. It's behind the `serde` feature.
+//
+// An element is mapped to a struct like this:
+//   - An attribute `name` goes to the field `name`, or `@name` if the struct has a field with that name.
+//   - Child elements go to the field of their tag name. Repeated ones are a sequence (eg. `Vec`), and a missing one is `None`.
+//   - `$text` is the text of the element itself (char data, CDATA sections and references), without its descendants.
+//   - `$value` takes the child elements that don't have their own fields, in order. They're usually enums.
+// An enum is chosen by the tag name of the element, the tag name of its only child element, or its text, in that order.
+// Other values (numbers, strings, ...) are parsed from the text content. A sequence in an attribute is separated by whitespaces.
+
+use crate::err::HxmlError;
+use crate::dom;
+use crate::mapping::{get_path, locate_message};
+use crate::node::element::{parse_fragment, Content};
+use crate::node::memory;
+use crate::node::pointer::ElementPtr;
+use crate::parse::get_prolog_end_index;
+use crate::text::get_own_text;
+use crate::utils::{from_v16, into_v16};
+use serde::de::{self, DeserializeOwned, DeserializeSeed, Deserializer, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess, Visitor};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// It deserializes `T` from an XML document. The prolog (`<?xml ...?>`, `<!DOCTYPE ...>`) is skipped.
+/// It doesn't touch the current document: the elements are parsed like `Content::from_string`, and deleted afterwards.
+pub fn from_str<T: DeserializeOwned>(s: &str) -> Result<T, HxmlError> {
+
+    // the prolog is blanked out, so that the line numbers in errors are kept
+    let mut string_v16 = into_v16(s);
+
+    if let Some(end_index) = get_prolog_end_index(&string_v16, 0) {
+
+        for c in string_v16[..(end_index + 1)].iter_mut() {
+
+            if *c != '\n' as u16 && *c != '\r' as u16 {
+                *c = ' ' as u16;
+            }
+
+        }
+
+    }
+
+    let contents = parse_fragment(&from_v16(&string_v16))?;
+    let elements = contents.iter().filter_map(
+        |content| match content {
+            Content::Element(element) => Some(*element),
+            _ => None
+        }
+    ).collect::<Vec<ElementPtr>>();
+
+    let result = if elements.len() == 1 {
+        from_element(elements[0])
+    } else {
+        Err(HxmlError::new(format!("expected a root element, found {} elements", elements.len()), usize::MAX))
+    };
+
+    for element in elements.into_iter() {
+        dom::delete(element);
+    }
+
+    result
+}
+
+/// It deserializes `T` from `element` and its descendants. See the `de` module for the mapping.
+/// Errors have the path of the element (eg. `/config/server[2]/@port`) and the line and column where it's parsed.
+pub fn from_element<T: DeserializeOwned>(element: ElementPtr) -> Result<T, HxmlError> {
+
+    match T::deserialize(ElementDeserializer::new(element, get_path(element))) {
+        Ok(result) => Ok(result),
+        Err(e) => Err(HxmlError::new(e.message, usize::MAX))
+    }
+
+}
+
+#[derive(Debug)]
+pub(crate) struct DeError {
+    message: String,
+
+    // whether the path and the location are in `message`
+    is_located: bool,
+}
+
+impl Display for DeError {
+
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "{}", self.message)
+    }
+
+}
+
+impl std::error::Error for DeError {}
+
+impl de::Error for DeError {
+
+    fn custom<T: Display>(message: T) -> Self {
+        DeError {
+            message: message.to_string(),
+            is_located: false,
+        }
+    }
+
+}
+
+struct ElementDeserializer {
+    element: ElementPtr,
+    path: String,
+}
+
+impl ElementDeserializer {
+
+    fn new(element: ElementPtr, path: String) -> Self {
+        ElementDeserializer { element, path }
+    }
+
+    // It adds the path (with `suffix`, eg. `/@id`) and the source location to the message of an error.
+    fn locate<T>(&self, result: Result<T, DeError>, suffix: &str) -> Result<T, DeError> {
+
+        match result {
+            Err(e) if !e.is_located => Err(DeError {
+                message: locate_message(self.element, &format!("{}{}", self.path, suffix), &e.message),
+                is_located: true,
+            }),
+            _ => result
+        }
+
+    }
+
+    fn text(&self) -> TextDeserializer {
+        TextDeserializer(self.element.text_content())
+    }
+
+    // It counts the tag names first, so that the paths (`a/b[2]`) are built in a single pass.
+    fn children(&self) -> Vec<ElementDeserializer> {
+        let children = self.element.get_children();
+        let tag_names = children.iter().map(|child| child.get_tag_name()).collect::<Vec<String>>();
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+
+        for tag_name in tag_names.iter() {
+            *counts.entry(tag_name).or_insert(0) += 1;
+        }
+
+        let mut indices: HashMap<&str, usize> = HashMap::with_capacity(counts.len());
+
+        children.iter().zip(tag_names.iter()).map(
+            |(child, tag_name)| {
+                let path = if counts[tag_name.as_str()] > 1 {
+                    let index = indices.entry(tag_name).or_insert(0);
+                    *index += 1;
+
+                    format!("{}/{}[{}]", self.path, tag_name, index)
+                } else {
+                    format!("{}/{}", self.path, tag_name)
+                };
+
+                ElementDeserializer::new(*child, path)
+            }
+        ).collect()
+    }
+
+    // `fields` is None for maps: every attribute and child element is an entry.
+    fn entries(&self, fields: Option<&[&str]>) -> Vec<(String, Entry)> {
+        let has_field = |name: &str| match fields {
+            Some(fields) => fields.contains(&name),
+            None => true
+        };
+
+        let mut result = vec![];
+
+        for attribute in self.element.get_all_attributes().iter() {
+            let key = if fields.is_some() && has_field(&format!("@{}", attribute.name)) {
+                format!("@{}", attribute.name)
+            } else {
+                attribute.name.clone()
+            };
+
+            result.push((key, Entry::Attribute(attribute.name.clone(), attribute.value.clone())));
+        }
+
+        let mut rest = vec![];
+
+        // tag name -> index of its entry in `result`
+        let mut entry_indices: HashMap<String, usize> = HashMap::new();
+
+        for child in self.children().into_iter() {
+            let tag_name = child.element.get_tag_name();
+
+            if !has_field(&tag_name) && has_field("$value") {
+                rest.push(child);
+                continue;
+            }
+
+            match entry_indices.get(&tag_name) {
+                Some(index) => match &mut result[*index] {
+                    (_, Entry::Elements(_, elements)) => {
+                        elements.push(child);
+                    }
+                    _ => unreachable!()
+                },
+                None => {
+                    entry_indices.insert(tag_name.clone(), result.len());
+                    result.push((tag_name.clone(), Entry::Elements(format!("{}/{}", self.path, tag_name), vec![child])));
+                }
+            }
+
+        }
+
+        if !rest.is_empty() {
+            result.push((String::from("$value"), Entry::Elements(format!("{}/*", self.path), rest)));
+        }
+
+        if fields.is_some() && has_field("$text") {
+            result.push((String::from("$text"), Entry::Text(get_own_text(memory::get(self.element.ptr)))));
+        }
+
+        result
+    }
+
+    fn deserialize_entries<'de, V: Visitor<'de>>(self, fields: Option<&[&str]>, visitor: V) -> Result<V::Value, DeError> {
+        let entries = self.entries(fields);
+        let result = visitor.visit_map(ElementMap { element: &self, entries: entries.into_iter(), value: None });
+
+        self.locate(result, "")
+    }
+
+    fn deserialize_children<'de, V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+
+        if self.element.get_children().is_empty() {
+            let result = self.text().deserialize_seq(visitor);
+            return self.locate(result, "");
+        }
+
+        let result = visitor.visit_seq(Seq(self.children().into_iter()));
+
+        self.locate(result, "")
+    }
+
+}
+
+// methods that parse the text content
+macro_rules! forward_to_text {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+                let result = self.text().$method(visitor);
+                self.locate(result, "")
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for ElementDeserializer {
+    type Error = DeError;
+
+    forward_to_text! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64 deserialize_i128
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_u128 deserialize_f32 deserialize_f64
+        deserialize_char deserialize_str deserialize_string deserialize_bytes deserialize_byte_buf deserialize_identifier
+    }
+
+    // a text if there are no attributes and child elements, otherwise a map
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+
+        if self.element.get_all_attributes().is_empty() && self.element.get_children().is_empty() {
+            self.deserialize_string(visitor)
+        }
+
+        else {
+            self.deserialize_entries(None, visitor)
+        }
+
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_children(visitor)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_children(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(self, _name: &'static str, _len: usize, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_children(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_entries(None, visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, _name: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_entries(Some(fields), visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value, DeError> {
+        let tag_name = self.element.get_tag_name();
+
+        if variants.contains(&tag_name.as_str()) {
+            let result = visitor.visit_enum(ElementEnum(&self));
+            return self.locate(result, "");
+        }
+
+        let children = self.children();
+
+        let result = if children.len() == 1 {
+            children.into_iter().next().unwrap().deserialize_enum(name, variants, visitor)
+        } else {
+            self.text().deserialize_enum(name, variants, visitor)
+        };
+
+        self.locate(result, "")
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_unit()
+    }
+}
+
+enum Entry {
+
+    // (name, value)
+    Attribute(String, String),
+    Text(String),
+
+    // (path, elements): child elements with the same tag name, or the ones for `$value`
+    Elements(String, Vec<ElementDeserializer>),
+}
+
+struct ElementMap<'a> {
+    element: &'a ElementDeserializer,
+    entries: std::vec::IntoIter<(String, Entry)>,
+    value: Option<Entry>,
+}
+
+impl<'de, 'a> MapAccess<'de> for ElementMap<'a> {
+    type Error = DeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, DeError> {
+
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(key.into_deserializer()).map(Some)
+            }
+            None => Ok(None)
+        }
+
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, DeError> {
+
+        match self.value.take() {
+            Some(Entry::Attribute(name, value)) => {
+                let result = seed.deserialize(TextDeserializer(value));
+                self.element.locate(result, &format!("/@{}", name))
+            }
+            Some(Entry::Text(text)) => {
+                let result = seed.deserialize(TextDeserializer(text));
+                self.element.locate(result, "/text()")
+            }
+            Some(Entry::Elements(path, elements)) => {
+                let result = seed.deserialize(ElementsDeserializer { path, elements });
+                self.element.locate(result, "")
+            }
+            None => Err(de::Error::custom("a value is requested before its key"))
+        }
+
+    }
+}
+
+struct Seq<I>(I);
+
+impl<'de, D: Deserializer<'de, Error = DeError>, I: Iterator<Item = D>> SeqAccess<'de> for Seq<I> {
+    type Error = DeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, DeError> {
+
+        match self.0.next() {
+            Some(item) => seed.deserialize(item).map(Some),
+            None => Ok(None)
+        }
+
+    }
+}
+
+struct ElementEnum<'a>(&'a ElementDeserializer);
+
+impl<'de, 'a> EnumAccess<'de> for ElementEnum<'a> {
+    type Error = DeError;
+    type Variant = ElementDeserializer;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, ElementDeserializer), DeError> {
+        let tag_name: de::value::StringDeserializer<DeError> = self.0.element.get_tag_name().into_deserializer();
+        let variant = seed.deserialize(tag_name)?;
+
+        Ok((variant, ElementDeserializer::new(self.0.element, self.0.path.clone())))
+    }
+}
+
+impl<'de> VariantAccess<'de> for ElementDeserializer {
+    type Error = DeError;
+
+    fn unit_variant(self) -> Result<(), DeError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, DeError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_children(visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_entries(Some(fields), visitor)
+    }
+}
+
+// Child elements with the same tag name. A sequence takes all of them, and the others take exactly one.
+struct ElementsDeserializer {
+    path: String,
+    elements: Vec<ElementDeserializer>,
+}
+
+impl ElementsDeserializer {
+
+    fn into_one(self) -> Result<ElementDeserializer, DeError> {
+
+        if self.elements.len() == 1 {
+            Ok(self.elements.into_iter().next().unwrap())
+        }
+
+        else {
+            Err(DeError {
+                message: format!("expected one element, found {} at `{}`", self.elements.len(), self.path),
+                is_located: true,
+            })
+        }
+
+    }
+
+}
+
+macro_rules! forward_to_one {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+                self.into_one()?.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for ElementsDeserializer {
+    type Error = DeError;
+
+    forward_to_one! {
+        deserialize_any deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64 deserialize_i128
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_u128 deserialize_f32 deserialize_f64
+        deserialize_char deserialize_str deserialize_string deserialize_bytes deserialize_byte_buf deserialize_identifier
+        deserialize_unit deserialize_map deserialize_ignored_any
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, name: &'static str, visitor: V) -> Result<V::Value, DeError> {
+        self.into_one()?.deserialize_unit_struct(name, visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_seq(Seq(self.elements.into_iter()))
+    }
+
+    // `(i32, i32)` is either `<p>1 2</p>` or `<p>1</p><p>2</p>`
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, DeError> {
+
+        if self.elements.len() == 1 {
+            self.into_one()?.deserialize_tuple(len, visitor)
+        }
+
+        else {
+            self.deserialize_seq(visitor)
+        }
+
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(self, _name: &'static str, len: usize, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, name: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value, DeError> {
+        self.into_one()?.deserialize_struct(name, fields, visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value, DeError> {
+        self.into_one()?.deserialize_enum(name, variants, visitor)
+    }
+}
+
+// the value of an attribute, or a text
+// Errors are located by the caller.
+struct TextDeserializer(String);
+
+impl TextDeserializer {
+
+    fn parse<T: FromStr>(&self, type_name: &str) -> Result<T, DeError> where T::Err: Display {
+
+        match self.0.trim().parse::<T>() {
+            Ok(result) => Ok(result),
+            Err(e) => Err(de::Error::custom(format!("cannot parse `{}` as {}: {}", self.0, type_name, e)))
+        }
+
+    }
+
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident $visit:ident $type:ty)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+                visitor.$visit(self.parse::<$type>(stringify!($type))?)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for TextDeserializer {
+    type Error = DeError;
+
+    deserialize_parsed! {
+        deserialize_i8 visit_i8 i8 deserialize_i16 visit_i16 i16 deserialize_i32 visit_i32 i32
+        deserialize_i64 visit_i64 i64 deserialize_i128 visit_i128 i128
+        deserialize_u8 visit_u8 u8 deserialize_u16 visit_u16 u16 deserialize_u32 visit_u32 u32
+        deserialize_u64 visit_u64 u64 deserialize_u128 visit_u128 u128
+        deserialize_f32 visit_f32 f32 deserialize_f64 visit_f64 f64 deserialize_char visit_char char
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_string(self.0)
+    }
+
+    // https://www.w3.org/TR/xmlschema-2/#boolean
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+
+        match self.0.trim() {
+            "true" | "1" => visitor.visit_bool(true),
+            "false" | "0" => visitor.visit_bool(false),
+            _ => Err(de::Error::custom(format!("cannot parse `{}` as bool", self.0)))
+        }
+
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_string(self.0)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_string(self.0)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_string(self.0)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_byte_buf(self.0.into_bytes())
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_byte_buf(self.0.into_bytes())
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    // whitespace-separated values, like `xs:list`
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_seq(Seq(self.0.split_whitespace().map(|s| TextDeserializer(s.to_string())).collect::<Vec<TextDeserializer>>().into_iter()))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(self, _name: &'static str, _len: usize, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, DeError> {
+        Err(de::Error::custom(format!("expected an element, found a text `{}`", self.0)))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, _name: &'static str, _fields: &'static [&'static str], _visitor: V) -> Result<V::Value, DeError> {
+        Err(de::Error::custom(format!("expected an element, found a text `{}`", self.0)))
+    }
+
+    // unit variants only
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_enum(self.0.trim().to_string().into_deserializer())
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_unit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{dom, from_element, from_str, into_dom};
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Config {
+        name: String,
+        #[serde(rename = "@version")]
+        version: u32,
+        debug: Option<bool>,
+        #[serde(rename = "server")]
+        servers: Vec<Server>,
+        level: Level,
+        shape: Shape,
+        env: HashMap<String, String>,
+        tags: Vec<String>,
+        missing: Option<Server>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Server {
+        host: String,
+        port: u16,
+        #[serde(rename = "$text")]
+        description: String,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Level {
+        Debug,
+        Info,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Shape {
+        Circle { r: f64 },
+        Point,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Document {
+        #[serde(rename = "$value")]
+        blocks: Vec<Block>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Block {
+        P(String),
+        Hr,
+        Img { src: String },
+    }
+
+    #[test]
+    fn deserialize_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        into_dom("<doc/>".to_string()).unwrap();
+
+        let config = from_str::<Config>(r#"<?xml version="1.0"?>
+<config version="2" name="app" tags="x y  z">
+    <server host="a" port="80">main</server>
+    <server host="b"><port> 8080 </port></server>
+    <level>info</level>
+    <shape><circle r="1.5"/></shape>
+    <env><HOME>/root</HOME><LANG>C</LANG></env>
+</config>"#).unwrap();
+
+        assert_eq!(
+            config,
+            Config {
+                name: "app".to_string(),
+                version: 2,
+                debug: None,
+                servers: vec![
+                    Server { host: "a".to_string(), port: 80, description: "main".to_string() },
+                    Server { host: "b".to_string(), port: 8080, description: String::new() },
+                ],
+                level: Level::Info,
+                shape: Shape::Circle { r: 1.5 },
+                env: vec![("HOME", "/root"), ("LANG", "C")].into_iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+                tags: vec!["x".to_string(), "y".to_string(), "z".to_string()],
+                missing: None,
+            }
+        );
+
+        // the document is not affected
+        assert_eq!(dom::to_string(), "<doc/>");
+        dom::check_indices().unwrap();
+
+        let document = from_str::<Document>("<doc><p>a &amp; b</p><hr/><img src=\"x.png\"/></doc>").unwrap();
+        assert_eq!(document.blocks, vec![Block::P("a & b".to_string()), Block::Hr, Block::Img { src: "x.png".to_string() }]);
+
+        // errors have paths and locations
+        let error = from_str::<Server>("<server host=\"a\">\n  <port>x</port>\n</server>").unwrap_err().to_string();
+        assert!(error.contains("cannot parse `x` as u16") && error.contains("`/server/port` (line 2, column 3)"), "{}", error);
+
+        let error = from_str::<Config>("<config version=\"v2\"/>").unwrap_err().to_string();
+        assert!(error.contains("`/config/@version` (line 1, column 1)"), "{}", error);
+
+        let error = from_str::<Config>("<config version=\"2\">\n<server host=\"a\"/><server port=\"1\"/></config>").unwrap_err().to_string();
+        assert!(error.contains("missing field `port` at `/config/server[1]` (line 2, column 1)"), "{}", error);
+
+        let error = from_str::<Server>("<server host=\"a\" port=\"1\"><port>2</port></server>").unwrap_err().to_string();
+        assert!(error.contains("duplicate field `port`"), "{}", error);
+
+        assert!(from_str::<Server>("<a/><b/>").is_err());
+        assert!(from_str::<Server>("<server>").is_err());
+
+        // from an element of the document
+        into_dom("<r><server host=\"h\" port=\"1\"/></r>".to_string()).unwrap();
+        let server = from_element::<Server>(dom::get_root().get_children()[0]).unwrap();
+        assert_eq!(server.host, "h");
+        assert_eq!(from_element::<Vec<Server>>(dom::get_root()).unwrap().len(), 1);
+
+        drop(lock);
+    }
+
+}
@@ -1,49 +1,113 @@
+use crate::c14n::{canonicalize_element, C14nOptions};
+use crate::encoding::{self, Encoding};
+use crate::err::HxmlError;
+use crate::index::{self, IndexKey};
+use crate::order;
+use crate::minify::{minify_element, Minified, MinifyOptions};
+use crate::pretty::{pretty_document, PrettyOptions};
+use crate::selector::{self, parse_selector_list};
+use crate::xpath::{XPath, XPathNode, XPathValue};
 use crate::node::{
     memory::{ELEMENTS, self},
     pointer::ElementPtr,
     prolog::Prolog
 };
 use std::collections::{HashSet, HashMap};
+use std::io::Write;
+use std::ptr::{addr_of, addr_of_mut};
+
+/// the root element from `into_dom`
+/// Elements created later (eg. by `Element::from_string`) may reuse the memory of deleted elements, so `get_root` can't just pick the first alive element.
+pub static mut ROOT: Option<ElementPtr> = None;
 
 pub static mut PROLOG: Option<Prolog> = None;
 
-pub static mut TAGS_BY_NAME: Option<HashMap<String, Vec<ElementPtr>>> = None;
-pub static mut TAGS_BY_ID: Option<HashMap<String, ElementPtr>> = None;
-pub static mut TAGS_BY_CLASS: Option<HashMap<String, Vec<ElementPtr>>> = None;
+/// `ParseOptions::preserve_format`
+/// the text after the root element
+pub static mut EPILOG: Option<String> = None;
 
-#[cfg(test)]
-pub static mut LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+pub static mut TAGS_BY_NAME: Option<HashMap<String, ElementSet>> = None;
+/// Ids are supposed to be unique, but a document may have duplicate ids.
+pub static mut TAGS_BY_ID: Option<HashMap<String, ElementSet>> = None;
+pub static mut TAGS_BY_CLASS: Option<HashMap<String, ElementSet>> = None;
 
-pub fn get_all_elements() -> Vec<ElementPtr> {
-    unsafe {
-        ELEMENTS.iter().filter(|e| e.is_alive).map(|e| e.pointer).collect()
-    }
+/// The elements under a key of an index, in no particular order.
+/// An element is inserted and removed in O(1).
+#[derive(Clone, Debug, Default)]
+pub struct ElementSet {
+    elements: Vec<ElementPtr>,
+
+    // ptr -> the index in `elements`
+    slots: HashMap<usize, usize>,
 }
 
-/// if `elements` is None, it searches the entire DOM.
-pub fn get_element_by_id(elements: Option<Vec<ElementPtr>>, id: String) -> Option<ElementPtr> {
+impl ElementSet {
 
-    match elements {
-        None => {
-            unsafe {
-                return TAGS_BY_ID.as_ref().unwrap().get(&id).copied();
-            }
+    pub fn as_slice(&self) -> &[ElementPtr] {
+        &self.elements
+    }
+
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    pub fn contains(&self, element: ElementPtr) -> bool {
+        self.slots.contains_key(&element.ptr)
+    }
+
+    // It does nothing if `element` is already in the set.
+    pub(crate) fn insert(&mut self, element: ElementPtr) {
+
+        if !self.slots.contains_key(&element.ptr) {
+            self.slots.insert(element.ptr, self.elements.len());
+            self.elements.push(element);
         }
-        Some(elements) => {
 
-            for element in elements.into_iter() {
-                let el = memory::get(element.ptr);
+    }
 
-                if el.id.is_some() && el.id.as_ref().unwrap() == &id {
-                    return Some(el.pointer);
-                }
+    // The last element takes the slot of the removed one.
+    pub(crate) fn remove(&mut self, element: ElementPtr) {
+
+        if let Some(slot) = self.slots.remove(&element.ptr) {
+            self.elements.swap_remove(slot);
 
+            if let Some(moved) = self.elements.get(slot) {
+                self.slots.insert(moved.ptr, slot);
             }
 
         }
+
+    }
+
+}
+
+#[cfg(test)]
+pub static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+pub fn get_all_elements() -> Vec<ElementPtr> {
+    unsafe {
+        (*addr_of!(ELEMENTS)).iter().filter(|e| e.is_alive).map(|e| e.pointer).collect()
+    }
+}
+
+/// if `elements` is None, it searches the entire DOM.
+/// If the id appears multiple times, it returns the first one in document order. Use `get_elements_by_id` to get all of them.
+pub fn get_element_by_id(elements: Option<Vec<ElementPtr>>, id: String) -> Option<ElementPtr> {
+    get_elements_by_id(elements, id).first().copied()
+}
+
+/// if `elements` is None, it searches the entire DOM, and the result is in document order. See `get_elements_by_tag_name`.
+pub fn get_elements_by_id(elements: Option<Vec<ElementPtr>>, id: String) -> Vec<ElementPtr> {
+
+    match elements {
+        None => in_document_order(get_id_candidates(&id)),
+        Some(elements) => elements.into_iter().filter(|e| memory::get(e.ptr).id.as_ref() == Some(&id)).collect()
     }
 
-    None
 }
 
 /// if `elements` is None, it searches the entire DOM.
@@ -51,78 +115,150 @@ pub fn get_ids(elements: Option<Vec<ElementPtr>>) -> Vec<String> {
 
     match elements {
         None => unsafe {
-            TAGS_BY_ID.as_ref().unwrap().keys().map(|id| id.to_string()).collect()
+            get_tags(addr_of_mut!(TAGS_BY_ID)).keys().map(|id| id.to_string()).collect()
         }
         Some(elements) => elements.into_iter().filter_map(|e| memory::get(e.ptr).id.clone()).collect()
     }
 
 }
 
+/// It deletes `element` and its descendants, and removes them from the indices.
 pub fn delete(element: ElementPtr) {
+    unindex_subtree(element);
+
+    if let Some(p) = element.get_parent() {
+        p.delete_child_element(element);
+    }
+
+    memory::delete(element);
+}
+
+fn unindex_subtree(element: ElementPtr) {
+    unindex_element(element);
+
+    for child in element.get_children().into_iter() {
+        unindex_subtree(child);
+    }
+
+}
+
+// The indices have every alive element, whether it's in the document tree or not.
+// These functions keep them in sync with the elements: call them whenever a tag name, an id or classes change.
+pub(crate) fn index_element(element: ElementPtr) {
     let el = memory::get(element.ptr);
 
     unsafe {
+        insert_index(get_tags(addr_of_mut!(TAGS_BY_NAME)), &el.tag_name, element);
+    }
+
+    if let Some(id) = &el.id {
+        index_id(element, id);
+    }
+
+    index_classes(element, &el.classes);
+    index::insert_element(element);
+}
+
+pub(crate) fn unindex_element(element: ElementPtr) {
+    let el = memory::get(element.ptr);
 
-        match &el.id {
-            Some(id) => {
-                TAGS_BY_ID.as_mut().unwrap().remove(id);
+    unsafe {
+        remove_index(get_tags(addr_of_mut!(TAGS_BY_NAME)), &el.tag_name, element);
+    }
+
+    if let Some(id) = &el.id {
+        unindex_id(element, id);
+    }
+
+    unindex_classes(element, &el.classes);
+    index::remove_element(element);
+}
+
+pub(crate) fn index_id(element: ElementPtr, id: &str) {
+    unsafe {
+        insert_index(get_tags(addr_of_mut!(TAGS_BY_ID)), id, element);
+    }
+}
+
+pub(crate) fn unindex_id(element: ElementPtr, id: &str) {
+    unsafe {
+        remove_index(get_tags(addr_of_mut!(TAGS_BY_ID)), id, element);
+    }
+}
+
+// `class="a a"` is indexed once
+pub(crate) fn index_classes(element: ElementPtr, classes: &[String]) {
+
+    for (ind, class) in classes.iter().enumerate() {
+
+        if !classes[..ind].contains(class) {
+            unsafe {
+                insert_index(get_tags(addr_of_mut!(TAGS_BY_CLASS)), class, element);
             }
-            _ => {}
         }
 
-        for class in el.classes.iter() {
-            let tags = TAGS_BY_CLASS.as_mut().unwrap().get_mut(class).unwrap();
-            let mut tag_ind = 0;
+    }
 
-            for ind in 0..tags.len() {
+}
 
-                if tags[ind] == element {
-                    tag_ind = ind;
-                    break;
-                }
+pub(crate) fn unindex_classes(element: ElementPtr, classes: &[String]) {
 
-            }
+    for (ind, class) in classes.iter().enumerate() {
 
-            #[cfg(test)] assert!(tags[tag_ind] == element);
-            tags.swap_remove(tag_ind);
+        if !classes[..ind].contains(class) {
+            unsafe {
+                remove_index(get_tags(addr_of_mut!(TAGS_BY_CLASS)), class, element);
+            }
         }
 
-        let tags = TAGS_BY_NAME.as_mut().unwrap().get_mut(&el.tag_name).unwrap();
-        let mut tag_ind = 0;
+    }
 
-        for ind in 0..tags.len() {
+}
 
-            if tags[ind] == element {
-                tag_ind = ind;
-                break;
-            }
+// `into_dom` creates the indices
+unsafe fn get_tags(tags: *mut Option<HashMap<String, ElementSet>>) -> &'static mut HashMap<String, ElementSet> {
+    (*tags).as_mut().unwrap()
+}
 
-        }
+fn insert_index(index: &mut HashMap<String, ElementSet>, key: &str, element: ElementPtr) {
 
-        #[cfg(test)] assert!(tags[tag_ind] == element);
-        tags.swap_remove(tag_ind);
+    match index.get_mut(key) {
+        Some(tags) => {
+            tags.insert(element);
+        }
+        None => {
+            let mut tags = ElementSet::default();
+            tags.insert(element);
+            index.insert(key.to_string(), tags);
+        }
     }
 
-    match element.get_parent() {
-        Some(p) => {
-            p.delete_child_element(element);
+}
+
+// It removes the key if no elements are left, so that `get_ids` doesn't return ids of deleted elements.
+fn remove_index(index: &mut HashMap<String, ElementSet>, key: &str, element: ElementPtr) {
+    let tags = match index.get_mut(key) {
+        Some(tags) => tags,
+        None => {
+            return;
         }
-        _ => {}
+    };
+
+    tags.remove(element);
+
+    if tags.is_empty() {
+        index.remove(key);
     }
 
-    memory::delete(element);
 }
 
-/// if `elements` is None, it searches the entire DOM.
+/// if `elements` is None, it searches the entire DOM, and the result is in document order.
+/// Elements that are not in the document (eg. created by `Element::from_string` and not added yet) come after the ones in the document.
+/// Otherwise, it filters `elements` and keeps their order. Use `ElementPtr::get_elements_by_tag_name` to search the descendants of an element.
 pub fn get_elements_by_tag_name(elements: Option<Vec<ElementPtr>>, tag_name: String) -> Vec<ElementPtr> {
 
     match elements {
-        None => unsafe {
-            match TAGS_BY_NAME.as_ref().unwrap().get(&tag_name) {
-                Some(v) => v.to_vec(),
-                _ => vec![]
-            }
-        },
+        None => in_document_order(get_tag_name_candidates(&tag_name)),
         Some(elements) => elements.into_iter().filter(|e| memory::get(e.ptr).tag_name == tag_name).collect()
     }
 
@@ -135,8 +271,8 @@ pub fn get_element_by_tag_name(elements: Option<Vec<ElementPtr>>, tag_name: Stri
     match elements {
         None => unsafe {
 
-            return match TAGS_BY_NAME.as_ref().unwrap().get(&tag_name) {
-                Some(v) if v.len() > 0 => Some(v[0]),
+            return match get_tags(addr_of_mut!(TAGS_BY_NAME)).get(&tag_name) {
+                Some(v) => v.as_slice().iter().copied().min_by(|a, b| order::compare(*a, *b)),
                 _ => None
             };
 
@@ -157,27 +293,145 @@ pub fn get_element_by_tag_name(elements: Option<Vec<ElementPtr>>, tag_name: Stri
     None
 }
 
-/// if `elements` is None, it searches the entire DOM.
+/// if `elements` is None, it searches the entire DOM, and the result is in document order. See `get_elements_by_tag_name`.
 pub fn get_elements_by_class_name(elements: Option<Vec<ElementPtr>>, class_name: String) -> Vec<ElementPtr> {
 
     match elements {
-        None => unsafe {
-            match TAGS_BY_CLASS.as_ref().unwrap().get(&class_name) {
-                Some(v) => v.to_vec(),
-                _ => vec![]
-            }
-        },
+        None => in_document_order(get_class_name_candidates(&class_name)),
         Some(elements) => elements.into_iter().filter(|e| memory::get(e.ptr).classes.contains(&class_name)).collect()
     }
 
 }
 
+/// It registers a secondary index on the document, which is kept in sync with mutations.
+/// Indices are dropped when a new document is parsed. `get_contents_mut` doesn't update the indices.
+pub fn create_index(name: &str, key: IndexKey) -> Result<(), HxmlError> {
+    index::create(name, key)
+}
+
+/// It returns false if there's no index named `name`.
+pub fn drop_index(name: &str) -> bool {
+    index::drop(name)
+}
+
+/// It fails if there's no index named `name`.
+/// The result is in document order.
+pub fn get_elements_by_index(name: &str, key: &str) -> Result<Vec<ElementPtr>, HxmlError> {
+
+    Ok(in_document_order(get_index_candidates(name, key)?))
+}
+
+/// It uses an index on `attribute` if there's one. Otherwise, it searches all the elements.
+/// The result is in document order.
+pub fn get_elements_by_attribute(attribute: &str, value: &str) -> Vec<ElementPtr> {
+    in_document_order(get_attribute_candidates(attribute, value))
+}
+
+// The functions below return the elements in the global indices as they are, without sorting them.
+// Scoped queries filter and sort them by themselves, see `order::filter_descendants`.
+
+pub(crate) fn get_id_candidates(id: &str) -> Vec<ElementPtr> {
+
+    unsafe {
+        match get_tags(addr_of_mut!(TAGS_BY_ID)).get(id) {
+            Some(v) => v.as_slice().to_vec(),
+            _ => vec![]
+        }
+    }
+
+}
+
+pub(crate) fn get_tag_name_candidates(tag_name: &str) -> Vec<ElementPtr> {
+
+    unsafe {
+        match get_tags(addr_of_mut!(TAGS_BY_NAME)).get(tag_name) {
+            Some(v) => v.as_slice().to_vec(),
+            _ => vec![]
+        }
+    }
+
+}
+
+pub(crate) fn get_class_name_candidates(class_name: &str) -> Vec<ElementPtr> {
+
+    unsafe {
+        match get_tags(addr_of_mut!(TAGS_BY_CLASS)).get(class_name) {
+            Some(v) => v.as_slice().to_vec(),
+            _ => vec![]
+        }
+    }
+
+}
+
+pub(crate) fn get_index_candidates(name: &str, key: &str) -> Result<Vec<ElementPtr>, HxmlError> {
+
+    match index::get(name, key) {
+        Some(elements) => Ok(elements),
+        None => Err(HxmlError::new(format!("index `{}` doesn't exist", name), usize::MAX))
+    }
+
+}
+
+pub(crate) fn get_attribute_candidates(attribute: &str, value: &str) -> Vec<ElementPtr> {
+
+    match index::get_by_attribute(attribute, value) {
+        Some(elements) => elements,
+        None => get_all_elements().into_iter().filter(
+            |e| e.get_attribute(attribute.to_string()).as_deref() == Some(value)
+        ).collect()
+    }
+
+}
+
+/// It sorts `elements` in document order. Elements that are not in the document come last.
+pub fn sort_in_document_order(elements: &mut [ElementPtr]) {
+    order::sort(elements);
+}
+
+fn in_document_order(mut elements: Vec<ElementPtr>) -> Vec<ElementPtr> {
+    order::sort(&mut elements);
+
+    elements
+}
+
+/// It returns the elements that match the CSS selectors, in document order.
+/// See the `selector` module for the supported selectors.
+pub fn query_selector_all(selectors: &str) -> Result<Vec<ElementPtr>, HxmlError> {
+    Ok(selector::query_selector_all(&parse_selector_list(selectors)?, None))
+}
+
+/// It returns the first element that matches the CSS selectors, in document order.
+pub fn query_selector(selectors: &str) -> Result<Option<ElementPtr>, HxmlError> {
+    Ok(query_selector_all(selectors)?.first().copied())
+}
+
+/// It compiles and evaluates an XPath 1.0 expression, with the root node as the context node.
+/// Use `XPath::compile` to evaluate the same expression many times.
+pub fn evaluate_xpath(expression: &str) -> Result<XPathValue, HxmlError> {
+    XPath::compile(expression)?.evaluate(XPathNode::Root)
+}
+
 pub fn get_root() -> ElementPtr {
 
     unsafe {
-        let mut curr_element = &ELEMENTS[0];
 
-        for element in ELEMENTS.iter() {
+        match ROOT {
+            Some(root) if memory::get(root.ptr).is_alive => {
+                let mut curr_element = root;
+
+                while let Some(parent) = curr_element.get_parent() {
+                    curr_element = parent;
+                }
+
+                return curr_element;
+            }
+            _ => {}
+        }
+
+        let elements = &*addr_of!(ELEMENTS);
+        let mut curr_element = &elements[0];
+
+        for element in elements.iter() {
 
             if element.is_alive {
                 curr_element = element;
@@ -198,21 +452,119 @@ pub fn get_root() -> ElementPtr {
 }
 
 pub fn to_string() -> String {
+    let mut result = vec![];
+    write_to(&mut result).unwrap();
+
+    String::from_utf8(result).unwrap()
+}
+
+/// It's like `to_string`, but it streams the output to `w`.
+pub fn write_to<W: Write>(w: &mut W) -> Result<(), HxmlError> {
+
+    unsafe {
+        if let Some(p) = &*addr_of!(PROLOG) {
+            w.write_all(p.to_string().as_bytes())?;
+        }
+    }
+
+    memory::get(get_root().ptr).write_to(w)?;
+
+    unsafe {
+        if let Some(e) = &*addr_of!(EPILOG) {
+            w.write_all(e.as_bytes())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// It writes the document in HTML syntax. See `Element::to_html` for the details.
+/// The XML declaration is dropped, and the doctype is written as `<!DOCTYPE html>`.
+pub fn to_html() -> Result<String, HxmlError> {
+    let mut result = vec![];
+    write_html_to(&mut result)?;
+
+    Ok(String::from_utf8(result).unwrap())
+}
+
+/// It's like `to_html`, but it streams the output to `w`.
+pub fn write_html_to<W: Write>(w: &mut W) -> Result<(), HxmlError> {
+
+    unsafe {
+        if let Some(p) = &*addr_of!(PROLOG) {
+            w.write_all(p.to_html_string().as_bytes())?;
+        }
+    }
+
+    memory::get(get_root().ptr).write_html_to(w)
+}
+
+/// It re-indents the whole document. See `PrettyOptions` for the details.
+pub fn to_pretty_string(options: &PrettyOptions) -> String {
+    unsafe {
+        pretty_document(&*addr_of!(PROLOG), memory::get(get_root().ptr), options)
+    }
+}
+
+/// Canonical XML of the document. See `C14nOptions` for the details.
+/// The XML declaration and the DTD are removed.
+pub fn canonicalize(options: &C14nOptions) -> String {
+    canonicalize_element(memory::get(get_root().ptr), options)
+}
+
+/// See `MinifyOptions` for the details.
+/// The prolog is written as it is.
+/// It fails in HTML mode if a `<script>` or a `<style>` contains its own end tag.
+pub fn minify(options: &MinifyOptions) -> Result<Minified, HxmlError> {
+    let prolog_text = unsafe {
+        match &*addr_of!(PROLOG) {
+            Some(p) => p.to_string(),
+            None => String::new()
+        }
+    };
+    let output = format!("{}{}", prolog_text, minify_element(memory::get(get_root().ptr), options)?);
+    let original_len = to_string().len();
 
+    Ok(Minified {
+        bytes_saved: original_len.max(output.len()) - output.len(),
+        output,
+    })
+}
+
+/// It's like `to_string`, but it fails if the result cannot be parsed back into the same tree.
+pub fn to_xml() -> Result<String, HxmlError> {
     let prolog_text = unsafe {
-        match &PROLOG {
+        match &*addr_of!(PROLOG) {
             Some(p) => p.to_string(),
             None => String::new()
         }
     };
-    let ee = get_root();
-    let element_text = ee.to_string();
-    //let element_text = get_root().to_string();
-
-    format!(
-        "{}{}",
-        prolog_text,
-        element_text
+
+    Ok(format!("{}{}", prolog_text, get_root().to_xml()?))
+}
+
+/// It serializes the document in `encoding`.
+/// Characters that `encoding` cannot represent are written as numeric character references.
+/// The `encoding` of the XML declaration is updated. If the document doesn't have an XML declaration,
+/// it adds one, unless `encoding` is UTF-8 or UTF-16 (which don't need a declaration).
+pub fn to_bytes(encoding: Encoding) -> Result<Vec<u8>, HxmlError> {
+    let mut prolog = unsafe {
+        match &*addr_of!(PROLOG) {
+            Some(p) => p.clone(),
+            None => Prolog::new(None, None)
+        }
+    };
+
+    match encoding {
+        Encoding::Utf8 | Encoding::Utf16Le | Encoding::Utf16Be if !prolog.has_xml_decl() => {},
+        _ => {
+            prolog.set_xml_encoding(encoding.to_label().to_string());
+        }
+    }
+
+    encoding::encode(
+        &format!("{}{}", prolog.to_string(), get_root().to_string()),
+        encoding
     )
 }
 
@@ -221,16 +573,17 @@ pub fn to_string() -> String {
 /// - all the ids are unique
 /// - all the elements have unique attributes
 pub fn some_checks() -> Result<(), String> {
-    let ids = get_ids(None);
-    let mut id_set = HashSet::with_capacity(ids.len());
 
-    for id in ids.iter() {
+    unsafe {
+
+        for (id, tags) in get_tags(addr_of_mut!(TAGS_BY_ID)).iter() {
+
+            if tags.len() > 1 {
+                return Err(format!("ID#{} appears multiple times!", id));
+            }
 
-        if id_set.contains(id) {
-            return Err(format!("ID#{} appears multiple times!", id));
         }
 
-        id_set.insert(id.clone());
     }
 
     let root = get_root();
@@ -240,4 +593,177 @@ pub fn some_checks() -> Result<(), String> {
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// It walks every tree (the document and detached elements) and compares the result with the indices, including the ones from `create_index`.
+/// It's for debugging: it's slow, and it fails only if there's a bug in hxml or `get_contents_mut` is used to add or remove elements.
+pub fn check_indices() -> Result<(), String> {
+    let mut tags_by_name: HashMap<String, Vec<usize>> = HashMap::new();
+    let mut tags_by_id: HashMap<String, Vec<usize>> = HashMap::new();
+    let mut tags_by_class: HashMap<String, Vec<usize>> = HashMap::new();
+    let mut visited = HashSet::new();
+    let all_elements = get_all_elements();
+
+    for element in all_elements.iter() {
+
+        if element.get_parent().is_none() {
+            walk_tree(*element, &mut visited, &mut tags_by_name, &mut tags_by_id, &mut tags_by_class)?;
+        }
+
+    }
+
+    if visited.len() != all_elements.len() {
+        return Err(format!("{} elements are not reachable from any tree!", all_elements.len() - visited.len()));
+    }
+
+    unsafe {
+        compare_index("tag name", get_tags(addr_of_mut!(TAGS_BY_NAME)), tags_by_name)?;
+        compare_index("id", get_tags(addr_of_mut!(TAGS_BY_ID)), tags_by_id)?;
+        compare_index("class", get_tags(addr_of_mut!(TAGS_BY_CLASS)), tags_by_class)?;
+    }
+
+    index::check()
+}
+
+fn walk_tree(
+    element: ElementPtr,
+    visited: &mut HashSet<usize>,
+    tags_by_name: &mut HashMap<String, Vec<usize>>,
+    tags_by_id: &mut HashMap<String, Vec<usize>>,
+    tags_by_class: &mut HashMap<String, Vec<usize>>,
+) -> Result<(), String> {
+    let el = memory::get(element.ptr);
+
+    if !el.is_alive {
+        return Err(format!("{:?} is deleted, but it's still in the tree!", element));
+    }
+
+    if !visited.insert(element.ptr) {
+        return Err(format!("{:?} appears multiple times in the tree!", element));
+    }
+
+    tags_by_name.entry(el.tag_name.clone()).or_default().push(element.ptr);
+
+    if let Some(id) = &el.id {
+        tags_by_id.entry(id.clone()).or_default().push(element.ptr);
+    }
+
+    for class in el.classes.iter() {
+        let tags = tags_by_class.entry(class.clone()).or_default();
+
+        if !tags.contains(&element.ptr) {
+            tags.push(element.ptr);
+        }
+
+    }
+
+    for child in el.get_children().into_iter() {
+
+        if child.get_parent() != Some(element) {
+            return Err(format!("the parent of {:?} is not {:?}!", child, element));
+        }
+
+        walk_tree(child, visited, tags_by_name, tags_by_id, tags_by_class)?;
+    }
+
+    Ok(())
+}
+
+fn compare_index(index_name: &str, index: &HashMap<String, ElementSet>, expected: HashMap<String, Vec<usize>>) -> Result<(), String> {
+
+    if index.len() != expected.len() {
+        return Err(format!("the {} index has {} keys, but the tree has {}!", index_name, index.len(), expected.len()));
+    }
+
+    for (key, mut expected_tags) in expected.into_iter() {
+        let mut tags = match index.get(&key) {
+            Some(tags) => tags.as_slice().iter().map(|tag| tag.ptr).collect::<Vec<usize>>(),
+            None => {
+                return Err(format!("`{}` is missing in the {} index!", key, index_name));
+            }
+        };
+
+        tags.sort();
+        expected_tags.sort();
+
+        if tags != expected_tags {
+            return Err(format!("the {} index of `{}` is {:?}, but the tree has {:?}!", index_name, key, tags, expected_tags));
+        }
+
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dom;
+
+    #[test]
+    fn index_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        crate::into_dom("<body><div id=\"a\" class=\"x y\"><p class=\"x\">1</p></div><div id=\"a\"></div></body>".to_string()).unwrap();
+        dom::check_indices().unwrap();
+
+        // duplicate ids
+        let divs = dom::get_elements_by_tag_name(None, "div".to_string());
+        assert_eq!(dom::get_elements_by_id(None, "a".to_string()).len(), 2);
+        assert!(dom::some_checks().is_err());
+
+        divs[1].set_attribute("id".to_string(), "b".to_string());
+        assert_eq!(dom::get_element_by_id(None, "a".to_string()), Some(divs[0]));
+        assert_eq!(dom::get_element_by_id(None, "b".to_string()), Some(divs[1]));
+        assert!(dom::some_checks().is_ok());
+        dom::check_indices().unwrap();
+
+        // classes
+        let div = divs[0];
+        assert!(div.has_class("y") && !div.has_class("z"));
+        div.add_class("z");
+        div.remove_class("x");
+        assert_eq!(div.get_attribute("class".to_string()), Some("y z".to_string()));
+        assert!(!div.toggle_class("y"));
+        assert!(div.toggle_class("w"));
+        assert_eq!(div.get_attribute("class".to_string()), Some("z w".to_string()));
+        assert_eq!(dom::get_elements_by_class_name(None, "x".to_string()).len(), 1);
+        assert_eq!(dom::get_elements_by_class_name(None, "w".to_string()), vec![div]);
+        assert_eq!(dom::get_elements_by_class_name(None, "y".to_string()), vec![]);
+        dom::check_indices().unwrap();
+
+        // moving an element
+        let p = dom::get_element_by_tag_name(None, "p".to_string()).unwrap();
+        divs[1].add_element_ptr(p);
+        assert_eq!(div.get_children(), vec![]);
+        assert_eq!(p.get_parent(), Some(divs[1]));
+        dom::check_indices().unwrap();
+
+        // deleting a subtree
+        dom::delete(divs[1]);
+        assert_eq!(dom::get_element_by_id(None, "b".to_string()), None);
+        assert_eq!(dom::get_elements_by_tag_name(None, "p".to_string()), vec![]);
+        assert_eq!(dom::get_elements_by_class_name(None, "x".to_string()), vec![]);
+        assert!(!dom::get_ids(None).contains(&"b".to_string()));
+        dom::check_indices().unwrap();
+
+        // new elements
+        let contents = crate::Content::from_string("<span id=\"c\" class=\"x\"/>".to_string()).unwrap();
+        div.add_contents(contents);
+        assert_eq!(dom::get_element_by_id(None, "c".to_string()).unwrap().get_parent(), Some(div));
+        dom::check_indices().unwrap();
+        assert_eq!(dom::to_string(), "<body><div id=\"a\" class=\"z w\"><span id=\"c\" class=\"x\"/></div></body>");
+
+        // removing elements from the middle of a key keeps the others
+        crate::into_dom(format!("<a>{}</a>", "<b class=\"x\"/>".repeat(6))).unwrap();
+        let bs = dom::get_elements_by_tag_name(None, "b".to_string());
+        dom::delete(bs[1]);
+        dom::delete(bs[4]);
+        bs[0].remove_class("x");
+        dom::delete(bs[5]);
+        assert_eq!(dom::get_elements_by_class_name(None, "x".to_string()), vec![bs[2], bs[3]]);
+        dom::check_indices().unwrap();
+
+        drop(lock);
+    }
+
+}
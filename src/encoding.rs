@@ -0,0 +1,494 @@
+use crate::err::HxmlError;
+use crate::parse::{get_xml_decl_end_index, parse_xml_decl};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Ascii,
+    Iso8859_1,
+    Windows1252,
+}
+
+impl Encoding {
+
+    /// It's case-insensitive. It returns None if the encoding is not supported.
+    pub fn from_label(label: &str) -> Option<Self> {
+
+        match label.trim().to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" | "unicode-1-1-utf-8" => Some(Encoding::Utf8),
+
+            // `UTF-16` without a BOM is big endian
+            "utf-16" | "utf-16be" | "unicodefffe" => Some(Encoding::Utf16Be),
+            "utf-16le" | "unicode" => Some(Encoding::Utf16Le),
+            "us-ascii" | "ascii" | "iso-ir-6" | "ansi_x3.4-1968" => Some(Encoding::Ascii),
+            "iso-8859-1" | "iso8859-1" | "iso_8859-1" | "latin1" | "l1" | "iso-ir-100" => Some(Encoding::Iso8859_1),
+            "windows-1252" | "cp1252" | "x-cp1252" => Some(Encoding::Windows1252),
+            _ => None
+        }
+
+    }
+
+    /// The name that is written in `<?xml encoding='...'?>`
+    pub fn to_label(&self) -> &'static str {
+
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le | Encoding::Utf16Be => "UTF-16",
+            Encoding::Ascii => "US-ASCII",
+            Encoding::Iso8859_1 => "ISO-8859-1",
+            Encoding::Windows1252 => "windows-1252",
+        }
+
+    }
+
+}
+
+// Windows-1252 differs from ISO-8859-1 only at 0x80..=0x9F
+// undefined bytes (0x81, 0x8D, 0x8F, 0x90, 0x9D) are mapped to the C1 controls, like browsers do
+const WINDOWS_1252_HIGH: [u16; 32] = [
+    0x20ac, 0x0081, 0x201a, 0x0192, 0x201e, 0x2026, 0x2020, 0x2021,
+    0x02c6, 0x2030, 0x0160, 0x2039, 0x0152, 0x008d, 0x017d, 0x008f,
+    0x0090, 0x2018, 0x2019, 0x201c, 0x201d, 0x2022, 0x2013, 0x2014,
+    0x02dc, 0x2122, 0x0161, 0x203a, 0x0153, 0x009d, 0x017e, 0x0178,
+];
+
+/// It detects the encoding of `document` in this order.
+///
+/// 1. byte order mark
+/// 2. the first four bytes of UTF-16 documents without a BOM (`<?` or `<` followed by a name)
+/// 3. `<?xml encoding='...'?>`
+/// 4. `<meta charset='...'>` in the first 1024 bytes
+///
+/// If nothing is found, it's UTF-8. It returns the encoding and the length of the BOM.
+/// A declaration of UTF-16 in a document without a BOM is an error, because the declaration itself is in ASCII bytes.
+pub fn detect_encoding(document: &[u8]) -> Result<(Encoding, usize), HxmlError> {
+
+    if document.starts_with(&[0xef, 0xbb, 0xbf]) {
+        return Ok((Encoding::Utf8, 3));
+    }
+
+    else if document.starts_with(&[0xfe, 0xff]) {
+        return Ok((Encoding::Utf16Be, 2));
+    }
+
+    else if document.starts_with(&[0xff, 0xfe]) {
+        return Ok((Encoding::Utf16Le, 2));
+    }
+
+    else if document.len() > 3 && document[0] == 0 && document[1] == b'<' && document[2] == 0 {
+        return Ok((Encoding::Utf16Be, 0));
+    }
+
+    else if document.len() > 3 && document[0] == b'<' && document[1] == 0 && document[3] == 0 {
+        return Ok((Encoding::Utf16Le, 0));
+    }
+
+    if let Some(label) = get_declared_encoding(document) {
+
+        return match Encoding::from_label(&label) {
+            // the declaration is in ASCII bytes, so the document is not UTF-16
+            Some(Encoding::Utf16Be) | Some(Encoding::Utf16Le) => Err(HxmlError::new(
+                format!("The document declares {}, but it's not in UTF-16: it has no byte order mark", label),
+                usize::MAX
+            )),
+            Some(encoding) => Ok((encoding, 0)),
+            None => Err(HxmlError::new(format!("Unsupported encoding: {}", label), usize::MAX))
+        };
+
+    }
+
+    match get_meta_charset(document) {
+        Some(label) => match Encoding::from_label(&label) {
+            // HTML documents with `<meta charset='utf-16'>` are read as UTF-8, like browsers do
+            Some(Encoding::Utf16Be) | Some(Encoding::Utf16Le) => Ok((Encoding::Utf8, 0)),
+            Some(encoding) => Ok((encoding, 0)),
+            None => Err(HxmlError::new(format!("Unsupported encoding: {}", label), usize::MAX))
+        },
+        None => Ok((Encoding::Utf8, 0))
+    }
+
+}
+
+/// It detects the encoding of `document`, then transcodes it into a `String`.
+/// The BOM is not included in the result.
+pub fn decode_document(document: &[u8]) -> Result<String, HxmlError> {
+    let (encoding, bom_len) = detect_encoding(document)?;
+
+    decode(&document[bom_len..], encoding)
+}
+
+pub fn decode(bytes: &[u8], encoding: Encoding) -> Result<String, HxmlError> {
+
+    match encoding {
+        Encoding::Utf8 => match String::from_utf8(bytes.to_vec()) {
+            Ok(s) => Ok(s),
+            Err(e) => Err(HxmlError::new(
+                format!("Invalid UTF-8 sequence at byte {}", e.utf8_error().valid_up_to()),
+                usize::MAX
+            ))
+        },
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+
+            if bytes.len() % 2 == 1 {
+                return Err(HxmlError::new(String::from("A UTF-16 document has an odd number of bytes"), usize::MAX));
+            }
+
+            let units = bytes.chunks(2).map(
+                |c|
+                if encoding == Encoding::Utf16Le {
+                    u16::from_le_bytes([c[0], c[1]])
+                } else {
+                    u16::from_be_bytes([c[0], c[1]])
+                }
+            ).collect::<Vec<u16>>();
+
+            match String::from_utf16(&units) {
+                Ok(s) => Ok(s),
+                Err(_) => Err(HxmlError::new(String::from("Invalid UTF-16 sequence"), usize::MAX))
+            }
+
+        },
+        Encoding::Ascii => {
+
+            for (index, byte) in bytes.iter().enumerate() {
+
+                if *byte > 0x7f {
+                    return Err(HxmlError::new(format!("Non-ASCII byte 0x{:x} at byte {}", byte, index), usize::MAX));
+                }
+
+            }
+
+            Ok(bytes.iter().map(|b| *b as char).collect())
+        },
+        Encoding::Iso8859_1 => Ok(bytes.iter().map(|b| *b as char).collect()),
+        Encoding::Windows1252 => Ok(bytes.iter().map(
+            |b|
+            if 0x80 <= *b && *b <= 0x9f {
+                char::from_u32(WINDOWS_1252_HIGH[(*b - 0x80) as usize] as u32).unwrap()
+            } else {
+                *b as char
+            }
+        ).collect())
+    }
+
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum EncodeState {
+    Text,
+    Tag,
+    AttValue(char),
+    Comment,
+    CDSect,
+    PI,
+    Decl,
+}
+
+/// It encodes a serialized XML document.
+/// Characters that cannot be represented in `encoding` are replaced with numeric character references (`&#x...;`).
+/// It's an error if such characters appear where references are not allowed (names, comments, CDATA sections, PIs).
+/// UTF-16 outputs begin with a BOM.
+pub fn encode(document: &str, encoding: Encoding) -> Result<Vec<u8>, HxmlError> {
+    let mut result = Vec::with_capacity(document.len());
+    let mut state = EncodeState::Text;
+    let chars = document.chars().collect::<Vec<char>>();
+
+    match encoding {
+        Encoding::Utf16Le => { result.extend_from_slice(&[0xff, 0xfe]); },
+        Encoding::Utf16Be => { result.extend_from_slice(&[0xfe, 0xff]); },
+        _ => {}
+    }
+
+    for (index, c) in chars.iter().enumerate() {
+        let rest = &chars[index..];
+
+        state = match state {
+            EncodeState::Text if *c == '<' => if starts_with(rest, "<!--") {
+                EncodeState::Comment
+            } else if starts_with(rest, "<![CDATA[") {
+                EncodeState::CDSect
+            } else if starts_with(rest, "<?") {
+                EncodeState::PI
+            } else if starts_with(rest, "<!") {
+                EncodeState::Decl
+            } else {
+                EncodeState::Tag
+            },
+            EncodeState::Tag if *c == '"' || *c == '\'' => EncodeState::AttValue(*c),
+            EncodeState::AttValue(quote) if *c == quote => EncodeState::Tag,
+            _ => state
+        };
+
+        if is_representable(*c, encoding) {
+            encode_char(*c, encoding, &mut result);
+        }
+
+        else if state == EncodeState::Text || matches!(state, EncodeState::AttValue(_)) {
+
+            for c in format!("&#x{:x};", *c as u32).chars() {
+                encode_char(c, encoding, &mut result);
+            }
+
+        }
+
+        else {
+            return Err(HxmlError::new(
+                format!("`{}` cannot be represented in {} and it's not in char data or attribute values", c, encoding.to_label()),
+                usize::MAX
+            ));
+        }
+
+        // `index` points to the last character of the markup
+        state = match state {
+            EncodeState::Tag if *c == '>' => EncodeState::Text,
+            EncodeState::Comment if ends_with(&chars[..=index], "-->") => EncodeState::Text,
+            EncodeState::CDSect if ends_with(&chars[..=index], "]]>") => EncodeState::Text,
+            EncodeState::PI if ends_with(&chars[..=index], "?>") => EncodeState::Text,
+            EncodeState::Decl if *c == '>' => EncodeState::Text,
+            _ => state
+        };
+    }
+
+    Ok(result)
+}
+
+fn starts_with(chars: &[char], prefix: &str) -> bool {
+    chars.len() >= prefix.len() && prefix.chars().zip(chars.iter()).all(|(p, c)| p == *c)
+}
+
+fn ends_with(chars: &[char], suffix: &str) -> bool {
+    chars.len() >= suffix.len() && suffix.chars().rev().zip(chars.iter().rev()).all(|(s, c)| s == *c)
+}
+
+fn is_representable(c: char, encoding: Encoding) -> bool {
+
+    match encoding {
+        Encoding::Utf8 | Encoding::Utf16Le | Encoding::Utf16Be => true,
+        Encoding::Ascii => (c as u32) < 0x80,
+        Encoding::Iso8859_1 => (c as u32) < 0x100,
+        Encoding::Windows1252 => (c as u32) < 0x80 || (0xa0..0x100).contains(&(c as u32)) || (c as u32) < 0x10000 && WINDOWS_1252_HIGH.contains(&(c as u32 as u16)),
+    }
+
+}
+
+// it assumes that `c` is representable in `encoding`
+fn encode_char(c: char, encoding: Encoding, buffer: &mut Vec<u8>) {
+
+    match encoding {
+        Encoding::Utf8 => {
+            let mut bytes = [0; 4];
+            buffer.extend_from_slice(c.encode_utf8(&mut bytes).as_bytes());
+        },
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            let mut units = [0; 2];
+
+            for unit in c.encode_utf16(&mut units).iter() {
+
+                if encoding == Encoding::Utf16Le {
+                    buffer.extend_from_slice(&unit.to_le_bytes());
+                }
+
+                else {
+                    buffer.extend_from_slice(&unit.to_be_bytes());
+                }
+
+            }
+
+        },
+        Encoding::Ascii | Encoding::Iso8859_1 => {
+            buffer.push(c as u32 as u8);
+        },
+        Encoding::Windows1252 => {
+
+            match WINDOWS_1252_HIGH.iter().position(|h| *h as u32 == c as u32) {
+                Some(index) => { buffer.push(0x80 + index as u8); },
+                None => { buffer.push(c as u32 as u8); }
+            }
+
+        }
+    }
+
+}
+
+// `<?xml version='1.0' encoding='...'?>` at the beginning of an ASCII-compatible document
+fn get_declared_encoding(document: &[u8]) -> Option<String> {
+
+    if !document.starts_with(b"<?xml") {
+        return None;
+    }
+
+    let mut decl_end_index = 0;
+
+    while decl_end_index + 1 < document.len() && document[decl_end_index..(decl_end_index + 2)] != *b"?>" {
+        decl_end_index += 1;
+    }
+
+    // bytes in the declaration are ASCII, so it's safe to read them as UTF-16 code units
+    let decl = document[0..(decl_end_index + 2).min(document.len())].iter().map(|b| *b as u16).collect::<Vec<u16>>();
+
+    match get_xml_decl_end_index(&decl, 0) {
+        Some(_) => parse_xml_decl(&decl, 5).encoding,
+        None => None
+    }
+
+}
+
+// `<meta charset="...">` or `<meta http-equiv="Content-Type" content="text/html; charset=...">`
+fn get_meta_charset(document: &[u8]) -> Option<String> {
+    let head = document[0..document.len().min(1024)].to_ascii_lowercase();
+    let mut index = 0;
+
+    while index + 5 <= head.len() {
+
+        if &head[index..(index + 5)] != b"<meta" {
+            index += 1;
+            continue;
+        }
+
+        let mut tag_end_index = index;
+
+        while tag_end_index < head.len() && head[tag_end_index] != b'>' {
+            tag_end_index += 1;
+        }
+
+        let tag = &head[index..tag_end_index];
+        let mut curr_index = 0;
+
+        while curr_index + 7 <= tag.len() {
+
+            if &tag[curr_index..(curr_index + 7)] == b"charset" {
+                curr_index += 7;
+
+                while curr_index < tag.len() && (tag[curr_index] == b'=' || tag[curr_index] == b'"' || tag[curr_index] == b'\'' || tag[curr_index].is_ascii_whitespace()) {
+                    curr_index += 1;
+                }
+
+                let label_begin_index = curr_index;
+
+                while curr_index < tag.len() && (tag[curr_index].is_ascii_alphanumeric() || b"-_.:".contains(&tag[curr_index])) {
+                    curr_index += 1;
+                }
+
+                if curr_index > label_begin_index {
+                    // use the original bytes, not the lowercased ones
+                    return Some(String::from_utf8_lossy(&document[(index + label_begin_index)..(index + curr_index)]).to_string());
+                }
+
+            }
+
+            curr_index += 1;
+        }
+
+        index = tag_end_index;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dom;
+    use crate::encoding::*;
+
+    #[test]
+    fn detect_encoding_test() {
+        let test_cases = vec![
+            (b"<a/>".to_vec(), Some((Encoding::Utf8, 0))),
+            (b"\xef\xbb\xbf<a/>".to_vec(), Some((Encoding::Utf8, 3))),
+            (b"\xff\xfe<\x00a\x00/\x00>\x00".to_vec(), Some((Encoding::Utf16Le, 2))),
+            (b"\xfe\xff\x00<\x00a\x00/\x00>".to_vec(), Some((Encoding::Utf16Be, 2))),
+            (b"<\x00a\x00/\x00>\x00".to_vec(), Some((Encoding::Utf16Le, 0))),
+            (b"\x00<\x00a\x00/\x00>".to_vec(), Some((Encoding::Utf16Be, 0))),
+            (b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><a/>".to_vec(), Some((Encoding::Iso8859_1, 0))),
+            (b"<?xml version='1.0' encoding='Windows-1252' standalone='yes'?><a/>".to_vec(), Some((Encoding::Windows1252, 0))),
+            (b"<?xml version=\"1.0\"?><a/>".to_vec(), Some((Encoding::Utf8, 0))),
+            (b"<html><head><meta charset=\"latin1\"/></head></html>".to_vec(), Some((Encoding::Iso8859_1, 0))),
+            (b"<html><head><meta http-equiv=\"Content-Type\" content=\"text/html; charset=windows-1252\"/></head></html>".to_vec(), Some((Encoding::Windows1252, 0))),
+            (b"<?xml version=\"1.0\" encoding=\"Shift_JIS\"?><a/>".to_vec(), None),
+            (b"<html><head><meta charset=\"EUC-KR\"/></head></html>".to_vec(), None),
+            (b"<?xml version='1.0' encoding='UTF-16'?><a/>".to_vec(), None),
+            (b"<html><head><meta charset=\"utf-16\"/></head></html>".to_vec(), Some((Encoding::Utf8, 0))),
+        ];
+
+        for (case, answer) in test_cases.into_iter() {
+            assert_eq!(detect_encoding(&case).ok(), answer);
+        }
+
+    }
+
+    #[test]
+    fn decode_test() {
+        assert_eq!(decode(b"caf\xe9", Encoding::Iso8859_1).unwrap(), "café");
+        assert_eq!(decode(b"\x80 \x93quoted\x94", Encoding::Windows1252).unwrap(), "€ “quoted”");
+        assert_eq!(decode(b"\x00a\xd5\x5c", Encoding::Utf16Be).unwrap(), "a한");
+        assert_eq!(decode(b"a\x00\x5c\xd5", Encoding::Utf16Le).unwrap(), "a한");
+        assert!(decode(b"caf\xe9", Encoding::Ascii).is_err());
+        assert!(decode(b"caf\xe9", Encoding::Utf8).is_err());
+        assert!(decode(b"a\x00b", Encoding::Utf16Le).is_err());
+
+        assert_eq!(decode_document(b"\xef\xbb\xbf<a/>").unwrap(), "<a/>");
+        assert_eq!(decode_document(b"<?xml version='1.0' encoding='ISO-8859-1'?><a>\xe9</a>").unwrap(), "<?xml version='1.0' encoding='ISO-8859-1'?><a>é</a>");
+    }
+
+    #[test]
+    fn encode_test() {
+        assert_eq!(encode("<a title=\"café\">한</a>", Encoding::Ascii).unwrap(), b"<a title=\"caf&#xe9;\">&#xd55c;</a>");
+        assert_eq!(encode("<a title=\"café\">€한</a>", Encoding::Iso8859_1).unwrap(), b"<a title=\"caf\xe9\">&#x20ac;&#xd55c;</a>");
+        assert_eq!(encode("<a>€😀</a>", Encoding::Windows1252).unwrap(), b"<a>\x80&#x1f600;</a>");
+        assert_eq!(encode("<a>한</a>", Encoding::Utf16Le).unwrap(), b"\xff\xfe<\x00a\x00>\x00\x5c\xd5<\x00/\x00a\x00>\x00");
+        assert_eq!(encode("<a>한</a>", Encoding::Utf8).unwrap(), "<a>한</a>".as_bytes());
+
+        assert!(encode("<a><!-- 한 --></a>", Encoding::Ascii).is_err());
+        assert!(encode("<a><![CDATA[한]]></a>", Encoding::Ascii).is_err());
+        assert!(encode("<한/>", Encoding::Ascii).is_err());
+        assert_eq!(encode("<a><!-- a --><![CDATA[b]]>한</a>", Encoding::Ascii).unwrap(), b"<a><!-- a --><![CDATA[b]]>&#xd55c;</a>");
+
+        for encoding in [Encoding::Utf8, Encoding::Utf16Le, Encoding::Utf16Be, Encoding::Iso8859_1, Encoding::Windows1252] {
+            let document = "<a title='é'>é€</a>";
+            let encoded = encode(document, encoding).unwrap();
+            let bom_len = detect_encoding(&encoded).unwrap().1;
+
+            assert_eq!(
+                decode(&encoded[bom_len..], encoding).unwrap(),
+                if encoding == Encoding::Iso8859_1 { "<a title='é'>é&#x20ac;</a>" } else { document }
+            );
+        }
+
+    }
+
+    #[test]
+    fn parse_bytes_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        crate::parse_bytes(b"<?xml version='1.0' encoding='ISO-8859-1'?><p id=\"caf\xe9\">na\xefve</p>").unwrap();
+        let p = dom::get_element_by_id(None, "café".to_string()).unwrap();
+        assert_eq!(p.get_inner_string(), "naïve");
+
+        let utf16: Vec<u8> = vec![0xff, 0xfe].into_iter().chain(
+            "<?xml version='1.0' encoding='UTF-16'?><p>한글</p>".encode_utf16().flat_map(|c| c.to_le_bytes())
+        ).collect();
+        crate::parse_bytes(&utf16).unwrap();
+        assert_eq!(dom::get_element_by_tag_name(None, "p".to_string()).unwrap().get_inner_string(), "한글");
+
+        assert!(crate::parse_bytes(b"<?xml version='1.0' encoding='EBCDIC'?><p/>").is_err());
+        assert!(crate::parse_bytes(b"<?xml version='1.0' encoding='UTF-16'?><p/>").is_err());
+
+        crate::into_dom("<p title=\"café\">한글</p>".to_string()).unwrap();
+        let ascii = dom::to_bytes(crate::Encoding::Ascii).unwrap();
+        assert_eq!(ascii, b"<?xml version='1.0' encoding='US-ASCII'?><p title=\"caf&#xe9;\">&#xd55c;&#xae00;</p>");
+
+        let utf16 = dom::to_bytes(crate::Encoding::Utf16Be).unwrap();
+        crate::parse_bytes(&utf16).unwrap();
+        assert_eq!(dom::get_root().get_attribute("title".to_string()), Some("café".to_string()));
+        assert_eq!(dom::to_string(), "<p title=\"café\">한글</p>");
+
+        crate::parse_bytes(&ascii).unwrap();
+        assert_eq!(dom::get_root().get_attribute("title".to_string()), Some("café".to_string()));
+        assert_eq!(dom::to_bytes(crate::Encoding::Ascii).unwrap(), ascii);
+
+        drop(lock);
+    }
+
+}
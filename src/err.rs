@@ -83,6 +83,14 @@ impl std::fmt::Display for HxmlError {
 
 }
 
+impl From<std::io::Error> for HxmlError {
+
+    fn from(e: std::io::Error) -> Self {
+        HxmlError::new(format!("IO error: {}", e), usize::MAX)
+    }
+
+}
+
 pub static mut ERRORS: Vec<HxmlError> = vec![];
 
 pub fn reset_errors() {
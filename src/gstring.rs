@@ -1,4 +1,5 @@
 use crate::utils::from_v16;
+use std::ptr::addr_of;
 
 #[derive(Copy, Clone, Debug)]
 pub struct GString {
@@ -8,10 +9,45 @@ pub struct GString {
 
 pub static mut GLOBAL_STRING: Vec<u16> = vec![];
 
+// the indices where the lines of the global string start, for `get_line_column`
+// It's built lazily, and `set_global_string` invalidates it.
+static mut LINE_STARTS: Option<Vec<usize>> = None;
+
 pub fn set_global_string(string: Vec<u16>) {
 
     unsafe {
         GLOBAL_STRING = string;
+        LINE_STARTS = None;
+    }
+
+}
+
+// 1-based line and column of `index` of the global string. Columns are counted in UTF-16 units.
+pub fn get_line_column(index: usize) -> (usize, usize) {
+
+    unsafe {
+
+        if (*addr_of!(LINE_STARTS)).is_none() {
+            let mut line_starts = vec![0];
+
+            for (ind, c) in (*addr_of!(GLOBAL_STRING)).iter().enumerate() {
+
+                if *c == '\n' as u16 {
+                    line_starts.push(ind + 1);
+                }
+
+            }
+
+            LINE_STARTS = Some(line_starts);
+        }
+
+        let line_starts = (*addr_of!(LINE_STARTS)).as_ref().unwrap();
+        let line = match line_starts.binary_search(&index) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        };
+
+        (line + 1, index - line_starts[line] + 1)
     }
 
 }
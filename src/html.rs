@@ -0,0 +1,70 @@
+// Element categories of HTML
+// https://html.spec.whatwg.org/multipage/syntax.html#elements-2
+
+pub const VOID_ELEMENTS: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input",
+    "link", "meta", "param", "source", "track", "wbr",
+];
+
+// elements whose whitespaces are significant
+pub const WHITESPACE_SENSITIVE_ELEMENTS: [&str; 4] = ["pre", "textarea", "script", "style"];
+
+// elements whose contents are not escaped
+// https://html.spec.whatwg.org/multipage/syntax.html#raw-text-elements
+pub const RAW_TEXT_ELEMENTS: [&str; 2] = ["script", "style"];
+
+// https://html.spec.whatwg.org/multipage/indices.html#attributes-3
+pub const BOOLEAN_ATTRIBUTES: [&str; 26] = [
+    "allowfullscreen", "async", "autofocus", "autoplay", "checked", "controls", "default", "defer",
+    "disabled", "formnovalidate", "hidden", "inert", "ismap", "itemscope", "loop", "multiple",
+    "muted", "nomodule", "novalidate", "open", "playsinline", "readonly", "required", "reversed",
+    "selected", "shadowrootdelegatesfocus",
+];
+
+// elements that are not rendered inline: whitespaces around them are not rendered
+pub const BLOCK_ELEMENTS: [&str; 49] = [
+    "address", "article", "aside", "blockquote", "body", "caption", "dd", "details",
+    "dialog", "div", "dl", "dt", "fieldset", "figcaption", "figure", "footer",
+    "form", "h1", "h2", "h3", "h4", "h5", "h6", "head",
+    "header", "hgroup", "hr", "html", "li", "link", "main", "meta",
+    "nav", "ol", "p", "pre", "script", "section", "style", "summary",
+    "table", "tbody", "td", "tfoot", "th", "thead", "title", "tr",
+    "ul",
+];
+
+// elements that are not rendered (`display: none` in the default style sheet)
+// https://html.spec.whatwg.org/multipage/rendering.html#hidden-elements
+pub const HIDDEN_ELEMENTS: [&str; 13] = [
+    "area", "base", "datalist", "head", "link", "meta", "noscript", "param",
+    "rp", "script", "style", "template", "title",
+];
+
+#[inline]
+pub fn is_void_element(tag_name: &str) -> bool {
+    VOID_ELEMENTS.contains(&tag_name.to_ascii_lowercase().as_str())
+}
+
+#[inline]
+pub fn is_whitespace_sensitive_element(tag_name: &str) -> bool {
+    WHITESPACE_SENSITIVE_ELEMENTS.contains(&tag_name.to_ascii_lowercase().as_str())
+}
+
+#[inline]
+pub fn is_block_element(tag_name: &str) -> bool {
+    BLOCK_ELEMENTS.contains(&tag_name.to_ascii_lowercase().as_str())
+}
+
+#[inline]
+pub fn is_raw_text_element(tag_name: &str) -> bool {
+    RAW_TEXT_ELEMENTS.contains(&tag_name.to_ascii_lowercase().as_str())
+}
+
+#[inline]
+pub fn is_boolean_attribute(name: &str) -> bool {
+    BOOLEAN_ATTRIBUTES.contains(&name.to_ascii_lowercase().as_str())
+}
+
+#[inline]
+pub fn is_hidden_element(tag_name: &str) -> bool {
+    HIDDEN_ELEMENTS.contains(&tag_name.to_ascii_lowercase().as_str())
+}
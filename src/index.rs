@@ -0,0 +1,373 @@
+// Secondary indices that users register on a document, in addition to the built-in indices of tag names, ids and classes.
+// Like the built-in indices, they have every alive element, whether it's in the document tree or not.
+
+use crate::dom::{self, ElementSet};
+use crate::err::HxmlError;
+use crate::node::memory;
+use crate::node::pointer::ElementPtr;
+use std::collections::HashMap;
+use std::ptr::{addr_of, addr_of_mut};
+
+// IT'S NOT THREAD-SAFE!!!!
+static mut CUSTOM_INDICES: Vec<CustomIndex> = vec![];
+
+/// What a custom index is keyed on. See `dom::create_index`.
+pub enum IndexKey {
+
+    /// The value of the attribute. Elements without the attribute are not indexed.
+    Attribute(String),
+
+    /// Keys returned by `keys`. An element is indexed under each of the keys, like classes.
+    /// The function must not mutate the dom or use the indices.
+    ///
+    /// If `depends_on_descendants` is false, the keys must depend only on the element itself: its tag name, attributes and contents,
+    /// but not what's inside its child elements. A mutation re-runs `keys` on the mutated element only.
+    ///
+    /// If it's true, the keys may depend on the whole subtree (eg. `get_inner_string`). Then every mutation re-runs `keys`
+    /// on every ancestor of the mutated element, up to the root, which makes mutations in a large document expensive.
+    Function {
+        keys: Box<dyn Fn(ElementPtr) -> Vec<String>>,
+        depends_on_descendants: bool,
+    },
+}
+
+impl IndexKey {
+
+    fn get_keys(&self, element: ElementPtr) -> Vec<String> {
+        let mut keys = match self {
+            IndexKey::Attribute(attribute) => element.get_attribute(attribute.to_string()).into_iter().collect(),
+            IndexKey::Function { keys, .. } => keys(element),
+        };
+
+        let mut ind = 0;
+
+        while ind < keys.len() {
+
+            if keys[..ind].contains(&keys[ind]) {
+                keys.remove(ind);
+            }
+
+            else {
+                ind += 1;
+            }
+
+        }
+
+        keys
+    }
+
+}
+
+struct CustomIndex {
+    name: String,
+    key: IndexKey,
+    elements_by_key: HashMap<String, ElementSet>,
+
+    // the keys that each element is indexed under, so that an element can be removed even after its keys have changed
+    keys_by_element: HashMap<usize, Vec<String>>,
+}
+
+impl CustomIndex {
+
+    fn insert(&mut self, element: ElementPtr) {
+        let keys = self.key.get_keys(element);
+
+        for key in keys.iter() {
+            self.elements_by_key.entry(key.to_string()).or_default().insert(element);
+        }
+
+        self.keys_by_element.insert(element.ptr, keys);
+    }
+
+    fn remove(&mut self, element: ElementPtr) {
+        let keys = match self.keys_by_element.remove(&element.ptr) {
+            Some(keys) => keys,
+            None => {
+                return;
+            }
+        };
+
+        for key in keys.iter() {
+            let tags = self.elements_by_key.get_mut(key).unwrap();
+            tags.remove(element);
+
+            if tags.is_empty() {
+                self.elements_by_key.remove(key);
+            }
+
+        }
+
+    }
+
+    fn update(&mut self, element: ElementPtr) {
+
+        match self.keys_by_element.get(&element.ptr) {
+            Some(keys) if keys == &self.key.get_keys(element) => {}
+            _ => {
+                self.remove(element);
+                self.insert(element);
+            }
+        }
+
+    }
+
+}
+
+// `into_dom` calls this: indices are registered on a document
+pub fn reset() {
+    unsafe {
+        CUSTOM_INDICES = vec![];
+    }
+}
+
+pub fn create(name: &str, key: IndexKey) -> Result<(), HxmlError> {
+
+    unsafe {
+
+        if (*addr_of!(CUSTOM_INDICES)).iter().any(|index| index.name == name) {
+            return Err(HxmlError::new(format!("index `{}` already exists", name), usize::MAX));
+        }
+
+        let mut index = CustomIndex {
+            name: name.to_string(),
+            key,
+            elements_by_key: HashMap::new(),
+            keys_by_element: HashMap::new(),
+        };
+
+        for element in dom::get_all_elements().into_iter() {
+            index.insert(element);
+        }
+
+        (*addr_of_mut!(CUSTOM_INDICES)).push(index);
+    }
+
+    Ok(())
+}
+
+pub fn drop(name: &str) -> bool {
+
+    unsafe {
+
+        match (*addr_of!(CUSTOM_INDICES)).iter().position(|index| index.name == name) {
+            Some(ind) => {
+                (*addr_of_mut!(CUSTOM_INDICES)).remove(ind);
+                true
+            }
+            None => false
+        }
+
+    }
+
+}
+
+// None if there's no index named `name`
+pub fn get(name: &str, key: &str) -> Option<Vec<ElementPtr>> {
+
+    unsafe {
+
+        for index in (*addr_of!(CUSTOM_INDICES)).iter() {
+
+            if index.name == name {
+                return Some(get_elements(index, key));
+            }
+
+        }
+
+    }
+
+    None
+}
+
+// None if there's no index on `attribute`
+pub fn get_by_attribute(attribute: &str, value: &str) -> Option<Vec<ElementPtr>> {
+
+    unsafe {
+
+        for index in (*addr_of!(CUSTOM_INDICES)).iter() {
+
+            if matches!(&index.key, IndexKey::Attribute(a) if a == attribute) {
+                return Some(get_elements(index, value));
+            }
+
+        }
+
+    }
+
+    None
+}
+
+fn get_elements(index: &CustomIndex, key: &str) -> Vec<ElementPtr> {
+
+    match index.elements_by_key.get(key) {
+        Some(v) => v.as_slice().to_vec(),
+        None => vec![]
+    }
+
+}
+
+pub fn insert_element(element: ElementPtr) {
+    unsafe {
+
+        for index in (*addr_of_mut!(CUSTOM_INDICES)).iter_mut() {
+            index.insert(element);
+        }
+
+    }
+}
+
+pub fn remove_element(element: ElementPtr) {
+    unsafe {
+
+        for index in (*addr_of_mut!(CUSTOM_INDICES)).iter_mut() {
+            index.remove(element);
+        }
+
+    }
+}
+
+// It's called when the attributes or the contents of `element` have changed.
+// Keys of a function may depend on the descendants if it's asked to, then the ancestors are updated too.
+pub fn update_element(element: ElementPtr) {
+    unsafe {
+
+        for index in (*addr_of_mut!(CUSTOM_INDICES)).iter_mut() {
+            index.update(element);
+
+            if let IndexKey::Function { depends_on_descendants: true, .. } = index.key {
+                let mut curr_element = element.get_parent();
+
+                while let Some(ancestor) = curr_element {
+                    index.update(ancestor);
+                    curr_element = ancestor.get_parent();
+                }
+
+            }
+
+        }
+
+    }
+}
+
+// It recomputes the keys of every alive element and compares them with the indices.
+pub fn check() -> Result<(), String> {
+    let all_elements = dom::get_all_elements();
+
+    unsafe {
+
+        for index in (*addr_of!(CUSTOM_INDICES)).iter() {
+
+            if index.keys_by_element.len() != all_elements.len() {
+                return Err(format!("the index `{}` has {} elements, but there are {}!", index.name, index.keys_by_element.len(), all_elements.len()));
+            }
+
+            for element in all_elements.iter() {
+                let keys = index.key.get_keys(*element);
+
+                if index.keys_by_element.get(&element.ptr) != Some(&keys) {
+                    return Err(format!("the keys of {:?} in the index `{}` are outdated!", element, index.name));
+                }
+
+                for key in keys.iter() {
+
+                    if !index.elements_by_key.get(key).is_some_and(|tags| tags.contains(*element)) {
+                        return Err(format!("{:?} is missing in the index `{}` of `{}`!", element, index.name, key));
+                    }
+
+                }
+
+            }
+
+            for (key, tags) in index.elements_by_key.iter() {
+
+                for tag in tags.as_slice().iter() {
+
+                    if !memory::get(tag.ptr).is_alive {
+                        return Err(format!("{:?} is deleted, but it's in the index `{}` of `{}`!", tag, index.name, key));
+                    }
+
+                }
+
+            }
+
+        }
+
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dom;
+
+    #[test]
+    fn custom_index_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        crate::into_dom("<items><item key=\"a\">x</item><item key=\"b\">y</item><item>x</item></items>".to_string()).unwrap();
+        let items = dom::get_elements_by_tag_name(None, "item".to_string());
+
+        dom::create_index("key", crate::IndexKey::Attribute("key".to_string())).unwrap();
+        dom::create_index("text", crate::IndexKey::Function { keys: Box::new(|e| vec![e.get_inner_string()]), depends_on_descendants: true }).unwrap();
+        dom::create_index("upper", crate::IndexKey::Function {
+            keys: Box::new(|e| e.get_attribute("key".to_string()).into_iter().map(|key| key.to_uppercase()).collect()),
+            depends_on_descendants: false,
+        }).unwrap();
+        assert!(dom::create_index("key", crate::IndexKey::Attribute("ref".to_string())).is_err());
+        assert!(dom::get_elements_by_index("ref", "a").is_err());
+
+        assert_eq!(dom::get_elements_by_index("key", "a").unwrap(), vec![items[0]]);
+        assert_eq!(dom::get_elements_by_attribute("key", "b"), vec![items[1]]);
+        assert_eq!(dom::get_elements_by_index("text", "x").unwrap().len(), 2);
+        assert_eq!(dom::query_selector_all("[key=b]").unwrap(), vec![items[1]]);
+
+        // attributes
+        items[2].set_attribute("key".to_string(), "a".to_string());
+        items[0].set_attribute("key".to_string(), "c".to_string());
+        assert_eq!(dom::get_elements_by_index("key", "a").unwrap(), vec![items[2]]);
+        assert_eq!(dom::get_elements_by_index("key", "c").unwrap(), vec![items[0]]);
+        assert_eq!(dom::get_elements_by_index("upper", "C").unwrap(), vec![items[0]]);
+        dom::check_indices().unwrap();
+
+        // contents
+        items[1].add_char_data("z".to_string());
+        assert_eq!(dom::get_elements_by_index("text", "yz").unwrap(), vec![items[1]]);
+        assert_eq!(dom::get_elements_by_index("text", "y").unwrap(), vec![]);
+        dom::check_indices().unwrap();
+
+        let contents = crate::Content::from_string("<item key=\"d\">w</item>".to_string()).unwrap();
+        let root = dom::get_root();
+        root.add_contents(contents);
+        let new_item = dom::get_elements_by_index("key", "d").unwrap()[0];
+        assert_eq!(new_item.get_parent(), Some(root));
+        assert_eq!(dom::get_elements_by_index("text", "w").unwrap(), vec![new_item]);
+        dom::check_indices().unwrap();
+
+        dom::delete(items[0]);
+        assert_eq!(dom::get_elements_by_index("key", "c").unwrap(), vec![]);
+        assert_eq!(dom::get_elements_by_index("text", "x").unwrap(), vec![items[2]]);
+        dom::check_indices().unwrap();
+
+        assert!(dom::drop_index("key"));
+        assert!(!dom::drop_index("key"));
+        assert_eq!(dom::get_elements_by_attribute("key", "a"), vec![items[2]]);
+
+        // indices belong to a document
+        crate::into_dom("<items/>".to_string()).unwrap();
+        assert!(dom::get_elements_by_index("text", "x").is_err());
+
+        // removing elements from the middle of a key keeps the others
+        crate::into_dom(format!("<items>{}</items>", "<item key=\"a\"/>".repeat(5))).unwrap();
+        dom::create_index("key", crate::IndexKey::Attribute("key".to_string())).unwrap();
+        let items = dom::get_elements_by_tag_name(None, "item".to_string());
+        items[1].set_attribute("key".to_string(), "b".to_string());
+        dom::delete(items[3]);
+        dom::delete(items[4]);
+        assert_eq!(dom::get_elements_by_index("key", "a").unwrap(), vec![items[0], items[2]]);
+        dom::check_indices().unwrap();
+
+        drop(lock);
+    }
+
+}
@@ -1,60 +1,125 @@
 pub mod dom;
+mod c14n;
+#[cfg(feature = "serde")]
+mod de;
+mod encoding;
 mod err;
 mod gstring;
+mod html;
+mod index;
+pub mod mapping;
+mod minify;
 mod node;
+mod order;
 mod predicate;
 mod parse;
+mod pretty;
+mod selector;
+#[cfg(feature = "serde")]
+mod ser;
+mod serialize;
+mod text;
 mod utils;
+mod writer;
+mod xpath;
 
 #[cfg(test)]
 mod testbench;
 
+pub use c14n::{C14nMethod, C14nOptions};
+#[cfg(feature = "serde")]
+pub use de::{from_element, from_str};
+pub use encoding::Encoding;
+#[cfg(feature = "derive")]
+pub use hxml_derive::{FromXml, ToXml};
 pub use err::HxmlError;
+pub use index::IndexKey;
+pub use mapping::{FromXml, FromXmlText, ToXml, ToXmlText};
+pub use minify::{CommentPolicy, Minified, MinifyOptions};
 pub use node::attribute::Attribute;
+pub use node::clone::{CloneOptions, DuplicateIds};
 pub use node::element::{Content, Element};
-pub use node::pointer::ElementPtr;
+pub use node::fragment::DocumentFragment;
+pub use node::iter::{Ancestors, Children, ContentNode, DescendantContents, Descendants, PostOrderDescendants, Siblings};
+pub use node::pointer::{AdjacentPosition, ElementPtr};
 pub use node::prolog::Prolog;
+pub use parse::ParseOptions;
+pub use pretty::{Indent, PrettyOptions, SelfClosing};
+#[cfg(feature = "serde")]
+pub use ser::{to_element, to_element_with_options, to_string, to_string_with_options, SerializeOptions};
+pub use writer::XmlWriter;
+pub use xpath::{XPath, XPathNode, XPathValue};
 
 use err::{reset_errors, read_errors};
 use gstring::set_global_string;
 use node::memory;
-use utils::into_v16;
-use parse::{get_prolog_end_index, parse_element, parse_prolog};
+use utils::{from_v16, into_v16, normalize_line_endings};
+use parse::{get_prolog_end_index, parse_element, parse_prolog, set_parse_options};
 use std::collections::HashMap;
 
 /// It's global.
 /// You can't handle multiple doms at once.
 /// It frees all the elements created before.
 pub fn into_dom(document: String) -> Result<(), HxmlError> {
+    into_dom_with_options(document, ParseOptions::default())
+}
+
+/// It's like `into_dom`, but you can configure the normalizations.
+pub fn into_dom_with_options(document: String, options: ParseOptions) -> Result<(), HxmlError> {
     memory::init();
 
     unsafe {
+        dom::ROOT = None;
         dom::PROLOG = None;
+        dom::EPILOG = None;
         dom::TAGS_BY_NAME = Some(HashMap::new());
         dom::TAGS_BY_ID = Some(HashMap::new());
         dom::TAGS_BY_CLASS = Some(HashMap::new());
     }
 
-    let document = into_v16(&document);
+    index::reset();
+
+    let mut document = into_v16(&document);
+
+    if options.normalize_line_endings {
+        document = normalize_line_endings(&document);
+    }
+
+    let preserve_format = options.preserve_format;
+    set_parse_options(options.clone());
     set_global_string(document.clone());
     reset_errors();
 
     let mut curr_index = 0;
+    let mut prolog = None;
 
-    match get_prolog_end_index(&document, curr_index) {
-        Some(end_index) => {
-            unsafe {
-                dom::PROLOG = Some(parse_prolog(&document, curr_index).0);
-            }
-            curr_index = end_index + 1;
-        },
-        None => {}
+    if let Some(end_index) = get_prolog_end_index(&document, curr_index) {
+        let mut curr_prolog = parse_prolog(&document, curr_index).0;
+
+        if preserve_format {
+            curr_prolog.source = Some(from_v16(&document[curr_index..(end_index + 1)]));
+        }
+
+        prolog = Some(curr_prolog);
+        curr_index = end_index + 1;
     }
 
     match parse_element(&document, curr_index) {
-        Some((element, _)) => {
-            let result = element.to_real();
+        Some((element, end_index)) => {
+            let result = element.to_real(&options, prolog.as_ref().and_then(|prolog| prolog.doctype_decl.as_ref()));
             result.set_parent_recursive();
+
+            unsafe {
+                dom::ROOT = Some(result);
+                dom::PROLOG = prolog;
+            }
+
+            if preserve_format {
+                unsafe {
+                    dom::EPILOG = Some(from_v16(&document[(end_index + 1)..]));
+                }
+            }
+
             return Ok(());
         },
         None => {}
@@ -73,6 +138,13 @@ pub fn into_dom(document: String) -> Result<(), HxmlError> {
     return Err(HxmlError::new(String::from("Unexpected Eof!"), usize::MAX));
 }
 
+/// It's like `into_dom`, but it takes raw bytes.
+/// The encoding is detected by the BOM, `<?xml encoding='...'?>` or `<meta charset='...'>`, in that order.
+/// Supported encodings are UTF-8, UTF-16LE, UTF-16BE, US-ASCII, ISO-8859-1 and Windows-1252.
+pub fn parse_bytes(document: &[u8]) -> Result<(), HxmlError> {
+    into_dom(encoding::decode_document(document)?)
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs::File;
@@ -86,9 +158,7 @@ mod tests {
 
         f.read_to_string(&mut s).unwrap();
 
-        let lock = unsafe {
-            dom::LOCK.lock().unwrap()
-        };
+        let lock = dom::LOCK.lock().unwrap();
 
         crate::into_dom(s).unwrap();
         dom::some_checks().unwrap();
@@ -96,4 +166,4 @@ mod tests {
         drop(lock);
     }
 
-}
\ No newline at end of file
+}
@@ -0,0 +1,506 @@
+// Typed mapping between elements and Rust values: `FromXml` and `ToXml`, which `hxml-derive` implements.
+// The functions in this module are what the derived code calls, and they can be used in hand-written impls too.
+// Errors have the path of the element (eg. `/config/server[2]/@port`) and the line and column where it's parsed.
+
+use crate::dom;
+use crate::err::HxmlError;
+use crate::node::attribute::Attribute;
+use crate::node::element::{Content, Element};
+use crate::node::memory;
+use crate::node::pointer::ElementPtr;
+use crate::text::get_own_text;
+use crate::xpath::XPathNode;
+
+/// A value that's read from an element.
+pub trait FromXml: Sized {
+
+    /// It reads `element` and its descendants. The tag name is not checked: the caller chooses the element.
+    fn from_xml(element: ElementPtr) -> Result<Self, HxmlError>;
+
+    /// Is `element` named like this type? For enums, it's whether a variant matches the element.
+    fn matches(element: ElementPtr) -> bool;
+
+    /// It parses `s`, which has exactly one element (and maybe whitespaces and comments around it), checks the element with `matches`,
+    /// and deletes the elements afterwards.
+    fn from_xml_str(s: &str) -> Result<Self, HxmlError> {
+        let elements = Content::from_string(s.to_string())?.into_iter().filter_map(
+            |content| match content {
+                Content::Element(element) => Some(element),
+                _ => None
+            }
+        ).collect::<Vec<ElementPtr>>();
+
+        let result = if elements.len() != 1 {
+            Err(HxmlError::new(format!("expected one element, found {}", elements.len()), usize::MAX))
+        } else if Self::matches(elements[0]) {
+            Self::from_xml(elements[0])
+        } else {
+            Err(error(elements[0], "", &format!("unexpected element <{}>", elements[0].get_tag_name())))
+        };
+
+        for element in elements.into_iter() {
+            dom::delete(element);
+        }
+
+        result
+    }
+
+}
+
+/// A value that's written as an element.
+pub trait ToXml {
+
+    /// It allocates a new element without a parent. `tag_name` overrides the name of the type.
+    /// Enums ignore `tag_name`: a variant is written as its own element.
+    fn to_xml_element(&self, tag_name: Option<&str>) -> Result<ElementPtr, HxmlError>;
+
+    /// It doesn't touch the current document: the element is built, written and deleted.
+    fn to_xml_string(&self) -> Result<String, HxmlError> {
+        let element = self.to_xml_element(None)?;
+        let result = element.to_xml();
+        dom::delete(element);
+
+        result
+    }
+
+}
+
+/// A value in an attribute or a text. The error is a message without a location: the caller adds it.
+pub trait FromXmlText: Sized {
+    fn from_xml_text(text: &str) -> Result<Self, String>;
+}
+
+pub trait ToXmlText {
+    fn to_xml_text(&self) -> String;
+}
+
+impl FromXmlText for String {
+
+    fn from_xml_text(text: &str) -> Result<Self, String> {
+        Ok(text.to_string())
+    }
+
+}
+
+// https://www.w3.org/TR/xmlschema-2/#boolean
+impl FromXmlText for bool {
+
+    fn from_xml_text(text: &str) -> Result<Self, String> {
+
+        match text.trim() {
+            "true" | "1" => Ok(true),
+            "false" | "0" => Ok(false),
+            _ => Err(format!("cannot parse `{}` as bool", text))
+        }
+
+    }
+
+}
+
+impl ToXmlText for String {
+
+    fn to_xml_text(&self) -> String {
+        self.clone()
+    }
+
+}
+
+impl ToXmlText for str {
+
+    fn to_xml_text(&self) -> String {
+        self.to_string()
+    }
+
+}
+
+impl ToXmlText for bool {
+
+    fn to_xml_text(&self) -> String {
+        self.to_string()
+    }
+
+}
+
+fn parse_text<T: std::str::FromStr>(text: &str, type_name: &str) -> Result<T, String> where T::Err: std::fmt::Display {
+
+    match text.trim().parse::<T>() {
+        Ok(result) => Ok(result),
+        Err(e) => Err(format!("cannot parse `{}` as {}: {}", text, type_name, e))
+    }
+
+}
+
+// numbers and chars are trimmed before parsing
+macro_rules! impl_text {
+    ($($type:ty)*) => {
+        $(
+            impl FromXmlText for $type {
+
+                fn from_xml_text(text: &str) -> Result<Self, String> {
+                    parse_text(text, stringify!($type))
+                }
+
+            }
+
+            impl ToXmlText for $type {
+
+                fn to_xml_text(&self) -> String {
+                    self.to_string()
+                }
+
+            }
+        )*
+    };
+}
+
+impl_text! { i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize f32 f64 char }
+
+// a text value as an element, eg. `<port>80</port>`
+// It has no name of its own: it needs a tag name from the caller, and it matches no elements.
+macro_rules! impl_text_element {
+    ($($type:ty)*) => {
+        $(
+            impl FromXml for $type {
+
+                fn from_xml(element: ElementPtr) -> Result<Self, HxmlError> {
+                    read_text(element)
+                }
+
+                fn matches(_element: ElementPtr) -> bool {
+                    false
+                }
+
+            }
+
+            impl ToXml for $type {
+
+                fn to_xml_element(&self, tag_name: Option<&str>) -> Result<ElementPtr, HxmlError> {
+
+                    match tag_name {
+                        Some(tag_name) => {
+                            let element = new_element(tag_name, None);
+                            write_text(element, self);
+
+                            Ok(element)
+                        }
+                        None => Err(HxmlError::new(format!("{} needs a tag name to be written as an element", stringify!($type)), usize::MAX))
+                    }
+
+                }
+
+            }
+        )*
+    };
+}
+
+impl_text_element! { String bool i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize f32 f64 char }
+
+// `/a/b[2]/c`: an index is added if the parent has multiple children with the same tag name
+pub(crate) fn get_path(element: ElementPtr) -> String {
+    let mut names = vec![];
+    let mut curr_element = Some(element);
+
+    while let Some(element) = curr_element {
+        let tag_name = element.get_tag_name();
+
+        match element.get_parent() {
+            Some(parent) => {
+                let siblings = parent.children().filter(|child| child.get_tag_name() == tag_name).collect::<Vec<ElementPtr>>();
+
+                if siblings.len() > 1 {
+                    let index = siblings.iter().position(|sibling| *sibling == element).unwrap();
+                    names.push(format!("{}[{}]", tag_name, index + 1));
+                }
+
+                else {
+                    names.push(tag_name);
+                }
+
+            }
+            None => {
+                names.push(tag_name);
+            }
+        }
+
+        curr_element = element.get_parent();
+    }
+
+    names.reverse();
+
+    format!("/{}", names.join("/"))
+}
+
+// `{message} at `{path}` (line 3, column 5)`
+pub(crate) fn locate_message(element: ElementPtr, path: &str, message: &str) -> String {
+    let location = match element.get_source_location() {
+        Some((line, column)) => format!(" (line {}, column {})", line, column),
+        None => String::new()
+    };
+
+    format!("{} at `{}`{}", message, path, location)
+}
+
+/// an error at `element`, with its path and location
+/// `suffix` is appended to the path, eg. `/@port`.
+pub fn error(element: ElementPtr, suffix: &str, message: &str) -> HxmlError {
+    HxmlError::new(locate_message(element, &format!("{}{}", get_path(element), suffix), message), usize::MAX)
+}
+
+/// If `namespace` is None, it compares the tag name. Otherwise, it compares the local name and the namespace URI.
+pub fn matches_name(element: ElementPtr, name: &str, namespace: Option<&str>) -> bool {
+
+    match namespace {
+        Some(namespace) => {
+            let node = XPathNode::Element(element);
+            node.get_local_name() == name && node.get_namespace_uri() == namespace
+        }
+        None => element.get_tag_name() == name
+    }
+
+}
+
+pub fn read_attribute<T: FromXmlText>(element: ElementPtr, name: &str) -> Result<Option<T>, HxmlError> {
+
+    match element.get_attribute(name.to_string()) {
+        Some(value) => match T::from_xml_text(&value) {
+            Ok(result) => Ok(Some(result)),
+            Err(message) => Err(error(element, &format!("/@{}", name), &message))
+        },
+        None => Ok(None)
+    }
+
+}
+
+/// the text of the element itself (char data, CDATA sections and references), without its descendants
+pub fn read_text<T: FromXmlText>(element: ElementPtr) -> Result<T, HxmlError> {
+
+    match T::from_xml_text(&get_own_text(memory::get(element.ptr))) {
+        Ok(result) => Ok(result),
+        Err(message) => Err(error(element, "/text()", &message))
+    }
+
+}
+
+/// `what` is how the missing value is called in the error, eg. "attribute `port`".
+pub fn require<T>(element: ElementPtr, value: Option<T>, what: &str) -> Result<T, HxmlError> {
+
+    match value {
+        Some(value) => Ok(value),
+        None => Err(error(element, "", &format!("missing {}", what)))
+    }
+
+}
+
+/// the child elements named `name`, see `matches_name`
+pub fn get_children(element: ElementPtr, name: &str, namespace: Option<&str>) -> Vec<ElementPtr> {
+    element.children().filter(|child| matches_name(*child, name, namespace)).collect()
+}
+
+/// the child elements that `T` matches
+pub fn get_matching_children<T: FromXml>(element: ElementPtr) -> Vec<ElementPtr> {
+    element.children().filter(|child| T::matches(*child)).collect()
+}
+
+/// It reads exactly one of `children`, which are the children of `element`.
+pub fn read_one<T: FromXml>(element: ElementPtr, children: Vec<ElementPtr>, what: &str) -> Result<T, HxmlError> {
+
+    match children.len() {
+        1 => T::from_xml(children[0]),
+        n => Err(error(element, "", &format!("expected one {}, found {}", what, n)))
+    }
+
+}
+
+pub fn read_optional<T: FromXml>(element: ElementPtr, children: Vec<ElementPtr>, what: &str) -> Result<Option<T>, HxmlError> {
+
+    match children.len() {
+        0 => Ok(None),
+        1 => Ok(Some(T::from_xml(children[0])?)),
+        n => Err(error(element, "", &format!("expected at most one {}, found {}", what, n)))
+    }
+
+}
+
+pub fn read_many<T: FromXml>(children: Vec<ElementPtr>) -> Result<Vec<T>, HxmlError> {
+    let mut result = Vec::with_capacity(children.len());
+
+    for child in children.into_iter() {
+        result.push(T::from_xml(child)?);
+    }
+
+    Ok(result)
+}
+
+/// an element without contents, which declares `namespace` as the default namespace
+pub fn new_element(tag_name: &str, namespace: Option<&str>) -> ElementPtr {
+    let attributes = match namespace {
+        Some(namespace) => vec![Attribute::new(String::from("xmlns"), namespace.to_string())],
+        None => vec![]
+    };
+
+    Element::new(tag_name.to_string(), attributes, true, vec![])
+}
+
+/// It runs `f`, which fills `element`. If `f` fails, `element` is deleted.
+pub fn build<F: FnOnce() -> Result<(), HxmlError>>(element: ElementPtr, f: F) -> Result<ElementPtr, HxmlError> {
+
+    match f() {
+        Ok(()) => Ok(element),
+        Err(e) => {
+            dom::delete(element);
+            Err(e)
+        }
+    }
+
+}
+
+pub fn write_attribute<T: ToXmlText + ?Sized>(element: ElementPtr, name: &str, value: &T) {
+    element.set_attribute(name.to_string(), value.to_xml_text());
+}
+
+pub fn write_text<T: ToXmlText + ?Sized>(element: ElementPtr, value: &T) {
+    let text = value.to_xml_text();
+
+    if !text.is_empty() {
+        element.add_char_data(text);
+    }
+
+}
+
+/// It appends `value` to `element`. `tag_name` and `namespace` override the ones of the type.
+/// A declaration of the default namespace is omitted if it's the same as the one of `element`.
+pub fn write_child<T: ToXml + ?Sized>(element: ElementPtr, value: &T, tag_name: Option<&str>, namespace: Option<&str>) -> Result<(), HxmlError> {
+    let child = value.to_xml_element(tag_name)?;
+
+    if let Some(namespace) = namespace {
+        child.set_attribute(String::from("xmlns"), namespace.to_string());
+    }
+
+    element.add_contents(vec![Content::Element(child)]);
+
+    match child.get_attribute(String::from("xmlns")) {
+        Some(namespace) if namespace == XPathNode::Element(element).get_namespace_uri() && !element.get_tag_name().contains(':') => {
+            let child_element = memory::get_mut(child.ptr);
+            child_element.attributes.retain(|attribute| attribute.name != "xmlns");
+            child_element.update_custom_indices();
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{dom, into_dom, mapping, FromXml, FromXmlText, HxmlError, ElementPtr, ToXml};
+
+    // a hand-written impl, like the ones `hxml-derive` generates
+    #[derive(Debug, PartialEq)]
+    struct Item {
+        id: u32,
+        name: Option<String>,
+        children: Vec<Item>,
+    }
+
+    impl FromXml for Item {
+
+        fn from_xml(element: ElementPtr) -> Result<Self, HxmlError> {
+            Ok(Item {
+                id: mapping::require(element, mapping::read_attribute(element, "id")?, "attribute `id`")?,
+                name: mapping::read_optional::<Name>(element, mapping::get_children(element, "name", Some("urn:x")), "<name>")?.map(|name| name.0),
+                children: mapping::read_many(mapping::get_matching_children::<Item>(element))?,
+            })
+        }
+
+        fn matches(element: ElementPtr) -> bool {
+            mapping::matches_name(element, "item", Some("urn:x"))
+        }
+
+    }
+
+    impl ToXml for Item {
+
+        fn to_xml_element(&self, tag_name: Option<&str>) -> Result<ElementPtr, HxmlError> {
+            let element = mapping::new_element(tag_name.unwrap_or("item"), Some("urn:x"));
+
+            mapping::build(element, || {
+                mapping::write_attribute(element, "id", &self.id);
+
+                if let Some(name) = &self.name {
+                    mapping::write_child(element, &Name(name.clone()), None, None)?;
+                }
+
+                for child in self.children.iter() {
+                    mapping::write_child(element, child, None, None)?;
+                }
+
+                Ok(())
+            })
+        }
+
+    }
+
+    struct Name(String);
+
+    impl FromXml for Name {
+
+        fn from_xml(element: ElementPtr) -> Result<Self, HxmlError> {
+            Ok(Name(mapping::read_text(element)?))
+        }
+
+        fn matches(element: ElementPtr) -> bool {
+            mapping::matches_name(element, "name", None)
+        }
+
+    }
+
+    impl ToXml for Name {
+
+        fn to_xml_element(&self, tag_name: Option<&str>) -> Result<ElementPtr, HxmlError> {
+            let element = mapping::new_element(tag_name.unwrap_or("name"), Some("urn:x"));
+            mapping::write_text(element, &self.0);
+
+            Ok(element)
+        }
+
+    }
+
+    #[test]
+    fn mapping_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        into_dom("<doc/>".to_string()).unwrap();
+
+        let item = Item::from_xml_str(
+            "<x:item xmlns:x=\"urn:x\" id=\"1\"><x:name>a</x:name><x:item id=\" 2 \"/><item id=\"3\"/></x:item>"
+        ).unwrap();
+        assert_eq!(
+            item,
+            Item { id: 1, name: Some("a".to_string()), children: vec![Item { id: 2, name: None, children: vec![] }] }
+        );
+
+        // the default namespace is declared once
+        let xml = item.to_xml_string().unwrap();
+        assert_eq!(xml, "<item xmlns=\"urn:x\" id=\"1\"><name>a</name><item id=\"2\"/></item>");
+        assert_eq!(Item::from_xml_str(&xml).unwrap(), item);
+
+        let error = Item::from_xml_str("<item xmlns=\"urn:x\" id=\"1\">\n  <item id=\"x\"/></item>").unwrap_err().to_string();
+        assert!(error.contains("cannot parse `x` as u32") && error.contains("at `/item/item/@id` (line 2, column 3)"), "{}", error);
+
+        let error = Item::from_xml_str("<item xmlns=\"urn:x\"/>").unwrap_err().to_string();
+        assert!(error.contains("missing attribute `id` at `/item` (line 1, column 1)"), "{}", error);
+
+        let error = Item::from_xml_str("<item xmlns=\"urn:x\" id=\"1\"><name/><name/></item>").unwrap_err().to_string();
+        assert!(error.contains("expected at most one <name>, found 2"), "{}", error);
+
+        assert!(Item::from_xml_str("<item id=\"1\"/>").is_err());
+        assert_eq!(<f64 as FromXmlText>::from_xml_text(" 1.5 "), Ok(1.5));
+        assert_eq!(<bool as FromXmlText>::from_xml_text("0"), Ok(false));
+
+        assert_eq!(dom::to_string(), "<doc/>");
+        assert_eq!(dom::get_all_elements().len(), 1);
+
+        drop(lock);
+    }
+
+}
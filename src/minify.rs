@@ -0,0 +1,309 @@
+use crate::err::HxmlError;
+use crate::html::{is_block_element, is_void_element, is_whitespace_sensitive_element};
+use crate::node::element::{Content, Element};
+use crate::node::memory;
+use crate::serialize::{escape_attribute_value, escape_char_data, get_attributes, pick_quote, write_content, write_element as write_xml_element, write_html_element};
+use std::io::Write;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CommentPolicy {
+    RemoveAll,
+
+    /// It keeps conditional comments (`<!--[if IE]>...<![endif]-->`) and licence comments (`<!--! ... -->`, or comments with `@license` or `@preserve`)
+    KeepSpecial,
+    KeepAll,
+}
+
+#[derive(Clone, Debug)]
+pub struct MinifyOptions {
+
+    /// HTML mode:
+    /// - whitespaces around block elements are removed, and other sequences of whitespaces are collapsed into a single space
+    /// - quotes of attribute values are omitted when possible
+    /// - void elements are written without `/` (`<br>`), and other empty elements are written with an end tag (`<div></div>`)
+    ///
+    /// XML mode:
+    /// - char data that are only whitespaces are removed, unless the element has mixed content
+    /// - empty elements are written as `<a/>`
+    ///
+    /// In both modes, `pre`, `textarea`, `script`, `style` and elements with `xml:space="preserve"` are written as they are.
+    /// In HTML mode, they're written in HTML syntax (see `Element::to_html`), so `script` and `style` are not escaped.
+    pub html: bool,
+    pub comments: CommentPolicy,
+}
+
+impl Default for MinifyOptions {
+
+    fn default() -> Self {
+        MinifyOptions {
+            html: false,
+            comments: CommentPolicy::KeepSpecial,
+        }
+    }
+
+}
+
+pub struct Minified {
+    pub output: String,
+
+    /// compared to `to_string`
+    pub bytes_saved: usize,
+}
+
+// It fails in HTML mode if a `script` or a `style` contains its own end tag.
+pub fn minify_element(element: &Element, options: &MinifyOptions) -> Result<String, HxmlError> {
+    let mut result = vec![];
+    write_element(element, options, &mut result)?;
+
+    Ok(String::from_utf8(result).unwrap())
+}
+
+// It streams the output to `w`, like the serializer.
+fn write_element<W: Write>(element: &Element, options: &MinifyOptions, w: &mut W) -> Result<(), HxmlError> {
+
+    if is_preserved(element) && options.html {
+        return write_html_element(element, w);
+    }
+
+    else if is_preserved(element) {
+        return write_xml_element(element, false, w);
+    }
+
+    write!(w, "<{}", element.tag_name)?;
+
+    for (name, value) in get_attributes(element).into_iter() {
+
+        if options.html && can_omit_quotes(&value) {
+            write!(w, " {}={}", name, value)?;
+        }
+
+        else {
+            let quote = pick_quote(&value);
+            write!(w, " {}={}{}{}", name, quote, escape_attribute_value(&value, quote), quote)?;
+        }
+
+    }
+
+    let contents = get_minified_contents(element, options);
+
+    if contents.is_empty() {
+
+        if !options.html {
+            w.write_all(b"/>")?;
+        }
+
+        else if is_void_element(&element.tag_name) {
+            w.write_all(b">")?;
+        }
+
+        else {
+            write!(w, "></{}>", element.tag_name)?;
+        }
+
+        return Ok(());
+    }
+
+    w.write_all(b">")?;
+
+    for content in contents.iter() {
+
+        match content {
+            Content::Element(pointer) => {
+                write_element(memory::get(pointer.ptr), options, w)?;
+            }
+            Content::CharData(char_data) => {
+                w.write_all(escape_char_data(char_data).as_bytes())?;
+            }
+            _ => {
+                write_content(content, false, w)?;
+            }
+        }
+
+    }
+
+    write!(w, "</{}>", element.tag_name)?;
+
+    Ok(())
+}
+
+fn get_minified_contents(element: &Element, options: &MinifyOptions) -> Vec<Content> {
+    let is_mixed_content = element.contents.iter().any(
+        |content| match content {
+            Content::CharData(char_data) => !is_whitespaces(char_data),
+            Content::CDSect(_) | Content::Reference(_) => true,
+            _ => false
+        }
+    );
+
+    // removes comments, then merges adjacent char data
+    let mut contents: Vec<Content> = Vec::with_capacity(element.contents.len());
+
+    for content in element.contents.iter() {
+
+        match content {
+            Content::Comment(comment) => {
+
+                if options.comments == CommentPolicy::KeepAll || options.comments == CommentPolicy::KeepSpecial && is_special_comment(comment) {
+                    contents.push(content.clone());
+                }
+
+            },
+            Content::CharData(char_data) => match contents.last_mut() {
+                Some(Content::CharData(last)) => {
+                    last.push_str(char_data);
+                },
+                _ => {
+                    contents.push(content.clone());
+                }
+            },
+            _ => {
+                contents.push(content.clone());
+            }
+        }
+
+    }
+
+    let mut result = Vec::with_capacity(contents.len());
+
+    for (index, content) in contents.iter().enumerate() {
+
+        match content {
+            Content::CharData(char_data) if !options.html => {
+
+                if is_mixed_content || !is_whitespaces(char_data) {
+                    result.push(content.clone());
+                }
+
+            },
+            Content::CharData(char_data) => {
+                let collapsed = collapse_whitespaces(char_data);
+
+                if collapsed == " " && is_block_boundary(element, &contents, index) {
+                    continue;
+                }
+
+                result.push(Content::CharData(collapsed));
+            },
+            _ => {
+                result.push(content.clone());
+            }
+        }
+
+    }
+
+    result
+}
+
+// a whitespace-only char data at `contents[index]` is not rendered if it's next to a block element,
+// or it's the first or the last child of a block element (comments are not rendered, so they're skipped)
+fn is_block_boundary(element: &Element, contents: &[Content], index: usize) -> bool {
+    let is_block = |content: Option<&Content>| match content {
+        Some(Content::Element(pointer)) => is_block_element(&memory::get(pointer.ptr).tag_name),
+        None => is_block_element(&element.tag_name),
+        _ => false
+    };
+    let is_not_comment = |content: &&Content| !matches!(content, Content::Comment(_));
+
+    is_block(contents[..index].iter().rev().find(is_not_comment))
+    || is_block(contents[(index + 1)..].iter().find(is_not_comment))
+}
+
+fn is_preserved(element: &Element) -> bool {
+    is_whitespace_sensitive_element(&element.tag_name)
+    || element.attributes.iter().any(|att| att.name == "xml:space" && att.value == "preserve")
+}
+
+fn is_special_comment(comment: &str) -> bool {
+    let comment = comment.trim_start();
+
+    comment.starts_with("[if") || comment.starts_with("<![endif]") || comment.starts_with('!')
+    || comment.contains("@license") || comment.contains("@preserve")
+}
+
+fn is_whitespaces(s: &str) -> bool {
+    s.chars().all(|c| c.is_ascii_whitespace())
+}
+
+fn collapse_whitespaces(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+
+    for c in s.chars() {
+
+        if c.is_ascii_whitespace() {
+
+            if !result.ends_with(' ') {
+                result.push(' ');
+            }
+
+        }
+
+        else {
+            result.push(c);
+        }
+
+    }
+
+    result
+}
+
+// https://html.spec.whatwg.org/multipage/syntax.html#unquoted
+fn can_omit_quotes(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(
+        |c| !c.is_ascii_whitespace() && !"\"'=<>`&".contains(c)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dom;
+    use crate::minify::*;
+
+    #[test]
+    fn minify_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        let document = "<html>
+  <head>
+    <!--[if IE]><p>IE</p><![endif]-->
+    <!-- a comment -->
+    <style>
+      p { color: red; }
+    </style>
+    <script><![CDATA[if (a < b) {}]]> &amp;&amp; c</script>
+  </head>
+  <body>
+    <div title=\"a b\" class=\"box\">
+      <p>  Hello,   <b>world</b> !  </p>
+      <br/>
+      <span></span>
+    </div>
+    <pre>  keep
+    this  </pre>
+  </body>
+</html>";
+
+        crate::into_dom(document.to_string()).unwrap();
+
+        let minified = dom::minify(&MinifyOptions { html: true, comments: CommentPolicy::KeepSpecial }).unwrap();
+        assert_eq!(
+            minified.output,
+            "<html><head><!--[if IE]><p>IE</p><![endif]--><style>
+      p { color: red; }
+    </style><script>if (a < b) {} && c</script></head><body><div title=\"a b\" class=box><p> Hello, <b>world</b> ! </p><br> <span></span></div><pre>  keep
+    this  </pre></body></html>"
+        );
+        assert_eq!(minified.bytes_saved, dom::to_string().len() - minified.output.len());
+
+        let minified = dom::minify(&MinifyOptions { html: false, comments: CommentPolicy::RemoveAll }).unwrap();
+        assert_eq!(
+            minified.output,
+            "<html><head><style>
+      p { color: red; }
+    </style><script><![CDATA[if (a < b) {}]]> &amp;&amp; c</script></head><body><div title=\"a b\" class=\"box\"><p>  Hello,   <b>world</b> !  </p><br/><span/></div><pre>  keep
+    this  </pre></body></html>"
+        );
+
+        drop(lock);
+    }
+
+}
@@ -0,0 +1,225 @@
+use super::element::{Content, Element};
+use super::memory;
+use super::pointer::ElementPtr;
+use crate::dom::TAGS_BY_ID;
+use std::ptr::addr_of;
+
+/// What to do with the `id` attributes of the copies
+#[derive(Clone, Debug, PartialEq)]
+pub enum DuplicateIds {
+
+    /// The copies have the same ids as the originals.
+    Keep,
+
+    /// The copies don't have ids.
+    Remove,
+
+    /// `id="a"` becomes `id="a-copy"` with `Suffix(String::from("-copy"))`.
+    /// If `a-copy` is taken, it's numbered like `MakeUnique`: `a-copy-2`, `a-copy-3` and so on.
+    Suffix(String),
+
+    /// `id="a"` becomes `id="a-2"`, or `id="a-3"` if `a-2` is taken, and so on.
+    MakeUnique,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CloneOptions {
+
+    /// If it's false, only the element is copied, without its contents.
+    pub deep: bool,
+    pub duplicate_ids: DuplicateIds,
+}
+
+impl Default for CloneOptions {
+
+    fn default() -> Self {
+        CloneOptions {
+            deep: true,
+            duplicate_ids: DuplicateIds::Keep,
+        }
+    }
+
+}
+
+// `Element` derives `Clone`, but the copy shares the children and the pointer with the original.
+// This allocates new elements for the subtree and registers them in the indices.
+// The copy doesn't have a parent.
+// Allocating may reallocate `memory::ELEMENTS`, so it copies what it needs from `element` before that, and never keeps a `&Element`.
+pub fn clone_element(element: ElementPtr, options: &CloneOptions) -> ElementPtr {
+    let el = memory::get(element.ptr);
+    let tag_name = el.tag_name.clone();
+    let id = el.id.clone();
+    let original_attributes = el.attributes.clone();
+    let empty_element = el.empty_element;
+    let source_start_tag = el.source_start_tag.clone();
+    let source_end_tag = el.source_end_tag.clone();
+    let original_contents = if options.deep { el.contents.clone() } else { vec![] };
+
+    let mut contents = Vec::with_capacity(original_contents.len());
+
+    for content in original_contents.into_iter() {
+
+        match content {
+            Content::Element(child) => {
+                contents.push(Content::Element(clone_element(child, options)));
+            }
+            _ => {
+                contents.push(content);
+            }
+        }
+
+    }
+
+    // None: the same id, Some(None): no id, Some(Some(id)): a new id
+    let new_id = match (&id, &options.duplicate_ids) {
+        (None, _) | (_, DuplicateIds::Keep) => None,
+        (Some(_), DuplicateIds::Remove) => Some(None),
+        (Some(id), DuplicateIds::Suffix(suffix)) => {
+            let suffixed = format!("{}{}", id, suffix);
+
+            if is_id_taken(&suffixed) {
+                Some(Some(get_unique_id(&suffixed)))
+            } else {
+                Some(Some(suffixed))
+            }
+
+        },
+        (Some(id), DuplicateIds::MakeUnique) => Some(Some(get_unique_id(id))),
+    };
+
+    let attributes = match &new_id {
+        Some(None) => original_attributes.into_iter().filter(|attribute| attribute.name != "id").collect(),
+        _ => original_attributes,
+    };
+
+    let result = Element::new(tag_name, attributes, empty_element, contents);
+
+    for child in result.get_children().into_iter() {
+        child.set_parent(result);
+    }
+
+    let el = memory::get_mut(result.ptr);
+
+    // the source text is kept unless an attribute is removed
+    match &new_id {
+        Some(None) => {}
+        _ => {
+            el.source_start_tag = source_start_tag;
+            el.source_end_tag = source_end_tag;
+        }
+    }
+
+    if let Some(Some(id)) = new_id {
+        el.set_attribute(String::from("id"), id);
+    }
+
+    result
+}
+
+fn get_unique_id(id: &str) -> String {
+    let mut n = 2;
+
+    loop {
+        let candidate = format!("{}-{}", id, n);
+
+        if !is_id_taken(&candidate) {
+            return candidate;
+        }
+
+        n += 1;
+    }
+
+}
+
+fn is_id_taken(id: &str) -> bool {
+    unsafe { (*addr_of!(TAGS_BY_ID)).as_ref().unwrap().contains_key(id) }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{dom, into_dom, into_dom_with_options, CloneOptions, Content, DuplicateIds, ParseOptions};
+
+    #[test]
+    fn clone_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        into_dom("<r><card id=\"c\" class=\"x\"><h id=\"t\">title</h><!--c--></card></r>".to_string()).unwrap();
+        let root = dom::get_root();
+        let card = dom::get_element_by_id(None, "c".to_string()).unwrap();
+
+        let shallow = card.clone_node(false);
+        assert_eq!(shallow.to_string(), "<card id=\"c\" class=\"x\"></card>");
+        assert_eq!(shallow.get_parent(), None);
+
+        let deep = card.clone_node(true);
+        assert_eq!(deep.to_string(), card.to_string());
+        assert!(deep != card && deep.get_children()[0] != card.get_children()[0]);
+        assert_eq!(deep.get_children()[0].get_parent(), Some(deep));
+        assert_eq!(dom::get_elements_by_id(None, "t".to_string()).len(), 2);
+
+        // the copy is independent of the original
+        deep.get_children()[0].set_attribute("id".to_string(), "u".to_string());
+        assert_eq!(card.to_string(), "<card id=\"c\" class=\"x\"><h id=\"t\">title</h><!--c--></card>");
+
+        dom::delete(shallow);
+        dom::delete(deep);
+
+        let unique = CloneOptions { duplicate_ids: DuplicateIds::MakeUnique, ..CloneOptions::default() };
+
+        for _ in 0..2 {
+            root.add_contents(vec![Content::Element(card.clone_node_with_options(&unique))]);
+        }
+
+        let removed = card.clone_node_with_options(&CloneOptions { duplicate_ids: DuplicateIds::Remove, ..CloneOptions::default() });
+        let suffixed = card.clone_node_with_options(&CloneOptions { duplicate_ids: DuplicateIds::Suffix("-x".to_string()), ..CloneOptions::default() });
+        root.add_contents(vec![Content::Element(removed), Content::Element(suffixed)]);
+
+        assert_eq!(
+            dom::to_string(),
+            concat!(
+                "<r><card id=\"c\" class=\"x\"><h id=\"t\">title</h><!--c--></card>",
+                "<card id=\"c-2\" class=\"x\"><h id=\"t-2\">title</h><!--c--></card>",
+                "<card id=\"c-3\" class=\"x\"><h id=\"t-3\">title</h><!--c--></card>",
+                "<card class=\"x\"><h>title</h><!--c--></card>",
+                "<card id=\"c-x\" class=\"x\"><h id=\"t-x\">title</h><!--c--></card></r>",
+            )
+        );
+        assert_eq!(dom::get_elements_by_class_name(None, "x".to_string()).len(), 5);
+        assert!(dom::some_checks().is_ok());
+        dom::check_indices().unwrap();
+
+        // suffixed ids that are taken are numbered
+        let suffixed_again = card.clone_node_with_options(&CloneOptions { duplicate_ids: DuplicateIds::Suffix("-x".to_string()), ..CloneOptions::default() });
+        assert_eq!(suffixed_again.get_attribute("id".to_string()), Some("c-x-2".to_string()));
+        assert_eq!(suffixed_again.get_elements_by_tag_name("h")[0].get_attribute("id".to_string()), Some("t-x-2".to_string()));
+        assert!(dom::some_checks().is_ok());
+
+        // the source text is copied
+        into_dom_with_options("<r><a  x = '1' id='a'/></r>".to_string(), ParseOptions { preserve_format: true, ..ParseOptions::default() }).unwrap();
+        let a = dom::get_element_by_id(None, "a".to_string()).unwrap();
+        assert_eq!(a.clone_node(true).to_string(), "<a  x = '1' id='a'/>");
+        assert_eq!(a.clone_node_with_options(&unique).to_string(), "<a  x = '1' id='a-2'/>");
+
+        drop(lock);
+    }
+
+    // cloning allocates elements, which may reallocate the arena while the original is being read
+    #[test]
+    fn clone_large_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        into_dom(format!("<r><big id=\"b\">{}</big></r>", "<c class=\"x\">t</c>".repeat(3000))).unwrap();
+        let big = dom::get_element_by_id(None, "b".to_string()).unwrap();
+
+        for _ in 0..3 {
+            let copy = big.clone_node(true);
+            assert_eq!(copy.to_string(), big.to_string());
+            assert_eq!(copy.get_attribute("id".to_string()), Some("b".to_string()));
+        }
+
+        dom::check_indices().unwrap();
+
+        drop(lock);
+    }
+
+}
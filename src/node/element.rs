@@ -1,16 +1,27 @@
 use super::attribute::Attribute;
+use super::clone::{clone_element, CloneOptions};
 use super::pointer::ElementPtr;
+use super::prolog::DocTypeDecl;
 use super::memory::{self, allocate};
+use super::source::patch_attribute;
 use crate::dom::{
-    TAGS_BY_CLASS,
-    TAGS_BY_ID,
-    TAGS_BY_NAME,
+    self,
+    index_classes,
+    index_element,
+    index_id,
+    unindex_classes,
+    unindex_id,
 };
-use crate::err::{read_errors, reset_errors, HxmlError};
-use crate::gstring::set_global_string;
-use crate::parse::{parse_content, parse_element};
-use crate::utils::into_v16;
+use crate::err::{read_errors, HxmlError, ERRORS};
+use crate::gstring::{set_global_string, GLOBAL_STRING};
+use crate::index;
+use crate::order;
+use crate::parse::{expand_reference, get_parse_options, parse_content, parse_element};
+use crate::serialize::{get_attributes, write_content, write_element, write_html_element};
+use crate::text::{get_inner_text, get_text_content};
+use crate::utils::{from_v16, into_v16, normalize_line_endings};
 use std::collections::HashSet;
+use std::io::Write;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Content {
@@ -24,7 +35,10 @@ pub enum Content {
 impl Content {
 
     pub fn new_element(element: Element) -> Self {
-        Content::Element(memory::allocate(element))
+        let pointer = memory::allocate(element);
+        index_element(pointer);
+
+        Content::Element(pointer)
     }
 
     pub fn new_char_data(char_data: String) -> Self {
@@ -43,63 +57,31 @@ impl Content {
         Content::Reference(reference)
     }
 
+    // It parses the contents into detached elements, char data and so on.
+    // The global string and the errors of the document are kept, and errors have positions in `string`.
     pub fn from_string(string: String) -> Result<Vec<Content>, HxmlError> {
-        let string_v16 = into_v16(&string);
-        set_global_string(string_v16.clone());
-        reset_errors();
-
-        let mut curr_index = 0;
-        let mut result = vec![];
-
-        while curr_index < string_v16.len() {
-
-            match parse_content(&string_v16, curr_index) {
-                Some((content, last_index)) => {
-                    let real_content = content.to_real();
-
-                    match &real_content {
-                        Content::Element(ptr) => {
-                            ptr.set_parent_recursive();
-                        }
-                        _ => {}
-                    }
-
-                    result.push(real_content);
-                    curr_index = last_index + 1;
-                },
-                None => {
-                    return Err(HxmlError::new(String::from("failed to parse an XML string..."), usize::MAX));
-                }
-            }
-
-        }
+        let result = parse_fragment(&string)?;
 
         if result.len() > 0 {
             return Ok(result);
         }
 
-        let errors = read_errors();
-
-        // reset global states
-        set_global_string(vec![]);
-        reset_errors();
-
-        if errors.len() > 0 {
-            return Err(errors[0].clone());
-        }
-
         return Err(HxmlError::new(String::from("No contents have been found!"), usize::MAX));
     }
 
     pub fn to_string(&self) -> String {
-        match self {
-            Content::Element(pointer) => pointer.to_string(),
-            Content::CharData(char_data) => char_data.clone(),
-            Content::CDSect(cd_sect) => format!("<![CDATA[{}]]>", cd_sect),
-            Content::Comment(comment) => format!("<!--{}-->", comment),
-            Content::Reference(reference) => format!("&{};", reference)
-        }
+        let mut result = vec![];
+        write_content(self, false, &mut result).unwrap();
+
+        String::from_utf8(result).unwrap()
+    }
+
+    /// It fails if the result cannot be parsed back into the same tree (eg. a comment with `--`).
+    pub fn to_xml(&self) -> Result<String, HxmlError> {
+        let mut result = vec![];
+        write_content(self, true, &mut result)?;
 
+        Ok(String::from_utf8(result).unwrap())
     }
 
 }
@@ -109,16 +91,29 @@ pub struct Element {
     pub(crate) pointer: ElementPtr,
     parent: Option<ElementPtr>,
     pub(crate) tag_name: String,
+
+    /// all the attributes (including `id` and `class`), in the original order
     pub(crate) attributes: Vec<Attribute>,
-    empty_element: bool,
+    pub(crate) empty_element: bool,
     pub(crate) contents: Vec<Content>,
     pub(crate) is_alive: bool,
 
     /// for HTML
+    /// a copy of the `id` attribute, for the indices
     pub(crate) id: Option<String>,
 
     /// for HTML
-    pub(crate) classes: Vec<String>
+    /// the `class` attribute split by whitespaces, for the indices
+    pub(crate) classes: Vec<String>,
+
+    /// `ParseOptions::preserve_format`
+    /// the source text of the start tag (or the empty element tag) and the end tag
+    pub(crate) source_start_tag: Option<String>,
+    pub(crate) source_end_tag: Option<String>,
+
+    /// 1-based line and column of the start tag in the parsed string
+    /// It's None if the element is not from a parser.
+    pub(crate) source_location: Option<(usize, usize)>
 }
 
 impl Element {
@@ -126,20 +121,15 @@ impl Element {
     pub fn new(tag_name: String, attributes: Vec<Attribute>, empty_element: bool, contents: Vec<Content>) -> ElementPtr {
         let mut id = None;
         let mut classes = vec![];
-        let mut attributes_without_id_and_classes = Vec::with_capacity(attributes.len());
 
-        for attribute in attributes.into_iter() {
+        for attribute in attributes.iter() {
 
             if attribute.name == "id" {
                 id = Some(attribute.value.clone());
             }
 
             else if attribute.name == "class" {
-                classes = attribute.value.split(" ").map(|c| c.to_string()).collect();
-            }
-
-            else {
-                attributes_without_id_and_classes.push(attribute);
+                classes = split_classes(&attribute.value);
             }
 
         }
@@ -148,104 +138,138 @@ impl Element {
             pointer: ElementPtr::null(),
             parent: None,
             is_alive: true,
-            tag_name: tag_name.clone(),
+            tag_name,
             empty_element, contents,
-            attributes: attributes_without_id_and_classes,
-            id: id.clone(),
-            classes: classes.clone()
+            attributes,
+            id,
+            classes,
+            source_start_tag: None,
+            source_end_tag: None,
+            source_location: None
         };
 
         let result_ptr = allocate(result);
-
-        unsafe {
-            let tags_by_name = TAGS_BY_NAME.as_mut().unwrap();
-            let tags_by_id = TAGS_BY_ID.as_mut().unwrap();
-            let tags_by_class = TAGS_BY_CLASS.as_mut().unwrap();
-
-            match tags_by_name.get_mut(&tag_name) {
-                Some(v) => {
-                    v.push(result_ptr);
-                }
-                _ => {
-                    tags_by_name.insert(tag_name, vec![result_ptr]);
-                }
-            }
-
-            for class in classes.into_iter() {
-
-                match tags_by_class.get_mut(&class) {
-                    Some(v) => {
-                        v.push(result_ptr);
-                    }
-                    _ => {
-                        tags_by_class.insert(class, vec![result_ptr]);
-                    }
-                }
-
-            }
-
-            match id {
-                Some(id) => {
-                    tags_by_id.insert(id, result_ptr);
-                }
-                _ => {}
-            }
-
-        }
+        index_element(result_ptr);
 
         result_ptr
     }
 
     pub fn from_string(string: String) -> Result<ElementPtr, HxmlError> {
-        let string_v16 = into_v16(&string);
-        set_global_string(string_v16.clone());
-        reset_errors();
+        let string_v16 = get_fragment_string(&string);
 
-        match parse_element(&string_v16, 0) {
+        with_fragment_string(&string_v16, || match parse_element(&string_v16, 0) {
             Some((element, _)) => {
-                let result = element.to_real();
+                let result = element.to_real(&get_parse_options(), get_doctype_decl());
                 result.set_parent_recursive();
-                return Ok(result);
+                Ok(result)
             },
-            None => {}
-        }
-
-        let errors = read_errors();
-
-        // reset global states
-        set_global_string(vec![]);
-        reset_errors();
+            None => match read_errors().first() {
+                Some(error) => Err(error.clone()),
+                None => Err(HxmlError::new(String::from("No Elements have been found!"), usize::MAX))
+            }
+        })
+    }
 
-        if errors.len() > 0 {
-            return Err(errors[0].clone());
-        }
+    // It copies the element (and its descendants if `deep` is set), with the same ids.
+    // The copy doesn't have a parent.
+    pub fn clone_node(&self, deep: bool) -> ElementPtr {
+        clone_element(self.pointer, &CloneOptions { deep, ..CloneOptions::default() })
+    }
 
-        return Err(HxmlError::new(String::from("No Elements have been found!"), usize::MAX));
+    pub fn clone_node_with_options(&self, options: &CloneOptions) -> ElementPtr {
+        clone_element(self.pointer, options)
     }
 
     pub fn get_contents(&self) -> &Vec<Content> {
         &self.contents
     }
 
+    // The caller may move elements.
     pub fn get_contents_mut(&mut self) -> &mut Vec<Content> {
+        order::invalidate();
         &mut self.contents
     }
 
-    pub fn add_contents(&mut self, contents: Vec<Content>) {
+    // Elements in `contents` are moved from their parents.
+    // If an element in `contents` is `self`, an ancestor of `self` or deleted, it does nothing and returns false. Use `append` to get the error.
+    pub fn add_contents(&mut self, contents: Vec<Content>) -> bool {
+        self.append(contents).is_ok()
+    }
+
+    // It inserts `contents` at `index` of `self.contents`. Elements in `contents` are moved from their parents.
+    // It fails if an element in `contents` is `self`, an ancestor of `self` (it would make a cycle) or deleted.
+    // If an element in `contents` is already a child of `self`, `index` is the index before it's moved.
+    pub fn insert_contents(&mut self, index: usize, contents: Vec<Content>) -> Result<(), HxmlError> {
+
+        if index > self.contents.len() {
+            return Err(HxmlError::new(format!("index {} is out of range: the element has {} contents", index, self.contents.len()), usize::MAX));
+        }
+
+        let mut elements: Vec<ElementPtr> = vec![];
+
+        for content in contents.iter() {
 
-        for content in contents.into_iter() {
             match content {
-                Content::Element(ptr) => {
-                    self.add_element_ptr(ptr);
-                },
-                _ => {
-                    self.contents.push(content);
+                Content::Element(element) => {
+
+                    if !memory::get(element.ptr).is_alive {
+                        return Err(HxmlError::new(String::from("cannot insert a deleted element"), usize::MAX));
+                    }
+
+                    if *element == self.pointer || self.pointer.ancestors().any(|ancestor| ancestor == *element) {
+                        return Err(HxmlError::new(format!("cannot insert <{}> into itself or its descendant", element.get_tag_name()), usize::MAX));
+                    }
+
+                    if elements.contains(element) {
+                        return Err(HxmlError::new(format!("<{}> appears multiple times", element.get_tag_name()), usize::MAX));
+                    }
+
+                    elements.push(*element);
                 }
+                _ => {}
             }
+
         }
 
+        let mut index = index;
+
+        for element in elements.into_iter() {
+
+            match element.get_parent() {
+                Some(parent) if parent == self.pointer => {
+                    let child_index = self.contents.iter().position(|content| content == &Content::Element(element)).unwrap();
+
+                    if child_index < index {
+                        index -= 1;
+                    }
+
+                    self.contents.remove(child_index);
+                }
+                Some(parent) => {
+                    parent.delete_child_element(element);
+                }
+                None => {}
+            }
+
+            element.set_parent(self.pointer);
+        }
+
+        self.contents.splice(index..index, contents);
+        order::invalidate();
+        self.update_custom_indices();
+
+        Ok(())
+    }
+
+    pub fn prepend(&mut self, contents: Vec<Content>) -> Result<(), HxmlError> {
+        self.insert_contents(0, contents)
+    }
+
+    pub fn append(&mut self, contents: Vec<Content>) -> Result<(), HxmlError> {
+        self.insert_contents(self.contents.len(), contents)
     }
 
+    // It detaches `element_ptr` from `self`, but doesn't delete it. Use `dom::delete` to delete it.
     // it does nothing if `element_ptr` is not a child of `self`
     pub fn delete_child_element(&mut self, element_ptr: ElementPtr) {
         let deletion_indexes = self.contents.iter().enumerate().filter(
@@ -261,32 +285,67 @@ impl Element {
         };
 
         self.contents.remove(deletion_index);
+        memory::get_mut(element_ptr.ptr).parent = None;
+        order::invalidate();
+        self.update_custom_indices();
     }
 
-    pub fn add_element_ptr(&mut self, element_ptr: ElementPtr) {
-        element_ptr.set_parent(self.pointer);
-        self.contents.push(Content::Element(element_ptr));
+    // If `element_ptr` already has a parent, it's moved from the parent.
+    // It does nothing and returns false in the same cases as `add_contents`.
+    pub fn add_element_ptr(&mut self, element_ptr: ElementPtr) -> bool {
+        self.add_contents(vec![Content::Element(element_ptr)])
     }
 
     pub fn add_char_data(&mut self, char_data: String) {
         self.contents.push(Content::new_char_data(char_data));
+        self.update_custom_indices();
     }
 
-    pub fn get_attribute(&self, attribute: String) -> Option<String> {
+    // `dom::create_index` may have indices keyed on the attributes or the contents
+    pub(crate) fn update_custom_indices(&self) {
 
-        if attribute == "id" {
-            return self.id.clone();
+        if self.is_alive {
+            index::update_element(self.pointer);
         }
 
-        else if attribute == "class" {
+    }
+
+    pub fn text_content(&self) -> String {
+        get_text_content(self)
+    }
+
+    // It deletes all the contents, and adds `text` as char data.
+    pub fn set_text_content(&mut self, text: String) {
+        self.delete_contents();
 
-            return if self.classes.len() > 0 {
-                Some(self.classes.join(" "))
-            } else {
-                None
-            };
+        if !text.is_empty() {
+            self.contents.push(Content::CharData(text));
         }
 
+        self.update_custom_indices();
+    }
+
+    // It deletes all the contents, including the child elements and their descendants.
+    pub fn delete_contents(&mut self) {
+
+        for content in std::mem::take(&mut self.contents).into_iter() {
+
+            if let Content::Element(child) = content {
+                memory::get_mut(child.ptr).parent = None;
+                dom::delete(child);
+            }
+
+        }
+
+        self.update_custom_indices();
+    }
+
+    pub fn inner_text(&self) -> String {
+        get_inner_text(self)
+    }
+
+    pub fn get_attribute(&self, attribute: String) -> Option<String> {
+
         for att in self.attributes.iter() {
 
             if att.name == attribute {
@@ -298,30 +357,90 @@ impl Element {
         None
     }
 
-    // TODO: add_class, remove_class, toggle_class
+    pub fn has_class(&self, class: &str) -> bool {
+        self.classes.iter().any(|c| c == class)
+    }
 
-    pub fn set_attribute(&mut self, attribute: String, value: String) {
+    // https://dom.spec.whatwg.org/#dom-domtokenlist-add
+    // The `class` attribute is rewritten as the classes joined by a space.
+    pub fn add_class(&mut self, class: &str) {
 
-        if attribute == "id" {
-            self.id = Some(value);
+        if self.has_class(class) {
             return;
         }
 
-        else if attribute == "class" {
-            self.classes = value.split(" ").map(|c| c.to_string()).collect();
+        let mut classes = self.classes.clone();
+        classes.push(class.to_string());
+        self.set_attribute(String::from("class"), classes.join(" "));
+    }
+
+    // It does nothing if `self` doesn't have `class`.
+    pub fn remove_class(&mut self, class: &str) {
+
+        if !self.has_class(class) {
             return;
         }
 
-        for att in self.attributes.iter_mut() {
+        let classes = self.classes.iter().filter(|c| *c != class).cloned().collect::<Vec<String>>();
+        self.set_attribute(String::from("class"), classes.join(" "));
+    }
+
+    // It returns true if `self` has `class` after the toggle.
+    pub fn toggle_class(&mut self, class: &str) -> bool {
 
-            if att.name == attribute {
-                att.value = value;
-                return;
+        if self.has_class(class) {
+            self.remove_class(class);
+            false
+        }
+
+        else {
+            self.add_class(class);
+            true
+        }
+
+    }
+
+    pub fn set_attribute(&mut self, attribute: String, value: String) {
+
+        if let Some(start_tag) = &self.source_start_tag {
+            self.source_start_tag = patch_attribute(start_tag, &attribute, &value);
+        }
+
+        if attribute == "id" {
+
+            if self.is_alive {
+
+                if let Some(id) = &self.id {
+                    unindex_id(self.pointer, id);
+                }
+
+                index_id(self.pointer, &value);
             }
 
+            self.id = Some(value.clone());
         }
 
-        self.attributes.push(Attribute::new(attribute, value));
+        else if attribute == "class" {
+            let classes = split_classes(&value);
+
+            if self.is_alive {
+                unindex_classes(self.pointer, &self.classes);
+                index_classes(self.pointer, &classes);
+            }
+
+            self.classes = classes;
+        }
+
+        match self.attributes.iter().position(|att| att.name == attribute) {
+            Some(index) => {
+                self.attributes[index].value = value;
+            }
+            None => {
+                self.attributes.push(Attribute::new(attribute, value));
+            }
+        }
+
+        self.update_custom_indices();
     }
 
     pub fn has_unique_attributes(&self) -> bool {
@@ -358,6 +477,7 @@ impl Element {
 
     pub fn set_parent(&mut self, parent: ElementPtr) {
         self.parent = Some(parent);
+        order::invalidate();
     }
 
     pub fn get_children(&self) -> Vec<ElementPtr> {
@@ -400,57 +520,224 @@ impl Element {
 
     }
 
+    /// Char data and attribute values are escaped.
+    /// Comments, names and references are written as they are, even if they're not valid. Use `to_xml` to check them.
     pub fn to_string(&self) -> String {
-        let inside_tag = format!(
-            "{}{}{}{}{}",
-            self.tag_name,
-            if self.attributes.len() == 0 {
-                ""
-            } else {
-                " "
-            },
-            self.attributes.iter().map(
-                |att| format!("{}=\"{}\"", att.name, att.value)
-            ).collect::<Vec<String>>().join(" "),
-            match &self.id {
-                Some(id) => format!(" id=\"{}\"", id),
-                _ => String::new()
-            },
-            if self.classes.len() == 0 {
-                String::new()
-            } else {
-                format!(" class=\"{}\"", self.classes.join(" "))
+        let mut result = vec![];
+        write_element(self, false, &mut result).unwrap();
+
+        String::from_utf8(result).unwrap()
+    }
+
+    /// It's like `to_string`, but it streams the output to `w`.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> Result<(), HxmlError> {
+        write_element(self, false, w)
+    }
+
+    /// It writes the subtree in HTML syntax: `<br>`, `<div></div>`, `<input checked>` and unescaped `<script>` bodies.
+    /// It fails if a `<script>` or a `<style>` contains its own end tag (eg. `</script`), which HTML cannot represent.
+    pub fn to_html(&self) -> Result<String, HxmlError> {
+        let mut result = vec![];
+        write_html_element(self, &mut result)?;
+
+        Ok(String::from_utf8(result).unwrap())
+    }
+
+    /// It's like `to_html`, but it streams the output to `w`.
+    pub fn write_html_to<W: Write>(&self, w: &mut W) -> Result<(), HxmlError> {
+        write_html_element(self, w)
+    }
+
+    /// It fails if the result cannot be parsed back into the same tree (eg. a comment with `--`).
+    pub fn to_xml(&self) -> Result<String, HxmlError> {
+        let mut result = vec![];
+        write_element(self, true, &mut result)?;
+
+        Ok(String::from_utf8(result).unwrap())
+    }
+
+    /// It compares the tag names, the attributes (regardless of their order) and the contents of the subtrees.
+    /// Char data, CDATA sections and references to predefined entities are compared as text, so
+    /// `<a>1 &lt; 2</a>` and `<a><![CDATA[1 < 2]]></a>` are the same tree.
+    pub fn is_same_tree(&self, other: &Element) -> bool {
+        let mut attributes = get_attributes(self);
+        let mut other_attributes = get_attributes(other);
+        attributes.sort();
+        other_attributes.sort();
+
+        if self.tag_name != other.tag_name || attributes != other_attributes {
+            return false;
+        }
+
+        let contents = merge_texts(&self.contents);
+        let other_contents = merge_texts(&other.contents);
+
+        if contents.len() != other_contents.len() {
+            return false;
+        }
+
+        for (content, other_content) in contents.iter().zip(other_contents.iter()) {
+
+            match (content, other_content) {
+                (Content::Element(e), Content::Element(other_e)) => {
+
+                    if !e.is_same_tree(*other_e) {
+                        return false;
+                    }
+
+                }
+                _ => if content != other_content {
+                    return false;
+                }
+            }
+
+        }
+
+        true
+    }
+
+    pub fn get_inner_string(&self) -> String {
+        let mut result = vec![];
+
+        for content in self.contents.iter() {
+            write_content(content, false, &mut result).unwrap();
+        }
+
+        String::from_utf8(result).unwrap()
+    }
+
+}
+
+// It parses `string` as contents (`content` of the XML spec, which may have multiple elements).
+// If it fails, the elements parsed so far are deleted.
+pub(crate) fn parse_fragment(string: &str) -> Result<Vec<Content>, HxmlError> {
+    let string_v16 = get_fragment_string(string);
+    let mut result = vec![];
+
+    let parse_result = with_fragment_string(&string_v16, || {
+        let options = get_parse_options();
+        let mut curr_index = 0;
+
+        while curr_index < string_v16.len() {
+
+            match parse_content(&string_v16, curr_index) {
+                Some((content, last_index)) => {
+                    let real_content = content.to_real(&options, get_doctype_decl());
+
+                    if let Content::Element(ptr) = &real_content {
+                        ptr.set_parent_recursive();
+                    }
+
+                    result.push(real_content);
+                    curr_index = last_index + 1;
+                },
+                None => {
+                    return match read_errors().first() {
+                        Some(error) => Err(error.clone()),
+                        None => Err(HxmlError::new(String::from("failed to parse an XML string..."), curr_index))
+                    };
+                }
             }
-        );
-        let opening_tag = format!(
-            "<{}{}",
-            inside_tag,
-            if self.empty_element {
-                "/>"
-            } else {
-                ">"
+
+        }
+
+        Ok(())
+    });
+
+    match parse_result {
+        Ok(()) => Ok(result),
+        Err(error) => {
+
+            for content in result.into_iter() {
+
+                if let Content::Element(ptr) = content {
+                    dom::delete(ptr);
+                }
+
             }
-        );
-        let closing_tag = if self.empty_element {
-            String::new()
-        } else {
-            format!("</{}>", self.tag_name)
+
+            Err(error)
+        }
+    }
+
+}
+
+fn get_fragment_string(string: &str) -> Vec<u16> {
+    let string_v16 = into_v16(string);
+
+    if get_parse_options().normalize_line_endings {
+        normalize_line_endings(&string_v16)
+    }
+
+    else {
+        string_v16
+    }
+
+}
+
+// Fragments are parsed with the attribute types that are declared in the DTD of the document.
+fn get_doctype_decl<'a>() -> Option<&'a DocTypeDecl> {
+    unsafe { (*std::ptr::addr_of!(dom::PROLOG)).as_ref()?.doctype_decl.as_ref() }
+}
+
+// The parser reads the global string, and errors are rendered with it.
+// It swaps the global string and the errors of the document with the fragment's while running `f`.
+fn with_fragment_string<T, F: FnOnce() -> T>(string_v16: &[u16], f: F) -> T {
+    let (global_string, errors) = unsafe {
+        (std::mem::take(&mut *std::ptr::addr_of_mut!(GLOBAL_STRING)), std::mem::take(&mut *std::ptr::addr_of_mut!(ERRORS)))
+    };
+
+    set_global_string(string_v16.to_vec());
+    let result = f();
+    set_global_string(global_string);
+
+    unsafe {
+        ERRORS = errors;
+    }
+
+    result
+}
+
+// https://html.spec.whatwg.org/multipage/dom.html#classes
+#[inline]
+fn split_classes(class: &str) -> Vec<String> {
+    class.split_ascii_whitespace().map(|c| c.to_string()).collect()
+}
+
+// CDATA sections and references to predefined entities are converted to char data, and adjacent char data are merged
+fn merge_texts(contents: &[Content]) -> Vec<Content> {
+    let mut result = vec![];
+    let mut text = String::new();
+
+    for content in contents.iter() {
+        let curr_text = match content {
+            Content::CharData(s) | Content::CDSect(s) => Some(s.clone()),
+            Content::Reference(r) => expand_reference(&into_v16(&format!("&{};", r))).map(|expanded| from_v16(&expanded)),
+            _ => None
         };
-        let contents = self.get_inner_string();
 
-        format!(
-            "{}{}{}",
-            opening_tag,
-            contents,
-            closing_tag
-        )
+        match curr_text {
+            Some(curr_text) => {
+                text.push_str(&curr_text);
+            }
+            None => {
+
+                if !text.is_empty() {
+                    result.push(Content::CharData(text));
+                    text = String::new();
+                }
+
+                result.push(content.clone());
+            }
+        }
+
     }
 
-    #[inline]
-    pub fn get_inner_string(&self) -> String {
-        self.contents.iter().map(|c| c.to_string()).collect::<Vec<String>>().concat()
+    if !text.is_empty() {
+        result.push(Content::CharData(text));
     }
 
+    result
 }
 
 impl PartialEq for Element {
@@ -458,4 +745,32 @@ impl PartialEq for Element {
         self.pointer.ptr != super::pointer::NULL
         && self.pointer.ptr == other.pointer.ptr
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dom;
+
+    #[test]
+    fn attribute_order_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        let document = "<div class=\"a  b\" id=\"b\" title=\"c\"></div>";
+        crate::into_dom(document.to_string()).unwrap();
+        assert_eq!(dom::to_string(), document);
+
+        let div = dom::get_root();
+        let names = div.get_all_attributes().iter().map(|att| att.name.clone()).collect::<Vec<String>>();
+        assert_eq!(names, vec!["class", "id", "title"]);
+        assert_eq!(div.get_attributes().iter().map(|att| att.name.clone()).collect::<Vec<String>>(), vec!["title"]);
+        assert_eq!(div.get_attribute("class".to_string()), Some("a  b".to_string()));
+        assert_eq!(dom::get_elements_by_class_name(None, "b".to_string()), vec![div]);
+        assert_eq!(dom::get_element_by_id(None, "b".to_string()), Some(div));
+
+        div.set_attribute("id".to_string(), "d".to_string());
+        assert_eq!(dom::to_string(), "<div class=\"a  b\" id=\"d\" title=\"c\"></div>");
+
+        drop(lock);
+    }
+
+}
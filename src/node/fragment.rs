@@ -0,0 +1,306 @@
+use super::clone::CloneOptions;
+use super::element::{parse_fragment, Content};
+use super::memory;
+use super::pointer::ElementPtr;
+use crate::dom;
+use crate::err::HxmlError;
+use crate::selector::{self, matches_selector_list, parse_selector_list};
+use std::collections::HashSet;
+
+/// Contents without a parent: elements, char data, comments and so on, possibly with multiple top-level elements.
+/// The top-level elements are detached, and they're in the indices like other detached elements.
+/// Insert a fragment with `into_contents`, eg. `parent.add_contents(fragment.into_contents())`.
+/// Dropping a fragment doesn't delete its elements: use `delete` for that.
+/// An element that is deleted or moved somewhere else (eg. added to the document) is not a part of the fragment anymore:
+/// the queries and `delete` skip it.
+#[derive(Debug, Default, PartialEq)]
+pub struct DocumentFragment {
+    contents: Vec<Content>,
+}
+
+impl DocumentFragment {
+
+    pub fn new() -> Self {
+        DocumentFragment { contents: vec![] }
+    }
+
+    /// Unlike `Content::from_string`, an empty string is an empty fragment.
+    /// The global string and the errors of the document are kept, and errors have positions in `string`.
+    pub fn from_string(string: &str) -> Result<Self, HxmlError> {
+        Ok(DocumentFragment { contents: parse_fragment(string)? })
+    }
+
+    /// Elements in `contents` are detached from their parents.
+    pub fn from_contents(contents: Vec<Content>) -> Result<Self, HxmlError> {
+        let mut result = DocumentFragment::new();
+        result.add_contents(contents)?;
+
+        Ok(result)
+    }
+
+    /// It appends `contents` to the fragment. Elements in `contents` are detached from their parents.
+    /// It fails if an element in `contents` is deleted, already in the fragment, or appears multiple times.
+    pub fn add_contents(&mut self, contents: Vec<Content>) -> Result<(), HxmlError> {
+        let mut elements: Vec<ElementPtr> = vec![];
+
+        for content in contents.iter() {
+
+            if let Content::Element(element) = content {
+
+                if !memory::get(element.ptr).is_alive {
+                    return Err(HxmlError::new(String::from("cannot insert a deleted element"), usize::MAX));
+                }
+
+                if elements.contains(element) || self.contents.contains(content) {
+                    return Err(HxmlError::new(format!("<{}> appears multiple times", element.get_tag_name()), usize::MAX));
+                }
+
+                elements.push(*element);
+            }
+
+        }
+
+        for element in elements.into_iter() {
+            element.detach();
+        }
+
+        self.contents.extend(contents);
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn get_contents(&self) -> &Vec<Content> {
+        &self.contents
+    }
+
+    /// It's consumed: the elements belong to whatever the contents are inserted into.
+    #[inline]
+    pub fn into_contents(self) -> Vec<Content> {
+        self.contents
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.contents.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.contents.len() == 0
+    }
+
+    /// the top-level elements that still belong to the fragment: alive and without a parent
+    pub fn get_children(&self) -> Vec<ElementPtr> {
+        self.contents.iter().filter_map(
+            |content| match content {
+                Content::Element(element) if memory::get(element.ptr).is_alive && element.get_parent().is_none() => Some(*element),
+                _ => None
+            }
+        ).collect()
+    }
+
+    /// the top-level elements and their descendants, in document order
+    pub fn get_all_elements(&self) -> Vec<ElementPtr> {
+        let mut result = vec![];
+
+        for child in self.get_children().into_iter() {
+            result.push(child);
+            result.extend(child.descendants());
+        }
+
+        result
+    }
+
+    /// Is `element` a top-level element of the fragment or its descendant?
+    pub fn contains(&self, element: ElementPtr) -> bool {
+        self.get_children().into_iter().any(|child| child.contains(element))
+    }
+
+    // It keeps the elements in the fragment, in document order.
+    // It walks the fragment once: O(the size of the fragment + the number of the candidates).
+    fn filter(&self, elements: Vec<ElementPtr>) -> Vec<ElementPtr> {
+        let candidates = elements.iter().map(|element| element.ptr).collect::<HashSet<usize>>();
+
+        self.get_all_elements().into_iter().filter(|element| candidates.contains(&element.ptr)).collect()
+    }
+
+    pub fn get_element_by_id(&self, id: &str) -> Option<ElementPtr> {
+        self.get_elements_by_id(id).first().copied()
+    }
+
+    /// The ids in a fragment may duplicate the ones in the document.
+    pub fn get_elements_by_id(&self, id: &str) -> Vec<ElementPtr> {
+        self.filter(dom::get_id_candidates(id))
+    }
+
+    pub fn get_elements_by_tag_name(&self, tag_name: &str) -> Vec<ElementPtr> {
+        self.filter(dom::get_tag_name_candidates(tag_name))
+    }
+
+    pub fn get_elements_by_class_name(&self, class_name: &str) -> Vec<ElementPtr> {
+        self.filter(dom::get_class_name_candidates(class_name))
+    }
+
+    /// See `dom::get_elements_by_attribute`.
+    pub fn get_elements_by_attribute(&self, attribute: &str, value: &str) -> Vec<ElementPtr> {
+        self.filter(dom::get_attribute_candidates(attribute, value))
+    }
+
+    /// See `dom::create_index`.
+    pub fn get_elements_by_index(&self, name: &str, key: &str) -> Result<Vec<ElementPtr>, HxmlError> {
+        Ok(self.filter(dom::get_index_candidates(name, key)?))
+    }
+
+    /// It returns the elements in the fragment that match the CSS selectors, in document order.
+    /// The top-level elements don't have a parent, so `:root` matches them.
+    pub fn query_selector_all(&self, selectors: &str) -> Result<Vec<ElementPtr>, HxmlError> {
+        let selectors = parse_selector_list(selectors)?;
+        let mut result = vec![];
+
+        for child in self.get_children().into_iter() {
+
+            if matches_selector_list(child, &selectors) {
+                result.push(child);
+            }
+
+            result.extend(selector::query_selector_all(&selectors, Some(child)));
+        }
+
+        Ok(result)
+    }
+
+    pub fn query_selector(&self, selectors: &str) -> Result<Option<ElementPtr>, HxmlError> {
+        Ok(self.query_selector_all(selectors)?.first().copied())
+    }
+
+    /// It allocates a deep copy of the fragment. A partial can be inserted multiple times this way.
+    pub fn clone_fragment(&self) -> DocumentFragment {
+        self.clone_fragment_with_options(&CloneOptions::default())
+    }
+
+    /// See `CloneOptions` for the details. `CloneOptions::deep` applies to the top-level elements.
+    pub fn clone_fragment_with_options(&self, options: &CloneOptions) -> DocumentFragment {
+        DocumentFragment {
+            contents: self.contents.iter().map(
+                |content| match content {
+                    Content::Element(element) => Content::Element(element.clone_node_with_options(options)),
+                    _ => content.clone()
+                }
+            ).collect()
+        }
+    }
+
+    /// It deletes the elements in the fragment. Elements that have been moved somewhere else are not deleted.
+    pub fn delete(self) {
+
+        for child in self.get_children().into_iter() {
+            dom::delete(child);
+        }
+
+    }
+
+    /// It fails if the result cannot be parsed back into the same contents (eg. a comment with `--`).
+    pub fn to_xml(&self) -> Result<String, HxmlError> {
+        let mut result = String::new();
+
+        for content in self.contents.iter() {
+            result.push_str(&content.to_xml()?);
+        }
+
+        Ok(result)
+    }
+
+}
+
+impl std::fmt::Display for DocumentFragment {
+
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+
+        for content in self.contents.iter() {
+            write!(fmt, "{}", content.to_string())?;
+        }
+
+        Ok(())
+    }
+
+}
+
+impl From<DocumentFragment> for Vec<Content> {
+
+    fn from(fragment: DocumentFragment) -> Self {
+        fragment.contents
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{dom, into_dom, Content, DocumentFragment};
+
+    #[test]
+    fn fragment_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        into_dom("<page><main id=\"m\"/></page>".to_string()).unwrap();
+        let main = dom::get_element_by_id(None, "m".to_string()).unwrap();
+
+        let header = DocumentFragment::from_string("<h1 class=\"t\">a</h1> <p class=\"t\"><b id=\"x\"/></p><!--c-->").unwrap();
+        assert_eq!(header.len(), 4);
+        assert_eq!(header.get_children().len(), 2);
+        assert!(header.get_children().iter().all(|child| child.get_parent().is_none()));
+        assert_eq!(header.to_string(), "<h1 class=\"t\">a</h1> <p class=\"t\"><b id=\"x\"/></p><!--c-->");
+        assert!(DocumentFragment::from_string("").unwrap().is_empty());
+        assert!(DocumentFragment::from_string("<a>").is_err());
+
+        // queries don't see the document or the other fragments
+        let footer = DocumentFragment::from_string("<p class=\"t\" id=\"x\"/>").unwrap();
+        let names = |elements: Vec<crate::ElementPtr>| elements.iter().map(|e| e.get_tag_name()).collect::<Vec<String>>();
+        assert_eq!(names(header.get_elements_by_class_name("t")), vec!["h1", "p"]);
+        assert_eq!(names(header.get_all_elements()), vec!["h1", "p", "b"]);
+        assert_eq!(names(header.get_elements_by_id("x")), vec!["b"]);
+        assert_eq!(names(footer.get_elements_by_id("x")), vec!["p"]);
+        assert_eq!(header.get_elements_by_tag_name("main"), vec![]);
+        assert_eq!(names(header.query_selector_all(".t, p > b").unwrap()), vec!["h1", "p", "b"]);
+        assert_eq!(names(header.query_selector_all(":root").unwrap()), vec!["h1", "p"]);
+        assert!(header.contains(header.get_element_by_id("x").unwrap()));
+        assert!(!header.contains(main));
+
+        // a copy, then the fragment itself
+        let copy = header.clone_fragment();
+        main.add_contents(copy.into_contents());
+        main.insert_contents(0, header.into_contents()).unwrap();
+        assert_eq!(
+            dom::to_string(),
+            "<page><main id=\"m\"><h1 class=\"t\">a</h1> <p class=\"t\"><b id=\"x\"/></p><!--c--><h1 class=\"t\">a</h1> <p class=\"t\"><b id=\"x\"/></p><!--c--></main></page>"
+        );
+        assert_eq!(dom::get_elements_by_class_name(None, "t".to_string()).len(), 5);
+
+        // elements are moved into a fragment
+        let p = main.get_children()[1];
+        let moved = DocumentFragment::from_contents(vec![Content::Element(p), Content::CharData("!".to_string())]).unwrap();
+        assert_eq!(p.get_parent(), None);
+        assert_eq!(main.get_children().len(), 3);
+        assert_eq!(moved.to_string(), "<p class=\"t\"><b id=\"x\"/></p>!");
+        assert!(DocumentFragment::from_contents(vec![Content::Element(p), Content::Element(p)]).is_err());
+
+        moved.delete();
+        footer.delete();
+        assert_eq!(dom::get_elements_by_class_name(None, "t".to_string()).len(), 3);
+        dom::check_indices().unwrap();
+
+        // an element that is moved out of a fragment is not a part of it anymore
+        let stale = DocumentFragment::from_string("<q class=\"s\"/><r class=\"s\"/>").unwrap();
+        let q = stale.get_children()[0];
+        main.add_element_ptr(q);
+        assert_eq!(names(stale.get_elements_by_class_name("s")), vec!["r"]);
+        assert!(!stale.contains(q));
+        stale.delete();
+        assert_eq!(dom::get_elements_by_class_name(None, "s".to_string()), vec![q]);
+        assert_eq!(q.get_parent(), Some(main));
+        dom::check_indices().unwrap();
+
+        drop(lock);
+    }
+
+}
@@ -0,0 +1,429 @@
+// Lazy iterators over the tree.
+// The iterators over descendants keep a stack of (an element, the index of the next content to visit in it), from the root of the iteration,
+// so that a step doesn't have to look for the current element in its parent. The others follow the parent pointers and scan the contents of the parent.
+// The tree must not be mutated while iterating.
+
+use super::element::Content;
+use super::memory;
+use super::pointer::ElementPtr;
+
+// the index of `element` in the contents of its parent
+fn get_index_in_parent(element: ElementPtr) -> Option<usize> {
+
+    match element.get_parent() {
+        Some(parent) => memory::get(parent.ptr).contents.iter().position(
+            |content| content == &Content::Element(element)
+        ),
+        None => None
+    }
+
+}
+
+// the first child element of `element` whose index is `from` or greater
+fn get_next_child(element: ElementPtr, from: usize) -> Option<(ElementPtr, usize)> {
+    let contents = &memory::get(element.ptr).contents;
+
+    for (index, content) in contents.iter().enumerate().skip(from) {
+
+        if let Content::Element(child) = content {
+            return Some((*child, index));
+        }
+
+    }
+
+    None
+}
+
+// the last child element of `element` whose index is less than `before`
+fn get_previous_child(element: ElementPtr, before: usize) -> Option<(ElementPtr, usize)> {
+    let contents = &memory::get(element.ptr).contents;
+
+    for index in (0..before.min(contents.len())).rev() {
+
+        if let Content::Element(child) = contents[index] {
+            return Some((child, index));
+        }
+
+    }
+
+    None
+}
+
+// the next element in document order, which is not a descendant of `element`
+// It stops at `root`: it returns None instead of leaving the subtree of `root`.
+fn get_next_skipping_children(element: ElementPtr, index: usize, root: Option<ElementPtr>) -> Option<(ElementPtr, usize)> {
+    let mut curr_element = element;
+    let mut curr_index = index;
+
+    loop {
+
+        if Some(curr_element) == root {
+            return None;
+        }
+
+        let parent = match curr_element.get_parent() {
+            Some(parent) => parent,
+            None => {
+                return None;
+            }
+        };
+
+        if let Some(next) = get_next_child(parent, curr_index + 1) {
+            return Some(next);
+        }
+
+        if Some(parent) == root {
+            return None;
+        }
+
+        curr_element = parent;
+        curr_index = get_index_in_parent(parent).unwrap_or(0);
+    }
+
+}
+
+pub(crate) fn get_next_element(element: ElementPtr) -> Option<ElementPtr> {
+
+    match get_next_child(element, 0) {
+        Some((child, _)) => Some(child),
+        None => match get_index_in_parent(element) {
+            Some(index) => get_next_skipping_children(element, index, None).map(|(next, _)| next),
+            None => None
+        }
+    }
+
+}
+
+pub(crate) fn get_previous_element(element: ElementPtr) -> Option<ElementPtr> {
+    let parent = element.get_parent()?;
+    let index = get_index_in_parent(element)?;
+
+    match get_previous_child(parent, index) {
+
+        // the last descendant of the previous sibling
+        Some((mut curr_element, _)) => {
+
+            while let Some((child, _)) = get_previous_child(curr_element, usize::MAX) {
+                curr_element = child;
+            }
+
+            Some(curr_element)
+        }
+        None => Some(parent)
+    }
+
+}
+
+/// `ElementPtr::children`
+pub struct Children {
+    parent: ElementPtr,
+    next: Option<(ElementPtr, usize)>,
+}
+
+impl Children {
+
+    pub(crate) fn new(parent: ElementPtr) -> Self {
+        Children {
+            parent,
+            next: get_next_child(parent, 0),
+        }
+    }
+
+}
+
+impl Iterator for Children {
+    type Item = ElementPtr;
+
+    fn next(&mut self) -> Option<ElementPtr> {
+        let (result, index) = self.next?;
+        self.next = get_next_child(self.parent, index + 1);
+
+        Some(result)
+    }
+}
+
+/// `ElementPtr::descendants`, in pre-order
+pub struct Descendants {
+    stack: Vec<(ElementPtr, usize)>,
+}
+
+impl Descendants {
+
+    pub(crate) fn new(root: ElementPtr) -> Self {
+        Descendants {
+            stack: vec![(root, 0)],
+        }
+    }
+
+}
+
+impl Iterator for Descendants {
+    type Item = ElementPtr;
+
+    fn next(&mut self) -> Option<ElementPtr> {
+
+        loop {
+            let (parent, from) = *self.stack.last()?;
+
+            match get_next_child(parent, from) {
+                Some((child, index)) => {
+                    self.stack.last_mut().unwrap().1 = index + 1;
+                    self.stack.push((child, 0));
+
+                    return Some(child);
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+
+        }
+
+    }
+}
+
+/// `ElementPtr::descendants_post_order`
+pub struct PostOrderDescendants {
+    stack: Vec<(ElementPtr, usize)>,
+}
+
+impl PostOrderDescendants {
+
+    pub(crate) fn new(root: ElementPtr) -> Self {
+        PostOrderDescendants {
+            stack: vec![(root, 0)],
+        }
+    }
+
+}
+
+impl Iterator for PostOrderDescendants {
+    type Item = ElementPtr;
+
+    // An element is returned after all of its children, and the root is not returned.
+    fn next(&mut self) -> Option<ElementPtr> {
+
+        loop {
+            let (parent, from) = *self.stack.last()?;
+
+            match get_next_child(parent, from) {
+                Some((child, index)) => {
+                    self.stack.last_mut().unwrap().1 = index + 1;
+                    self.stack.push((child, 0));
+                }
+                None => {
+                    self.stack.pop();
+
+                    return if self.stack.is_empty() { None } else { Some(parent) };
+                }
+            }
+
+        }
+
+    }
+}
+
+/// `ElementPtr::ancestors`, from the parent to the root
+pub struct Ancestors {
+    next: Option<ElementPtr>,
+}
+
+impl Ancestors {
+
+    pub(crate) fn new(element: ElementPtr) -> Self {
+        Ancestors {
+            next: element.get_parent(),
+        }
+    }
+
+}
+
+impl Iterator for Ancestors {
+    type Item = ElementPtr;
+
+    fn next(&mut self) -> Option<ElementPtr> {
+        let result = self.next?;
+        self.next = result.get_parent();
+
+        Some(result)
+    }
+}
+
+/// `ElementPtr::following_siblings` and `ElementPtr::preceding_siblings`, from the nearest one
+pub struct Siblings {
+    parent: Option<ElementPtr>,
+    next: Option<(ElementPtr, usize)>,
+    is_forward: bool,
+}
+
+impl Siblings {
+
+    pub(crate) fn new(element: ElementPtr, is_forward: bool) -> Self {
+        let parent = element.get_parent();
+
+        let next = match (parent, get_index_in_parent(element)) {
+            (Some(parent), Some(index)) if is_forward => get_next_child(parent, index + 1),
+            (Some(parent), Some(index)) => get_previous_child(parent, index),
+            _ => None
+        };
+
+        Siblings { parent, next, is_forward }
+    }
+
+}
+
+impl Iterator for Siblings {
+    type Item = ElementPtr;
+
+    fn next(&mut self) -> Option<ElementPtr> {
+        let (result, index) = self.next?;
+        let parent = self.parent.unwrap();
+
+        self.next = if self.is_forward {
+            get_next_child(parent, index + 1)
+        } else {
+            get_previous_child(parent, index)
+        };
+
+        Some(result)
+    }
+}
+
+/// A content in the tree, which `ElementPtr::descendant_contents` returns.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ContentNode {
+
+    /// the element that has this content
+    pub parent: ElementPtr,
+
+    /// the index in the contents of `parent`
+    pub index: usize,
+
+    /// 1 for the contents of the element where the iteration starts, 2 for the contents of its children...
+    pub depth: usize,
+}
+
+impl ContentNode {
+
+    pub fn get_content(&self) -> &Content {
+        &memory::get(self.parent.ptr).contents[self.index]
+    }
+
+}
+
+/// `ElementPtr::descendant_contents`, in pre-order
+pub struct DescendantContents {
+
+    // the depth of a content is the length of the stack when it's returned
+    stack: Vec<(ElementPtr, usize)>,
+}
+
+impl DescendantContents {
+
+    pub(crate) fn new(root: ElementPtr) -> Self {
+        DescendantContents {
+            stack: vec![(root, 0)],
+        }
+    }
+
+}
+
+impl Iterator for DescendantContents {
+    type Item = ContentNode;
+
+    fn next(&mut self) -> Option<ContentNode> {
+
+        loop {
+            let (parent, index) = *self.stack.last()?;
+            let contents = &memory::get(parent.ptr).contents;
+
+            if index >= contents.len() {
+                self.stack.pop();
+                continue;
+            }
+
+            self.stack.last_mut().unwrap().1 = index + 1;
+            let result = ContentNode { parent, index, depth: self.stack.len() };
+
+            if let Content::Element(child) = contents[index] {
+                self.stack.push((child, 0));
+            }
+
+            return Some(result);
+        }
+
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{dom, into_dom, Content, ElementPtr};
+
+    fn names(elements: impl Iterator<Item = ElementPtr>) -> Vec<String> {
+        elements.map(|e| e.get_tag_name()).collect()
+    }
+
+    #[test]
+    fn iter_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        into_dom("<r>0<a>1<b/>2<c><d/></c></a><!--3--><e><f/></e>4<g/></r>".to_string()).unwrap();
+        let root = dom::get_root();
+        let c = dom::get_element_by_tag_name(None, "c".to_string()).unwrap();
+        let e = dom::get_element_by_tag_name(None, "e".to_string()).unwrap();
+
+        assert_eq!(names(root.children()), vec!["a", "e", "g"]);
+        assert_eq!(names(root.descendants()), vec!["a", "b", "c", "d", "e", "f", "g"]);
+        assert_eq!(names(root.descendants_post_order()), vec!["b", "d", "c", "a", "f", "e", "g"]);
+        assert_eq!(names(c.descendants()), vec!["d"]);
+        assert_eq!(names(c.descendants_post_order()), vec!["d"]);
+        assert_eq!(names(e.children().next().unwrap().descendants()), Vec::<String>::new());
+        assert_eq!(names(c.ancestors()), vec!["a", "r"]);
+        assert_eq!(names(e.following_siblings()), vec!["g"]);
+        assert_eq!(names(e.preceding_siblings()), vec!["a"]);
+        assert_eq!(names(root.following_siblings()), Vec::<String>::new());
+
+        // document order
+        let mut forward = vec![];
+        let mut curr_element = Some(root);
+
+        while let Some(element) = curr_element {
+            forward.push(element.get_tag_name());
+            curr_element = element.next_element();
+        }
+
+        let mut backward = vec![];
+        let mut curr_element = dom::get_element_by_tag_name(None, "g".to_string());
+
+        while let Some(element) = curr_element {
+            backward.push(element.get_tag_name());
+            curr_element = element.previous_element();
+        }
+
+        backward.reverse();
+        assert_eq!(forward, vec!["r", "a", "b", "c", "d", "e", "f", "g"]);
+        assert_eq!(forward, backward);
+
+        // contents
+        let contents = root.descendant_contents().map(
+            |node| (
+                match node.get_content() {
+                    Content::Element(element) => element.get_tag_name(),
+                    Content::CharData(s) | Content::Comment(s) => s.to_string(),
+                    _ => unreachable!()
+                },
+                node.parent.get_tag_name(),
+                node.depth
+            )
+        ).collect::<Vec<(String, String, usize)>>();
+        let answer = vec![
+            ("0", "r", 1), ("a", "r", 1), ("1", "a", 2), ("b", "a", 2), ("2", "a", 2), ("c", "a", 2), ("d", "c", 3),
+            ("3", "r", 1), ("e", "r", 1), ("f", "e", 2), ("4", "r", 1), ("g", "r", 1),
+        ].into_iter().map(|(s, parent, depth)| (s.to_string(), parent.to_string(), depth)).collect::<Vec<(String, String, usize)>>();
+        assert_eq!(contents, answer);
+        assert_eq!(c.descendant_contents().count(), 1);
+
+        drop(lock);
+    }
+
+}
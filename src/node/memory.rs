@@ -1,11 +1,14 @@
 use super::element::Element;
 use super::pointer::ElementPtr;
+use crate::order;
 
 // IT'S NOT THREAD-SAFE!!!!
 static mut FREE_LIST: Vec<usize> = vec![];
 pub static mut ELEMENTS: Vec<Element> = vec![];
 
 pub fn init() {
+    order::invalidate();
+
     unsafe {
         ELEMENTS = vec![];
         FREE_LIST = vec![];
@@ -13,6 +16,8 @@ pub fn init() {
 }
 
 pub fn delete(pointer: ElementPtr) {
+    order::invalidate();
+
     unsafe {
         ELEMENTS[pointer.ptr].is_alive = false;
         FREE_LIST.push(pointer.ptr);
@@ -25,6 +30,7 @@ pub fn delete(pointer: ElementPtr) {
 }
 
 pub fn allocate(mut element: Element) -> ElementPtr {
+    order::invalidate();
 
     unsafe {
 
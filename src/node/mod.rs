@@ -1,6 +1,10 @@
 pub mod attribute;
+pub mod clone;
 pub mod element;
+pub mod fragment;
+pub mod iter;
 pub mod memory;
 pub mod pointer;
 pub mod prolog;
-pub mod raw_element;
\ No newline at end of file
+pub mod raw_element;
+pub mod source;
\ No newline at end of file
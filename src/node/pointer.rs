@@ -1,6 +1,133 @@
 use super::memory;
-use super::element::Content;
+use super::element::{parse_fragment, Content};
 use super::attribute::Attribute;
+use super::clone::CloneOptions;
+use super::iter::{self, Ancestors, Children, DescendantContents, Descendants, PostOrderDescendants, Siblings};
+use crate::c14n::{canonicalize_element, C14nOptions};
+use crate::dom;
+use crate::encoding::{self, Encoding};
+use crate::err::HxmlError;
+use crate::minify::{minify_element, Minified, MinifyOptions};
+use crate::order;
+use crate::pretty::{pretty_element, PrettyOptions};
+use crate::selector::{self, matches_selector_list, parse_selector_list};
+use crate::xpath::{XPath, XPathNode, XPathValue};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::Write;
+
+// It parses `xml` into contents of `target`, and checks that their prefixes are bound: by the declarations in `xml`,
+// or the ones in scope at `target`. Nothing is left allocated if it fails.
+fn parse_fragment_at(xml: &str, target: ElementPtr) -> Result<Vec<Content>, HxmlError> {
+    let contents = parse_fragment(xml)?;
+    let prefixes = get_in_scope_prefixes(target);
+
+    for content in contents.iter() {
+
+        if let Content::Element(element) = content {
+
+            if let Err(e) = check_prefixes(*element, &prefixes) {
+
+                for content in contents.iter() {
+
+                    if let Content::Element(element) = content {
+                        dom::delete(*element);
+                    }
+
+                }
+
+                return Err(e);
+            }
+
+        }
+
+    }
+
+    Ok(contents)
+}
+
+// prefix -> namespace URI, of the `xmlns:*` declarations on `element` and its ancestors (the nearest one wins)
+fn get_in_scope_prefixes(element: ElementPtr) -> HashMap<String, String> {
+    let mut result = HashMap::new();
+
+    for curr_element in std::iter::once(element).chain(element.ancestors()) {
+        push_declarations(curr_element, &mut result);
+    }
+
+    result
+}
+
+// Declarations that are already in `prefixes` are nearer, so they're kept.
+fn push_declarations(element: ElementPtr, prefixes: &mut HashMap<String, String>) {
+
+    for attribute in memory::get(element.ptr).attributes.iter() {
+
+        if let Some(prefix) = attribute.name.strip_prefix("xmlns:") {
+            prefixes.entry(prefix.to_string()).or_insert_with(|| attribute.value.clone());
+        }
+
+    }
+
+}
+
+// https://www.w3.org/TR/xml-names/#nsc-NSDeclared
+// `prefixes` are the ones in scope at the parent of `element`.
+fn check_prefixes(element: ElementPtr, prefixes: &HashMap<String, String>) -> Result<(), HxmlError> {
+    let el = memory::get(element.ptr);
+    let mut prefixes = prefixes;
+    let mut own_prefixes;
+
+    if el.attributes.iter().any(|attribute| attribute.name.starts_with("xmlns:")) {
+        own_prefixes = HashMap::new();
+        push_declarations(element, &mut own_prefixes);
+
+        for (prefix, uri) in prefixes.iter() {
+            own_prefixes.entry(prefix.to_string()).or_insert_with(|| uri.to_string());
+        }
+
+        prefixes = &own_prefixes;
+    }
+
+    let names = std::iter::once(&el.tag_name).chain(el.attributes.iter().map(|attribute| &attribute.name));
+
+    for name in names {
+
+        match name.split_once(':') {
+            Some(("xmlns", _)) | Some(("xml", _)) | None => {}
+            Some((prefix, _)) => {
+
+                if prefixes.get(prefix).is_none_or(|uri| uri.is_empty()) {
+                    return Err(HxmlError::new(format!("the prefix `{}` of `{}` is not declared", prefix, name), usize::MAX));
+                }
+
+            }
+        }
+
+    }
+
+    for child in element.children() {
+        check_prefixes(child, prefixes)?;
+    }
+
+    Ok(())
+}
+
+/// `ElementPtr::insert_adjacent_xml`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AdjacentPosition {
+
+    /// before the element, as its previous siblings
+    BeforeBegin,
+
+    /// before the first content of the element
+    AfterBegin,
+
+    /// after the last content of the element
+    BeforeEnd,
+
+    /// after the element, as its next siblings
+    AfterEnd,
+}
 
 #[derive(Copy, Clone, Debug)]
 pub struct ElementPtr {
@@ -25,14 +152,137 @@ impl ElementPtr {
         memory::get(self.ptr).to_string()
     }
 
+    /// It's like `to_string`, but it streams the output to `w`.
+    #[inline]
+    pub fn write_to<W: Write>(&self, w: &mut W) -> Result<(), HxmlError> {
+        memory::get(self.ptr).write_to(w)
+    }
+
+    /// It writes the subtree in HTML syntax. See `Element::to_html` for the details.
+    #[inline]
+    pub fn to_html(&self) -> Result<String, HxmlError> {
+        memory::get(self.ptr).to_html()
+    }
+
+    /// It's like `to_html`, but it streams the output to `w`.
+    #[inline]
+    pub fn write_html_to<W: Write>(&self, w: &mut W) -> Result<(), HxmlError> {
+        memory::get(self.ptr).write_html_to(w)
+    }
+
+    /// It serializes the subtree in `encoding`, without an XML declaration.
+    /// Characters that `encoding` cannot represent are written as numeric character references.
+    pub fn to_bytes(&self, encoding: Encoding) -> Result<Vec<u8>, HxmlError> {
+        encoding::encode(&self.to_string(), encoding)
+    }
+
+    #[inline]
+    pub fn to_xml(&self) -> Result<String, HxmlError> {
+        memory::get(self.ptr).to_xml()
+    }
+
+    /// It re-indents the subtree. See `PrettyOptions` for the details.
+    #[inline]
+    pub fn to_pretty_string(&self, options: &PrettyOptions) -> String {
+        pretty_element(memory::get(self.ptr), options)
+    }
+
+    /// Canonical XML of the subtree. See `C14nOptions` for the details.
+    #[inline]
+    pub fn canonicalize(&self, options: &C14nOptions) -> String {
+        canonicalize_element(memory::get(self.ptr), options)
+    }
+
+    /// See `MinifyOptions` for the details.
+    /// It fails in HTML mode if a `<script>` or a `<style>` contains its own end tag.
+    pub fn minify(&self, options: &MinifyOptions) -> Result<Minified, HxmlError> {
+        let output = minify_element(memory::get(self.ptr), options)?;
+
+        Ok(Minified {
+            bytes_saved: self.to_string().len().max(output.len()) - output.len(),
+            output,
+        })
+    }
+
+    /// It returns the descendants with the tag name, in document order.
+    /// It takes the candidates from the global index, and checks if they are descendants in O(1).
+    /// After mutations that change the structure (adding, moving or deleting elements), the first query renumbers every element, in O(n).
+    pub fn get_elements_by_tag_name(&self, tag_name: &str) -> Vec<ElementPtr> {
+        order::filter_descendants(dom::get_tag_name_candidates(tag_name), *self)
+    }
+
+    /// It returns the descendants with the class, in document order. See `get_elements_by_tag_name` for the cost.
+    pub fn get_elements_by_class_name(&self, class_name: &str) -> Vec<ElementPtr> {
+        order::filter_descendants(dom::get_class_name_candidates(class_name), *self)
+    }
+
+    /// It returns the descendants whose `attribute` is `value`, in document order.
+    /// See `dom::get_elements_by_attribute`.
+    pub fn get_elements_by_attribute(&self, attribute: &str, value: &str) -> Vec<ElementPtr> {
+        order::filter_descendants(dom::get_attribute_candidates(attribute, value), *self)
+    }
+
+    /// It returns the descendants in the index `name` under `key`, in document order.
+    /// See `dom::create_index`.
+    pub fn get_elements_by_index(&self, name: &str, key: &str) -> Result<Vec<ElementPtr>, HxmlError> {
+        Ok(order::filter_descendants(dom::get_index_candidates(name, key)?, *self))
+    }
+
+    /// Is `other` this element or one of its descendants?
+    /// O(1), or O(n) right after mutations that change the structure. See `get_elements_by_tag_name`.
+    #[inline]
+    pub fn contains(&self, other: ElementPtr) -> bool {
+        *self == other || order::is_descendant(other, *self)
+    }
+
+    /// `Ordering::Less` if this element comes before `other` in document order.
+    /// An element comes before its descendants.
+    /// O(1), or O(n) right after mutations that change the structure. See `get_elements_by_tag_name`.
+    #[inline]
+    pub fn compare_document_order(&self, other: ElementPtr) -> Ordering {
+        order::compare(*self, other)
+    }
+
+    /// It returns the descendants that match the CSS selectors, in document order.
+    /// Like `Element.querySelectorAll` of DOM, the selectors are matched against the entire document:
+    /// `div p` matches `p`s in this element even if the `div` is an ancestor of this element.
+    pub fn query_selector_all(&self, selectors: &str) -> Result<Vec<ElementPtr>, HxmlError> {
+        Ok(selector::query_selector_all(&parse_selector_list(selectors)?, Some(*self)))
+    }
+
+    pub fn query_selector(&self, selectors: &str) -> Result<Option<ElementPtr>, HxmlError> {
+        Ok(self.query_selector_all(selectors)?.first().copied())
+    }
+
+    /// Does this element match the CSS selectors?
+    pub fn matches(&self, selectors: &str) -> Result<bool, HxmlError> {
+        Ok(matches_selector_list(*self, &parse_selector_list(selectors)?))
+    }
+
+    /// It compiles and evaluates an XPath 1.0 expression, with this element as the context node.
+    pub fn evaluate_xpath(&self, expression: &str) -> Result<XPathValue, HxmlError> {
+        XPath::compile(expression)?.evaluate(XPathNode::Element(*self))
+    }
+
+    #[inline]
+    pub fn is_same_tree(&self, other: ElementPtr) -> bool {
+        memory::get(self.ptr).is_same_tree(memory::get(other.ptr))
+    }
+
     #[inline]
     pub fn get_inner_string(&self) -> String {
         memory::get(self.ptr).get_inner_string()
     }
 
+    /// It does nothing and returns false in the same cases as `add_contents`.
+    #[inline]
+    pub fn add_element_ptr(&self, element_ptr: ElementPtr) -> bool {
+        memory::get_mut(self.ptr).add_element_ptr(element_ptr)
+    }
+
     #[inline]
-    pub fn add_element_ptr(&self, element_ptr: ElementPtr) {
-        memory::get_mut(self.ptr).add_element_ptr(element_ptr);
+    pub fn add_char_data(&self, char_data: String) {
+        memory::get_mut(self.ptr).add_char_data(char_data);
     }
 
     #[inline]
@@ -45,9 +295,197 @@ impl ElementPtr {
         memory::get_mut(self.ptr).get_contents_mut()
     }
 
+    /// It allocates a copy of this element (and its descendants if `deep` is set), with the same ids.
+    /// The copy doesn't have a parent: insert it with `add_contents`, `insert_after` and so on.
     #[inline]
-    pub fn add_contents(&self, contents: Vec<Content>) {
-        memory::get_mut(self.ptr).add_contents(contents);
+    pub fn clone_node(&self, deep: bool) -> ElementPtr {
+        memory::get(self.ptr).clone_node(deep)
+    }
+
+    /// See `CloneOptions` for the details.
+    #[inline]
+    pub fn clone_node_with_options(&self, options: &CloneOptions) -> ElementPtr {
+        memory::get(self.ptr).clone_node_with_options(options)
+    }
+
+    /// Elements in `contents` are moved from their parents.
+    /// If an element in `contents` is this element, its ancestor or deleted, it does nothing and returns false.
+    /// Use `append` to get the error.
+    #[inline]
+    pub fn add_contents(&self, contents: Vec<Content>) -> bool {
+        memory::get_mut(self.ptr).add_contents(contents)
+    }
+
+    /// It inserts `contents` at `index` of the contents of this element. Elements in `contents` are moved from their parents.
+    /// It fails if an element in `contents` is this element or its ancestor (it would make a cycle), or deleted.
+    #[inline]
+    pub fn insert_contents(&self, index: usize, contents: Vec<Content>) -> Result<(), HxmlError> {
+        memory::get_mut(self.ptr).insert_contents(index, contents)
+    }
+
+    /// It inserts `contents` before the first content of this element.
+    #[inline]
+    pub fn prepend(&self, contents: Vec<Content>) -> Result<(), HxmlError> {
+        memory::get_mut(self.ptr).prepend(contents)
+    }
+
+    /// It inserts `contents` after the last content of this element.
+    /// It fails in the same cases as `insert_contents`.
+    #[inline]
+    pub fn append(&self, contents: Vec<Content>) -> Result<(), HxmlError> {
+        memory::get_mut(self.ptr).append(contents)
+    }
+
+    /// It inserts `contents` before this element, as its siblings.
+    /// It fails if this element doesn't have a parent.
+    pub fn insert_before(&self, contents: Vec<Content>) -> Result<(), HxmlError> {
+        let (parent, index) = self.get_position()?;
+        parent.insert_contents(index, contents)
+    }
+
+    /// It inserts `contents` after this element, as its siblings.
+    /// It fails if this element doesn't have a parent.
+    pub fn insert_after(&self, contents: Vec<Content>) -> Result<(), HxmlError> {
+        let (parent, index) = self.get_position()?;
+        parent.insert_contents(index + 1, contents)
+    }
+
+    /// It replaces this element with `contents`. This element is detached, but not deleted.
+    /// It fails if this element doesn't have a parent.
+    pub fn replace_with(&self, contents: Vec<Content>) -> Result<(), HxmlError> {
+        let is_replaced = !contents.contains(&Content::Element(*self));
+        self.insert_before(contents)?;
+
+        if is_replaced {
+            self.detach();
+        }
+
+        Ok(())
+    }
+
+    /// `wrapper` takes the place of this element, and this element becomes the last content of `wrapper`.
+    /// `wrapper` is moved from its parent. It fails if `wrapper` is this element or its descendant.
+    pub fn wrap(&self, wrapper: ElementPtr) -> Result<(), HxmlError> {
+
+        if wrapper == *self || wrapper.ancestors().any(|ancestor| ancestor == *self) {
+            return Err(HxmlError::new(String::from("cannot wrap an element with itself or its descendant"), usize::MAX));
+        }
+
+        match self.get_parent() {
+            Some(_) => {
+                self.insert_before(vec![Content::Element(wrapper)])?;
+            }
+            None => {
+                wrapper.detach();
+            }
+        }
+
+        wrapper.insert_contents(wrapper.get_contents().len(), vec![Content::Element(*self)])
+    }
+
+    /// It replaces this element with its contents. This element is detached and empty, but not deleted.
+    /// It fails if this element doesn't have a parent.
+    pub fn unwrap(&self) -> Result<(), HxmlError> {
+        let contents = self.get_contents().clone();
+        self.replace_with(contents)?;
+        memory::get_mut(self.ptr).contents.clear();
+        memory::get(self.ptr).update_custom_indices();
+
+        Ok(())
+    }
+
+    /// It removes this element from its parent, without deleting it. Use `dom::delete` to delete it.
+    /// The element and its descendants are still in the indices, and can be inserted again.
+    #[inline]
+    pub fn detach(&self) {
+
+        if let Some(parent) = self.get_parent() {
+            parent.delete_child_element(*self);
+        }
+
+    }
+
+    /// It deletes all the contents, including the child elements and their descendants.
+    #[inline]
+    pub fn delete_contents(&self) {
+        memory::get_mut(self.ptr).delete_contents();
+    }
+
+    /// It deletes the contents of this element, and parses `xml` into new contents.
+    /// Prefixes in `xml` are resolved by the declarations in `xml` and the ones in scope at this element (on it and its ancestors).
+    /// It fails if a prefix is declared in neither.
+    /// If `xml` is not well-formed, the error has a position in `xml`, and the element is not changed.
+    pub fn set_inner_xml(&self, xml: &str) -> Result<(), HxmlError> {
+        let contents = parse_fragment_at(xml, *self)?;
+        memory::get_mut(self.ptr).delete_contents();
+
+        self.insert_contents(0, contents)
+    }
+
+    /// It replaces this element with the contents parsed from `xml`, and deletes this element.
+    /// It fails if this element doesn't have a parent. See `set_inner_xml` for the details.
+    pub fn set_outer_xml(&self, xml: &str) -> Result<(), HxmlError> {
+        let (parent, _) = self.get_position()?;
+        let contents = parse_fragment_at(xml, parent)?;
+        self.replace_with(contents)?;
+        dom::delete(*self);
+
+        Ok(())
+    }
+
+    /// It parses `xml` and inserts the contents at `position`, like `Element.insertAdjacentHTML` of DOM.
+    /// `BeforeBegin` and `AfterEnd` fail if this element doesn't have a parent. See `set_inner_xml` for the details.
+    pub fn insert_adjacent_xml(&self, position: AdjacentPosition, xml: &str) -> Result<(), HxmlError> {
+
+        // the element that will have the contents
+        let target = match position {
+            AdjacentPosition::BeforeBegin | AdjacentPosition::AfterEnd => self.get_position()?.0,
+            AdjacentPosition::AfterBegin | AdjacentPosition::BeforeEnd => *self,
+        };
+
+        let contents = parse_fragment_at(xml, target)?;
+
+        match position {
+            AdjacentPosition::BeforeBegin => self.insert_before(contents),
+            AdjacentPosition::AfterBegin => self.prepend(contents),
+            AdjacentPosition::BeforeEnd => self.insert_contents(self.get_contents().len(), contents),
+            AdjacentPosition::AfterEnd => self.insert_after(contents),
+        }
+
+    }
+
+    // (the parent, the index in the contents of the parent)
+    fn get_position(&self) -> Result<(ElementPtr, usize), HxmlError> {
+
+        match self.get_parent() {
+            Some(parent) => Ok((
+                parent,
+                parent.get_contents().iter().position(|content| content == &Content::Element(*self)).unwrap()
+            )),
+            None => Err(HxmlError::new(format!("<{}> doesn't have a parent", self.get_tag_name()), usize::MAX))
+        }
+
+    }
+
+    /// The text of the descendants: char data, CDATA sections and expanded references, without comments.
+    /// References to entities other than the predefined ones (eg. `&nbsp;`) are left as they are.
+    #[inline]
+    pub fn text_content(&self) -> String {
+        memory::get(self.ptr).text_content()
+    }
+
+    /// It deletes the contents of this element (including the child elements), and adds `text` as char data.
+    #[inline]
+    pub fn set_text_content(&self, text: String) {
+        memory::get_mut(self.ptr).set_text_content(text);
+    }
+
+    /// An approximation of the rendered text of an HTML element, like `HTMLElement.innerText` without CSS.
+    /// Block elements are on their own lines, `<br>` becomes a line break, `<script>` and `<style>` are skipped,
+    /// and whitespaces are collapsed except in `<pre>`.
+    #[inline]
+    pub fn inner_text(&self) -> String {
+        memory::get(self.ptr).inner_text()
     }
 
     #[inline]
@@ -55,8 +493,16 @@ impl ElementPtr {
         memory::get_mut(self.ptr).get_attribute(attribute)
     }
 
+    /// the attributes except `id` and `class`, in the original order
+    /// Use `get_all_attributes` to get all of them.
     #[inline]
-    pub fn get_attributes(&self) -> &Vec<Attribute> {
+    pub fn get_attributes(&self) -> Vec<Attribute> {
+        memory::get(self.ptr).attributes.iter().filter(|att| att.name != "id" && att.name != "class").cloned().collect()
+    }
+
+    /// All the attributes, including `id` and `class`, in the original order.
+    #[inline]
+    pub fn get_all_attributes(&self) -> &Vec<Attribute> {
         &memory::get(self.ptr).attributes
     }
 
@@ -65,11 +511,39 @@ impl ElementPtr {
         memory::get(self.ptr).tag_name.clone()
     }
 
+    /// 1-based line and column of the start tag in the string it's parsed from.
+    /// It's None if the element is created by `Element::new` or copied by `clone_node`.
+    #[inline]
+    pub fn get_source_location(&self) -> Option<(usize, usize)> {
+        memory::get(self.ptr).source_location
+    }
+
     #[inline]
     pub fn set_attribute(&self, attribute: String, value: String) {
         memory::get_mut(self.ptr).set_attribute(attribute, value);
     }
 
+    #[inline]
+    pub fn has_class(&self, class: &str) -> bool {
+        memory::get(self.ptr).has_class(class)
+    }
+
+    #[inline]
+    pub fn add_class(&self, class: &str) {
+        memory::get_mut(self.ptr).add_class(class);
+    }
+
+    #[inline]
+    pub fn remove_class(&self, class: &str) {
+        memory::get_mut(self.ptr).remove_class(class);
+    }
+
+    /// It returns true if the element has `class` after the toggle.
+    #[inline]
+    pub fn toggle_class(&self, class: &str) -> bool {
+        memory::get_mut(self.ptr).toggle_class(class)
+    }
+
     #[inline]
     pub fn get_parent(&self) -> Option<ElementPtr> {
         memory::get(self.ptr).get_parent()
@@ -85,6 +559,60 @@ impl ElementPtr {
         memory::get(self.ptr).get_children()
     }
 
+    /// It's like `get_children`, but lazy.
+    #[inline]
+    pub fn children(&self) -> Children {
+        Children::new(*self)
+    }
+
+    /// Descendants in pre-order (document order), without this element.
+    #[inline]
+    pub fn descendants(&self) -> Descendants {
+        Descendants::new(*self)
+    }
+
+    /// Descendants in post-order: children come before their parents.
+    #[inline]
+    pub fn descendants_post_order(&self) -> PostOrderDescendants {
+        PostOrderDescendants::new(*self)
+    }
+
+    /// From the parent to the root.
+    #[inline]
+    pub fn ancestors(&self) -> Ancestors {
+        Ancestors::new(*self)
+    }
+
+    /// Sibling elements after this element, from the nearest one.
+    #[inline]
+    pub fn following_siblings(&self) -> Siblings {
+        Siblings::new(*self, true)
+    }
+
+    /// Sibling elements before this element, from the nearest one.
+    #[inline]
+    pub fn preceding_siblings(&self) -> Siblings {
+        Siblings::new(*self, false)
+    }
+
+    /// The next element in document order: the first child, or the next sibling of this element or its nearest ancestor.
+    #[inline]
+    pub fn next_element(&self) -> Option<ElementPtr> {
+        iter::get_next_element(*self)
+    }
+
+    /// The previous element in document order: the last descendant of the previous sibling, or the parent.
+    #[inline]
+    pub fn previous_element(&self) -> Option<ElementPtr> {
+        iter::get_previous_element(*self)
+    }
+
+    /// All the contents in the subtree (elements, texts, comments...) in pre-order, with their parents and depths.
+    #[inline]
+    pub fn descendant_contents(&self) -> DescendantContents {
+        DescendantContents::new(*self)
+    }
+
     #[inline]
     pub fn get_siblings(&self) -> Vec<ElementPtr> {
         memory::get(self.ptr).get_siblings()
@@ -112,4 +640,140 @@ impl PartialEq for ElementPtr {
         self.ptr != super::pointer::NULL
         && self.ptr == other.ptr
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dom;
+
+    #[test]
+    fn mutation_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        crate::into_dom("<r><a><b/></a><c/></r>".to_string()).unwrap();
+        let get = |tag_name: &str| dom::get_element_by_tag_name(None, tag_name.to_string()).unwrap();
+        let new_element = |s: &str| crate::Element::from_string(s.to_string()).unwrap();
+        let (r, a, b, c) = (get("r"), get("a"), get("b"), get("c"));
+
+        // cycles
+        assert!(b.insert_contents(0, vec![crate::Content::Element(a)]).is_err());
+        assert!(a.prepend(vec![crate::Content::Element(a)]).is_err());
+        assert!(a.insert_contents(3, vec![]).is_err());
+        assert!(b.wrap(b).is_err());
+        assert!(a.wrap(b).is_err());
+        assert!(r.insert_before(vec![]).is_err());
+        assert!(b.append(vec![crate::Content::Element(r)]).is_err());
+        assert!(!b.add_element_ptr(a));
+        assert!(!b.add_contents(vec![crate::Content::CharData("x".to_string()), crate::Content::Element(r)]));
+        assert_eq!(dom::to_string(), "<r><a><b/></a><c/></r>");
+
+        // moving
+        c.insert_before(vec![crate::Content::Element(b)]).unwrap();
+        assert_eq!(dom::to_string(), "<r><a></a><b/><c/></r>");
+        assert_eq!(b.get_parent(), Some(r));
+
+        a.prepend(vec![crate::Content::CharData("x".to_string()), crate::Content::Element(c)]).unwrap();
+        assert_eq!(dom::to_string(), "<r><a>x<c/></a><b/></r>");
+
+        r.insert_contents(2, vec![crate::Content::Element(a)]).unwrap();
+        assert_eq!(dom::to_string(), "<r><b/><a>x<c/></a></r>");
+
+        b.insert_after(vec![crate::Content::Comment("y".to_string())]).unwrap();
+        assert_eq!(dom::to_string(), "<r><b/><!--y--><a>x<c/></a></r>");
+
+        // replace, wrap, unwrap
+        let d = new_element("<d id=\"d\"/>");
+        b.replace_with(vec![crate::Content::Element(d)]).unwrap();
+        assert_eq!(dom::to_string(), "<r><d id=\"d\"/><!--y--><a>x<c/></a></r>");
+        assert_eq!(b.get_parent(), None);
+
+        c.wrap(b).unwrap();
+        assert_eq!(dom::to_string(), "<r><d id=\"d\"/><!--y--><a>x<b><c/></b></a></r>");
+
+        a.unwrap().unwrap();
+        assert_eq!(dom::to_string(), "<r><d id=\"d\"/><!--y-->x<b><c/></b></r>");
+        assert_eq!(a.get_parent(), None);
+        assert_eq!(a.get_contents().len(), 0);
+
+        // the root
+        let e = new_element("<e/>");
+        r.wrap(e).unwrap();
+        assert_eq!(dom::get_root(), e);
+        assert_eq!(dom::to_string(), "<e><r><d id=\"d\"/><!--y-->x<b><c/></b></r></e>");
+
+        // deleted elements
+        let f = new_element("<f/>");
+        dom::delete(f);
+        assert!(!r.add_element_ptr(f));
+        assert!(r.append(vec![crate::Content::Element(f)]).is_err());
+        assert!(r.add_contents(vec![crate::Content::CharData("z".to_string())]));
+        assert_eq!(dom::to_string(), "<e><r><d id=\"d\"/><!--y-->x<b><c/></b>z</r></e>");
+
+        d.detach();
+        assert_eq!(dom::get_element_by_id(None, "d".to_string()), Some(d));
+        assert_eq!(dom::get_root().get_elements_by_tag_name("d"), vec![]);
+        dom::check_indices().unwrap();
+
+        drop(lock);
+    }
+
+    #[test]
+    fn inner_xml_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        crate::into_dom("<r xmlns:x=\"urn:x\"><a><old id=\"old\"/></a><b/></r>".to_string()).unwrap();
+        let a = dom::get_element_by_tag_name(None, "a".to_string()).unwrap();
+        let b = dom::get_element_by_tag_name(None, "b".to_string()).unwrap();
+
+        a.set_inner_xml("1<x:c id=\"c\">2</x:c><d/>").unwrap();
+        assert_eq!(dom::to_string(), "<r xmlns:x=\"urn:x\"><a>1<x:c id=\"c\">2</x:c><d/></a><b/></r>");
+        assert_eq!(dom::get_element_by_id(None, "old".to_string()), None);
+
+        // the prefix is bound by the declaration of `r`
+        assert_eq!(dom::evaluate_xpath("namespace-uri(//*[@id='c'])").unwrap().to_string(), "urn:x");
+
+        // prefixes declared only on an ancestor of the target, or in the fragment itself
+        let d = dom::get_element_by_tag_name(None, "d".to_string()).unwrap();
+        d.set_inner_xml("<x:e x:f=\"1\"/><y:g xmlns:y=\"urn:y\"><y:h/></y:g><xml:i xml:lang=\"en\"/>").unwrap();
+        assert_eq!(d.to_string(), "<d><x:e x:f=\"1\"/><y:g xmlns:y=\"urn:y\"><y:h/></y:g><xml:i xml:lang=\"en\"/></d>");
+        assert_eq!(dom::evaluate_xpath("namespace-uri(//*[local-name() = 'e'])").unwrap().to_string(), "urn:x");
+
+        // unbound prefixes
+        let element_count = dom::get_all_elements().len();
+        assert!(d.set_inner_xml("<y:h/>").is_err());
+        assert!(d.set_inner_xml("<e y:f=\"1\"/>").is_err());
+        assert!(d.insert_adjacent_xml(crate::AdjacentPosition::AfterEnd, "<e><y:h/></e>").is_err());
+        assert!(dom::get_root().set_inner_xml("<y:g xmlns:y=\"\"/>").is_err());
+        assert_eq!(dom::get_all_elements().len(), element_count);
+        d.delete_contents();
+
+        b.insert_adjacent_xml(crate::AdjacentPosition::BeforeBegin, "<e/>").unwrap();
+        b.insert_adjacent_xml(crate::AdjacentPosition::AfterBegin, "<f/>").unwrap();
+        b.insert_adjacent_xml(crate::AdjacentPosition::BeforeEnd, "g").unwrap();
+        b.insert_adjacent_xml(crate::AdjacentPosition::AfterBegin, "<h/>").unwrap();
+        b.insert_adjacent_xml(crate::AdjacentPosition::AfterEnd, "<i/><j/>").unwrap();
+        assert_eq!(b.to_string(), "<b><h/><f/>g</b>");
+        assert_eq!(
+            dom::to_string(),
+            "<r xmlns:x=\"urn:x\"><a>1<x:c id=\"c\">2</x:c><d/></a><e/><b><h/><f/>g</b><i/><j/></r>"
+        );
+
+        a.set_outer_xml("<k>3</k>").unwrap();
+        assert_eq!(dom::get_element_by_tag_name(None, "a".to_string()), None);
+        assert_eq!(dom::get_element_by_id(None, "c".to_string()), None);
+        assert!(dom::get_root().set_outer_xml("<l/>").is_err());
+        assert!(dom::get_root().insert_adjacent_xml(crate::AdjacentPosition::AfterEnd, "<l/>").is_err());
+
+        // errors don't change the tree or the elements
+        let before = dom::to_string();
+        let element_count = dom::get_all_elements().len();
+        assert!(b.set_inner_xml("<m/><n>").is_err());
+        assert!(b.set_inner_xml("<m></n>").is_err());
+        assert_eq!(dom::to_string(), before);
+        assert_eq!(dom::get_all_elements().len(), element_count);
+        dom::check_indices().unwrap();
+
+        drop(lock);
+    }
+
+}
@@ -1,13 +1,19 @@
+use std::collections::HashMap;
+
 #[derive(Clone)]
 pub struct Prolog {
-    xml_decl: Option<XMLDecl>,
-    doctype_decl: Option<DocTypeDecl>
+    pub(crate) xml_decl: Option<XMLDecl>,
+    pub(crate) doctype_decl: Option<DocTypeDecl>,
+
+    /// `ParseOptions::preserve_format`
+    /// the source text, including comments and whitespaces
+    pub(crate) source: Option<String>
 }
 
 impl Prolog {
 
     pub fn new(xml_decl: Option<XMLDecl>, doctype_decl: Option<DocTypeDecl>) -> Self {
-        Prolog { xml_decl, doctype_decl }
+        Prolog { xml_decl, doctype_decl, source: None }
     }
 
     pub fn get_doctype_name(&self) -> Option<String> {
@@ -28,7 +34,42 @@ impl Prolog {
 
     }
 
+    /// the encoding declared in `<?xml encoding='...'?>`
+    pub fn get_xml_encoding(&self) -> Option<String> {
+
+        match &self.xml_decl {
+            Some(d) => d.encoding.clone(),
+            _ => None
+        }
+
+    }
+
+    /// It updates `encoding` of the XML declaration.
+    /// If there's no XML declaration, it creates one with version 1.0.
+    pub fn set_xml_encoding(&mut self, encoding: String) {
+        self.source = None;
+
+        match &mut self.xml_decl {
+            Some(d) => {
+                d.encoding = Some(encoding);
+            }
+            None => {
+                self.xml_decl = Some(XMLDecl::new(String::from("1.0"), Some(encoding), None));
+            }
+        }
+
+    }
+
+    pub fn has_xml_decl(&self) -> bool {
+        self.xml_decl.is_some()
+    }
+
     pub fn to_string(&self) -> String {
+
+        if let Some(source) = &self.source {
+            return source.clone();
+        }
+
         let xml_decl_string = match &self.xml_decl {
             Some(x) => x.to_string(),
             _ => String::new()
@@ -45,34 +86,111 @@ impl Prolog {
         )
     }
 
+    /// HTML doesn't have XML declarations, and the public and system identifiers are dropped: `<!DOCTYPE html>`.
+    pub fn to_html_string(&self) -> String {
+
+        match &self.doctype_decl {
+            Some(d) if d.name.eq_ignore_ascii_case("html") => String::from("<!DOCTYPE html>"),
+            Some(d) => d.to_string(),
+            _ => String::new()
+        }
+
+    }
+
 }
 
 #[derive(Clone)]
 pub struct XMLDecl {
-    pub version_num: String
+    pub version_num: String,
+    pub encoding: Option<String>,
+    pub standalone: Option<bool>
 }
 
 impl XMLDecl {
 
+    pub fn new(version_num: String, encoding: Option<String>, standalone: Option<bool>) -> Self {
+        XMLDecl { version_num, encoding, standalone }
+    }
+
     pub fn to_string(&self) -> String {
-        format!("<?xml version='{}'>", self.version_num)
+        format!(
+            "<?xml version='{}'{}{}?>",
+            self.version_num,
+            match &self.encoding {
+                Some(encoding) => format!(" encoding='{}'", encoding),
+                _ => String::new()
+            },
+            match &self.standalone {
+                Some(true) => " standalone='yes'",
+                Some(false) => " standalone='no'",
+                _ => ""
+            }
+        )
     }
 
 }
 
 #[derive(Clone)]
 pub struct DocTypeDecl {
-    pub name: String
+    pub name: String,
+
+    /// the source text between `[` and `]`
+    pub internal_subset: Option<String>,
+
+    // element name -> attribute name -> the type in the attribute-list declaration (`CDATA`, `ID`, `NMTOKENS`, ...)
+    attribute_types: HashMap<String, HashMap<String, String>>,
 }
 
 impl DocTypeDecl {
 
     pub fn new(name: String) -> DocTypeDecl {
-        DocTypeDecl { name }
+        DocTypeDecl { name, internal_subset: None, attribute_types: HashMap::new() }
+    }
+
+    /// the type of the attribute declared in the internal subset
+    /// The type of an enumeration is `ENUMERATION`.
+    pub fn get_attribute_type(&self, element_name: &str, attribute_name: &str) -> Option<&str> {
+        self.attribute_types.get(element_name)?.get(attribute_name).map(|t| t.as_str())
+    }
+
+    // https://www.w3.org/TR/xml/#attdecls
+    // the first declaration of an attribute is binding, and the later ones are ignored
+    pub(crate) fn add_attribute_type(&mut self, element_name: &str, attribute_name: String, attribute_type: String) {
+
+        if !self.attribute_types.contains_key(element_name) {
+            self.attribute_types.insert(element_name.to_string(), HashMap::new());
+        }
+
+        self.attribute_types.get_mut(element_name).unwrap().entry(attribute_name).or_insert(attribute_type);
     }
 
     pub fn to_string(&self) -> String {
-        format!("<!DOCTYPE {}>", self.name)
+
+        match &self.internal_subset {
+            Some(internal_subset) => format!("<!DOCTYPE {} [{}]>", self.name, internal_subset),
+            None => format!("<!DOCTYPE {}>", self.name)
+        }
+
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dom;
+
+    #[test]
+    fn doctype_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        crate::into_dom("<!DOCTYPE r SYSTEM 'r.dtd'><r/>".to_string()).unwrap();
+        assert_eq!(dom::get_root().get_tag_name(), "r");
+
+        crate::into_dom("<!DOCTYPE r [<!ELEMENT r ANY>]><r/>".to_string()).unwrap();
+        assert_eq!(dom::to_string(), "<!DOCTYPE r [<!ELEMENT r ANY>]><r/>");
+        assert!(crate::into_dom("<!DOCTYPE r [<!ELEMENT r ANY>]<r/>".to_string()).is_err());
+
+        drop(lock);
     }
 
-}
\ No newline at end of file
+}
@@ -1,7 +1,11 @@
 use super::attribute::Attribute;
 use super::element::{Element, Content};
+use super::memory;
 use super::pointer::ElementPtr;
-use crate::gstring::GString;
+use super::prolog::DocTypeDecl;
+use crate::gstring::{get_line_column, GString};
+use crate::parse::{normalize_attribute_value, ParseOptions};
+use crate::utils::from_v16;
 
 pub enum RawContent {
     Element(RawElement),
@@ -13,9 +17,10 @@ pub enum RawContent {
 
 impl RawContent {
 
-    pub fn to_real(&self) -> Content {
+    // `doctype_decl` has the types of the attributes
+    pub fn to_real(&self, options: &ParseOptions, doctype_decl: Option<&DocTypeDecl>) -> Content {
         match self {
-            RawContent::Element(e) => Content::Element(e.to_real()),
+            RawContent::Element(e) => Content::Element(e.to_real(options, doctype_decl)),
             RawContent::CharData(c) => Content::CharData(c.to_string()),
             RawContent::CDSect(c) => Content::CDSect(c.to_string()),
             RawContent::Comment(c) => Content::Comment(c.to_string()),
@@ -29,27 +34,57 @@ pub struct RawElement {
     tag_name: GString,
     attributes: Vec<(GString, GString)>,  // Vec<(name, value)>
     empty_element: bool,
-    contents: Vec<Box<RawContent>>
+    contents: Vec<RawContent>,
+
+    // the whole start tag (or the empty element tag) and the end tag, for `ParseOptions::preserve_format`
+    start_tag: GString,
+    end_tag: Option<GString>
 }
 
 impl RawElement {
 
-    pub fn new(tag_name: GString, attributes: Vec<(GString, GString)>, empty_element: bool, contents: Vec<Box<RawContent>>) -> Self {
+    pub fn new(tag_name: GString, attributes: Vec<(GString, GString)>, empty_element: bool, contents: Vec<RawContent>, start_tag: GString, end_tag: Option<GString>) -> Self {
         RawElement {
-            tag_name, attributes, empty_element, contents
+            tag_name, attributes, empty_element, contents, start_tag, end_tag
         }
     }
 
-    pub fn to_real(&self) -> ElementPtr {
-        Element::new(
-            self.tag_name.to_string(),
-            self.attributes.iter().map(
-                |(name, value)|
-                Attribute::new(name.to_string(), value.to_string())
-            ).collect(),
+    // `doctype_decl` has the types of the attributes
+    pub fn to_real(&self, options: &ParseOptions, doctype_decl: Option<&DocTypeDecl>) -> ElementPtr {
+        let tag_name = self.tag_name.to_string();
+
+        let attributes = self.attributes.iter().map(
+            |(name, value)| {
+                let name = name.to_string();
+                let tokenized = options.tokenized_attributes.contains(&name) || doctype_decl.and_then(
+                    |doctype_decl| doctype_decl.get_attribute_type(&tag_name, &name)
+                ).is_some_and(|attribute_type| attribute_type != "CDATA");
+                let value = normalize_attribute_value(
+                    value.to_slice(),
+                    options.normalize_attribute_values,
+                    tokenized
+                );
+
+                Attribute::new(name, from_v16(&value))
+            }
+        ).collect();
+
+        let result = Element::new(
+            tag_name,
+            attributes,
             self.empty_element,
-            self.contents.iter().map(|content| content.to_real()).collect()
-        )
+            self.contents.iter().map(|content| content.to_real(options, doctype_decl)).collect()
+        );
+
+        let element = memory::get_mut(result.ptr);
+        element.source_location = Some(get_line_column(self.start_tag.start));
+
+        if options.preserve_format {
+            element.source_start_tag = Some(self.start_tag.to_string());
+            element.source_end_tag = self.end_tag.map(|end_tag| end_tag.to_string());
+        }
+
+        result
     }
 
 }
\ No newline at end of file
@@ -0,0 +1,139 @@
+// Editing the source text of a tag, for `ParseOptions::preserve_format`
+
+use crate::parse::{get_attribute_value_end_index, get_eq_end_index, get_name_end_index};
+use crate::predicate::is_whitespace;
+use crate::serialize::{escape_attribute_value, pick_quote};
+use crate::utils::{from_v16, into_v16, skip_whitespaces};
+
+// It replaces the value of `name` in `start_tag`, keeping the quote style and the spaces of the tag.
+// If `start_tag` doesn't have `name`, it appends the attribute after the last attribute.
+// It returns None if `start_tag` is not a valid start tag (or an empty element tag).
+pub fn patch_attribute(start_tag: &str, name: &str, value: &str) -> Option<String> {
+    let tag = into_v16(start_tag);
+
+    if tag.first() != Some(&('<' as u16)) {
+        return None;
+    }
+
+    let mut curr_index = get_name_end_index(&tag, 1)? + 1;
+    let mut last_attribute_end_index = curr_index;
+
+    loop {
+        let attribute_index = skip_whitespaces(&tag, curr_index);
+
+        if attribute_index >= tag.len() {
+            return None;
+        }
+
+        if tag[attribute_index] == '/' as u16 || tag[attribute_index] == '>' as u16 {
+            break;
+        }
+
+        // attributes are separated by whitespaces
+        if attribute_index == curr_index || !is_whitespace(&tag[curr_index]) {
+            return None;
+        }
+
+        let name_end_index = get_name_end_index(&tag, attribute_index)?;
+        let eq_end_index = get_eq_end_index(&tag, name_end_index + 1)?;
+        let value_end_index = get_attribute_value_end_index(&tag, eq_end_index + 1)?;
+
+        if from_v16(&tag[attribute_index..(name_end_index + 1)]) == name {
+            let quote = tag[eq_end_index + 1];
+
+            return Some(format!(
+                "{}{}{}",
+                from_v16(&tag[..(eq_end_index + 2)]),
+                escape_attribute_value(value, char::from_u32(quote as u32).unwrap()),
+                from_v16(&tag[value_end_index..])
+            ));
+        }
+
+        curr_index = value_end_index + 1;
+        last_attribute_end_index = curr_index;
+    }
+
+    let quote = pick_quote(value);
+
+    Some(format!(
+        "{} {}={}{}{}{}",
+        from_v16(&tag[..last_attribute_end_index]),
+        name,
+        quote,
+        escape_attribute_value(value, quote),
+        quote,
+        from_v16(&tag[last_attribute_end_index..])
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dom;
+    use crate::node::source::*;
+
+    #[test]
+    fn patch_attribute_test() {
+        let test_cases = vec![
+            ("<a x='1'  y = \"2\" />", "y", "3", Some("<a x='1'  y = \"3\" />")),
+            ("<a x='1'  y = \"2\" />", "x", "'", Some("<a x='&apos;'  y = \"2\" />")),
+            ("<a x='1'  y = \"2\" >", "z", "3", Some("<a x='1'  y = \"2\" z=\"3\" >")),
+            ("<a>", "z", "\"", Some("<a z='\"'>")),
+            ("<a/>", "z", "3", Some("<a z=\"3\"/>")),
+            ("not a tag", "z", "3", None),
+        ];
+
+        for (start_tag, name, value, answer) in test_cases.into_iter() {
+            assert_eq!(patch_attribute(start_tag, name, value), answer.map(|answer| answer.to_string()));
+        }
+
+    }
+
+    #[test]
+    fn preserve_format_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        let document = "<?xml version=\"1.0\" encoding=\"UTF-8\" ?>\r\n<!-- config -->\r\n<config  version = '2' >\r\n  <item key=\"a\"   value='1' />\r\n  <item key=\"b\" value=\"x &amp; y\"></item >\r\n  <empty/>\r\n</config>\r\n";
+
+        crate::into_dom_with_options(document.to_string(), crate::ParseOptions::lossless()).unwrap();
+        assert_eq!(dom::to_string(), document);
+
+        let items = dom::get_elements_by_tag_name(None, "item".to_string());
+        items[0].set_attribute("value".to_string(), "2".to_string());
+        items[1].set_attribute("enabled".to_string(), "yes".to_string());
+        dom::get_element_by_tag_name(None, "empty".to_string()).unwrap().add_contents(vec![crate::Content::new_char_data("text".to_string())]);
+
+        assert_eq!(
+            dom::to_string(),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" ?>\r\n<!-- config -->\r\n<config  version = '2' >\r\n  <item key=\"a\"   value='2' />\r\n  <item key=\"b\" value=\"x &amp; y\" enabled=\"yes\"></item >\r\n  <empty>text</empty>\r\n</config>\r\n"
+        );
+
+        // the format is not preserved by default
+        crate::into_dom(document.to_string()).unwrap();
+        assert_eq!(dom::to_string(), "<?xml version='1.0' encoding='UTF-8'?><config version=\"2\">\n  <item key=\"a\" value=\"1\"/>\n  <item key=\"b\" value=\"x &amp; y\"></item>\n  <empty/>\n</config>");
+
+        drop(lock);
+    }
+
+    #[test]
+    fn source_location_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        crate::into_dom("<?xml version=\"1.0\"?>\n<a>\n  <b/><c>\n\t<d/></c></a>".to_string()).unwrap();
+        let locations = dom::get_root().descendants().map(|e| e.get_source_location().unwrap()).collect::<Vec<(usize, usize)>>();
+        assert_eq!(dom::get_root().get_source_location(), Some((2, 1)));
+        assert_eq!(locations, vec![(3, 3), (3, 7), (4, 2)]);
+
+        // a fragment has locations in its own string
+        let fragment = crate::Content::from_string("x\n<e/>".to_string()).unwrap();
+
+        match &fragment[1] {
+            crate::Content::Element(e) => {
+                assert_eq!(e.get_source_location(), Some((2, 1)));
+            }
+            _ => unreachable!()
+        }
+
+        drop(lock);
+    }
+
+}
@@ -0,0 +1,170 @@
+// Pre-order and post-order numbers of the elements, for sorting in document order and checking ancestors in O(1).
+// `element` is a descendant of `ancestor` iff pre(ancestor) < pre(element) and post(element) < post(ancestor).
+// Mutations that change the structure only invalidate the numbers, and the first query after them renumbers every tree in O(n).
+// So a batch of mutations costs a single renumbering, however many mutations it has.
+
+use crate::dom;
+use crate::node::memory::{self, ELEMENTS};
+use crate::node::pointer::ElementPtr;
+use std::cmp::Ordering;
+use std::ptr::addr_of;
+
+// IT'S NOT THREAD-SAFE!!!!
+static mut IS_VALID: bool = false;
+
+// (pre-order, post-order), indexed by `ElementPtr::ptr`
+static mut NUMBERS: Vec<(usize, usize)> = vec![];
+
+pub fn invalidate() {
+    unsafe {
+        IS_VALID = false;
+    }
+}
+
+// The document comes first, then the detached trees in the order of allocation.
+fn number_elements() {
+
+    unsafe {
+
+        if IS_VALID {
+            return;
+        }
+
+        let elements = &*addr_of!(ELEMENTS);
+        let mut numbers = vec![(usize::MAX, usize::MAX); elements.len()];
+        let mut pre = 0;
+        let mut post = 0;
+
+        if elements.iter().any(|element| element.is_alive) {
+            number_tree(dom::get_root(), &mut numbers, &mut pre, &mut post);
+        }
+
+        for element in dom::get_all_elements().into_iter() {
+
+            if numbers[element.ptr].0 == usize::MAX && element.get_parent().is_none() {
+                number_tree(element, &mut numbers, &mut pre, &mut post);
+            }
+
+        }
+
+        NUMBERS = numbers;
+        IS_VALID = true;
+    }
+
+}
+
+fn number_tree(element: ElementPtr, numbers: &mut Vec<(usize, usize)>, pre: &mut usize, post: &mut usize) {
+    numbers[element.ptr].0 = *pre;
+    *pre += 1;
+
+    for child in memory::get(element.ptr).get_children().into_iter() {
+        number_tree(child, numbers, pre, post);
+    }
+
+    numbers[element.ptr].1 = *post;
+    *post += 1;
+}
+
+fn get_numbers(element: ElementPtr) -> (usize, usize) {
+    number_elements();
+
+    unsafe {
+        (&*addr_of!(NUMBERS))[element.ptr]
+    }
+}
+
+// It's false if `element` is `ancestor`.
+pub fn is_descendant(element: ElementPtr, ancestor: ElementPtr) -> bool {
+    let (pre, post) = get_numbers(element);
+    let (ancestor_pre, ancestor_post) = get_numbers(ancestor);
+
+    ancestor_pre < pre && post < ancestor_post
+}
+
+pub fn compare(element: ElementPtr, other: ElementPtr) -> Ordering {
+    get_numbers(element).0.cmp(&get_numbers(other).0)
+}
+
+// O(k log k) for k elements, after renumbering if needed.
+pub fn sort(elements: &mut [ElementPtr]) {
+    number_elements();
+
+    unsafe {
+        let numbers = &*addr_of!(NUMBERS);
+        elements.sort_by_key(|element| numbers[element.ptr].0);
+    }
+}
+
+// It keeps the descendants of `ancestor` in `elements`, and sorts them in document order.
+pub fn filter_descendants(elements: Vec<ElementPtr>, ancestor: ElementPtr) -> Vec<ElementPtr> {
+    let mut result = elements.into_iter().filter(|element| is_descendant(*element, ancestor)).collect::<Vec<ElementPtr>>();
+    sort(&mut result);
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dom;
+
+    #[test]
+    fn document_order_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        crate::into_dom("<r><a id=\"1\"><b id=\"2\"><a id=\"3\"/></b><a id=\"4\" class=\"c\"/></a><a id=\"5\" class=\"c\"/></r>".to_string()).unwrap();
+        let ids = |elements: Vec<crate::ElementPtr>| elements.iter().map(|e| e.get_attribute("id".to_string()).unwrap()).collect::<Vec<String>>();
+        let by_id = |id: &str| dom::get_element_by_id(None, id.to_string()).unwrap();
+
+        // children are allocated before their parents
+        assert_eq!(ids(dom::get_elements_by_tag_name(None, "a".to_string())), vec!["1", "3", "4", "5"]);
+        assert_eq!(dom::get_element_by_tag_name(None, "a".to_string()), Some(by_id("1")));
+
+        // scoped queries
+        assert_eq!(ids(by_id("1").get_elements_by_tag_name("a")), vec!["3", "4"]);
+        assert_eq!(ids(by_id("1").get_elements_by_class_name("c")), vec!["4"]);
+        assert_eq!(ids(by_id("2").get_elements_by_attribute("id", "3")), vec!["3"]);
+        assert_eq!(by_id("3").get_elements_by_tag_name("a"), vec![]);
+        assert!(by_id("1").contains(by_id("3")) && by_id("1").contains(by_id("1")) && !by_id("1").contains(by_id("5")));
+        assert_eq!(by_id("4").compare_document_order(by_id("3")), std::cmp::Ordering::Greater);
+
+        // mutations
+        dom::delete(by_id("1"));
+        crate::into_dom("<r><a id=\"1\"/><a id=\"2\"/><a id=\"3\"/><a id=\"4\"/></r>".to_string()).unwrap();
+        dom::delete(by_id("1"));
+        assert_eq!(ids(dom::get_elements_by_tag_name(None, "a".to_string())), vec!["2", "3", "4"]);
+
+        dom::get_root().add_element_ptr(by_id("2"));
+        assert_eq!(ids(dom::get_elements_by_tag_name(None, "a".to_string())), vec!["3", "4", "2"]);
+
+        by_id("3").add_element_ptr(by_id("4"));
+        assert_eq!(ids(by_id("3").get_elements_by_tag_name("a")), vec!["4"]);
+
+        // queries right after mutations see the new structure
+        let (a2, a3, a4) = (by_id("2"), by_id("3"), by_id("4"));
+        let e = crate::Element::new("e".to_string(), vec![], true, vec![]);
+        dom::get_root().add_element_ptr(e);
+        assert_eq!(a4.compare_document_order(a2), std::cmp::Ordering::Less);
+        assert_eq!(a3.compare_document_order(a4), std::cmp::Ordering::Less);
+        assert_eq!(a4.compare_document_order(a3), std::cmp::Ordering::Greater);
+        assert!(a3.contains(a4) && !a4.contains(a3) && !a2.contains(a4));
+        assert_eq!(dom::get_root().get_elements_by_tag_name("a"), vec![a3, a4, a2]);
+
+        e.add_element_ptr(a3);
+        e.add_element_ptr(a2);
+        assert!(e.contains(a4) && !a3.contains(a2));
+        assert_eq!(a2.compare_document_order(a4), std::cmp::Ordering::Greater);
+        assert_eq!(e.get_elements_by_tag_name("a"), vec![a3, a4, a2]);
+        assert_eq!(a3.get_elements_by_tag_name("a"), vec![a4]);
+
+        // detached elements come last
+        let detached = crate::Element::from_string("<a id=\"0\"/>".to_string()).unwrap();
+        assert_eq!(ids(dom::get_elements_by_tag_name(None, "a".to_string())), vec!["3", "4", "2", "0"]);
+        assert!(!dom::get_root().contains(detached));
+
+        dom::get_root().add_element_ptr(detached);
+        assert!(dom::get_root().contains(detached));
+
+        drop(lock);
+    }
+
+}
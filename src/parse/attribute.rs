@@ -1,5 +1,6 @@
-use super::{get_name_end_index, get_eq_end_index, get_reference_end_index};
+use super::{get_name_end_index, get_eq_end_index, get_reference_end_index, expand_reference};
 use crate::gstring::GString;
+use crate::predicate::is_whitespace;
 
 // https://www.w3.org/TR/xml/#NT-Attribute
 // name eq attribute_value
@@ -74,4 +75,95 @@ pub fn parse_attribute(document: &[u16], index: usize) -> ((GString, GString), u
     let att_value = GString::new(eq_end_index + 2, att_value_end_index);  // exclude quotes
 
     ((name, att_value), att_value_end_index)
-}
\ No newline at end of file
+}
+
+// https://www.w3.org/TR/xml/#AVNormalize
+// references are always expanded, except entities that are not predefined
+// if `normalize_whitespaces` is set, whitespace characters (not the ones from char references) are converted to spaces
+// if `tokenized` is also set, leading and trailing spaces are removed and sequences of spaces are collapsed
+pub fn normalize_attribute_value(value: &[u16], normalize_whitespaces: bool, tokenized: bool) -> Vec<u16> {
+    let mut result = Vec::with_capacity(value.len());
+    let mut index = 0;
+
+    while index < value.len() {
+
+        if value[index] == '&' as u16 {
+
+            if let Some(reference_end_index) = get_reference_end_index(value, index) {
+
+                match expand_reference(&value[index..(reference_end_index + 1)]) {
+                    Some(expanded) => {
+                        result.extend(expanded);
+                    }
+                    None => {
+                        result.extend_from_slice(&value[index..(reference_end_index + 1)]);
+                    }
+                }
+
+                index = reference_end_index + 1;
+                continue;
+            }
+
+        }
+
+        if normalize_whitespaces && is_whitespace(&value[index]) {
+            result.push(' ' as u16);
+        }
+
+        else {
+            result.push(value[index]);
+        }
+
+        index += 1;
+    }
+
+    if normalize_whitespaces && tokenized {
+        let mut collapsed = Vec::with_capacity(result.len());
+
+        for c in result.into_iter() {
+
+            if c == ' ' as u16 && (collapsed.is_empty() || collapsed[collapsed.len() - 1] == ' ' as u16) {
+                continue;
+            }
+
+            collapsed.push(c);
+        }
+
+        if collapsed.last() == Some(&(' ' as u16)) {
+            collapsed.pop();
+        }
+
+        collapsed
+    }
+
+    else {
+        result
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::*;
+    use crate::utils::{from_v16, into_v16};
+
+    #[test]
+    fn normalize_attribute_value_test() {
+        let test_cases = vec![
+            ("a\tb\r\nc", false, false, "a\tb\r\nc"),
+            ("a\tb\r\nc", true, false, "a b  c"),
+            ("a&#10;b&#x9;c", true, false, "a\nb\tc"),
+            ("&lt;&quot;&amp;&apos;&gt;", false, false, "<\"&'>"),
+            ("a&nbsp;b", true, false, "a&nbsp;b"),
+            ("  a \n b  ", true, true, "a b"),
+            ("  a \n b  ", false, true, "  a \n b  "),
+            (" a&#32;&#32;b ", true, true, "a b"),
+        ];
+
+        for (case, normalize_whitespaces, tokenized, answer) in test_cases.into_iter() {
+            assert_eq!(from_v16(&normalize_attribute_value(&into_v16(case), normalize_whitespaces, tokenized)), answer);
+        }
+
+    }
+
+}
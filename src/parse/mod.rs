@@ -16,6 +16,9 @@ pub use pi::*;
 mod prolog;
 pub use prolog::*;
 
+mod options;
+pub use options::*;
+
 // get_xxx_end_index(content: &[u16], index: usize) -> Option<usize>
 // if content[index..end_index + 1] is a valid `xxx`, it returns Some(end_index)
 // otherwise, it returns None
@@ -0,0 +1,136 @@
+/// Options for `into_dom_with_options`.
+/// `Content::from_string` and `Element::from_string` use the options of the last `into_dom_with_options` call.
+#[derive(Clone, Debug)]
+pub struct ParseOptions {
+
+    /// https://www.w3.org/TR/xml/#sec-line-ends
+    /// `\r\n` and `\r` are converted to `\n` before parsing.
+    pub normalize_line_endings: bool,
+
+    /// https://www.w3.org/TR/xml/#AVNormalize
+    /// Whitespace characters in attribute values are converted to spaces.
+    /// References in attribute values are always expanded, regardless of this option.
+    pub normalize_attribute_values: bool,
+
+    /// Attributes that are declared in the internal subset of the DTD with a type other than CDATA are tokenized:
+    /// leading and trailing spaces are removed, and sequences of spaces are collapsed into a single space.
+    /// External DTDs are not read, so the attributes listed here are tokenized too, in every element.
+    /// It's ignored if `normalize_attribute_values` is false.
+    pub tokenized_attributes: Vec<String>,
+
+    /// It remembers the source text of the tags, the prolog and the text after the root element.
+    /// `to_string` writes them as they were, unless they're modified (eg. by `set_attribute`).
+    /// Use it with `normalize_line_endings` and `normalize_attribute_values` turned off (`ParseOptions::lossless`), so that the char data are not changed either.
+    pub preserve_format: bool,
+}
+
+impl ParseOptions {
+
+    pub const fn new() -> Self {
+        ParseOptions {
+            normalize_line_endings: true,
+            normalize_attribute_values: true,
+            tokenized_attributes: vec![],
+            preserve_format: false,
+        }
+    }
+
+    /// It keeps line endings and whitespaces in attribute values as they are.
+    pub const fn raw() -> Self {
+        ParseOptions {
+            normalize_line_endings: false,
+            normalize_attribute_values: false,
+            tokenized_attributes: vec![],
+            preserve_format: false,
+        }
+    }
+
+    /// Untouched parts of the document are written byte-for-byte as they were.
+    pub const fn lossless() -> Self {
+        ParseOptions {
+            normalize_line_endings: false,
+            normalize_attribute_values: false,
+            tokenized_attributes: vec![],
+            preserve_format: true,
+        }
+    }
+
+}
+
+impl Default for ParseOptions {
+
+    fn default() -> Self {
+        ParseOptions::new()
+    }
+
+}
+
+// the options of the last `into_dom_with_options` call, for `Content::from_string` and `Element::from_string`
+// The document itself is parsed with the options that are passed to the parser.
+static mut PARSE_OPTIONS: ParseOptions = ParseOptions::new();
+
+pub fn set_parse_options(options: ParseOptions) {
+    unsafe { PARSE_OPTIONS = options; }
+}
+
+pub fn get_parse_options() -> ParseOptions {
+    unsafe { (*std::ptr::addr_of!(PARSE_OPTIONS)).clone() }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dom;
+
+    #[test]
+    fn normalization_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        let unix = "<a title=\"x\n y\">\n<b class=\" c1\tc2 \" id=\"b\"/>\n</a>";
+        let windows = "<a title=\"x\r\n y\">\r\n<b class=\" c1\tc2 \" id=\"b\"/>\r\n</a>";
+
+        crate::into_dom(unix.to_string()).unwrap();
+        let unix_result = dom::to_string();
+
+        crate::into_dom(windows.to_string()).unwrap();
+        assert_eq!(unix_result, dom::to_string());
+        assert_eq!(dom::get_root().get_attribute("title".to_string()), Some("x  y".to_string()));
+
+        let options = crate::ParseOptions {
+            tokenized_attributes: vec!["class".to_string()],
+            ..crate::ParseOptions::default()
+        };
+        crate::into_dom_with_options(windows.to_string(), options).unwrap();
+        assert_eq!(dom::get_element_by_id(None, "b".to_string()).unwrap().get_attribute("class".to_string()), Some("c1 c2".to_string()));
+        assert_eq!(dom::get_elements_by_class_name(None, "c2".to_string()).len(), 1);
+
+        // attributes that are declared with a type other than CDATA
+        crate::into_dom("<!DOCTYPE a [<!ATTLIST b class NMTOKENS #IMPLIED title CDATA #IMPLIED>]><a><b class=\" c1\tc2 \" title=\" x  y \"/><c class=\" c1 \"/></a>".to_string()).unwrap();
+        let b = dom::get_element_by_tag_name(None, "b".to_string()).unwrap();
+        assert_eq!(b.get_attribute("class".to_string()), Some("c1 c2".to_string()));
+        assert_eq!(b.get_attribute("title".to_string()), Some(" x  y ".to_string()));
+        assert_eq!(dom::get_element_by_tag_name(None, "c".to_string()).unwrap().get_attribute("class".to_string()), Some(" c1 ".to_string()));
+
+        // fragments use the declarations of the document
+        let new_b = crate::Element::from_string("<b class=\"  c3 \"/>".to_string()).unwrap();
+        assert_eq!(new_b.get_attribute("class".to_string()), Some("c3".to_string()));
+
+        crate::into_dom_with_options(windows.to_string(), crate::ParseOptions::raw()).unwrap();
+        assert_eq!(dom::get_root().get_attribute("title".to_string()), Some("x\r\n y".to_string()));
+        assert_eq!(dom::get_root().get_contents()[0], crate::Content::CharData("\r\n".to_string()));
+
+        // the serializer doesn't depend on the parse options: `\r` is always escaped
+        assert!(dom::to_string().starts_with("<a title=\"x&#xD;&#xA; y\">&#xD;\n"));
+        crate::into_dom(dom::to_string()).unwrap();
+        assert_eq!(dom::get_root().get_attribute("title".to_string()), Some("x\r\n y".to_string()));
+
+        // escaped values survive the round trip
+        crate::into_dom("<a title=\"&quot;1&quot; &lt; 2 &amp;&#10;3\"/>".to_string()).unwrap();
+        let title = dom::get_root().get_attribute("title".to_string());
+        assert_eq!(title, Some("\"1\" < 2 &\n3".to_string()));
+        crate::into_dom(dom::to_string()).unwrap();
+        assert_eq!(title, dom::get_root().get_attribute("title".to_string()));
+
+        drop(lock);
+    }
+
+}
@@ -4,6 +4,7 @@ use crate::predicate::{
     is_alpha_cap,
     is_alpha_low,
     is_numeric,
+    is_pubid_char,
     is_whitespace,
 };
 use crate::utils::{from_v16, into_v16, skip_whitespaces};
@@ -113,10 +114,10 @@ pub fn get_xml_decl_end_index(document: &[u16], mut index: usize) -> Option<usiz
         None => {}
     }
 
-    index = skip_whitespaces(document, index);
+    index = skip_whitespaces(document, index + 1);
 
-    if index + 2 < document.len() && document[index + 1] == '?' as u16 && document[index + 2] == '>' as u16 {
-        Some(index + 2)
+    if index + 1 < document.len() && document[index] == '?' as u16 && document[index + 1] == '>' as u16 {
+        Some(index + 1)
     }
 
     else {
@@ -135,13 +136,13 @@ pub fn get_version_info_end_index(document: &[u16], mut index: usize) -> Option<
 
     index = skip_whitespaces(document, index);
 
-    if index >= document.len() || &document[index..(index + 7)] != into_v16("version") {
+    if index + 7 >= document.len() || document[index..(index + 7)] != into_v16("version") {
         return None;
     }
 
     index += 7;
 
-    match get_eq_end_index(document, index + 1) {
+    match get_eq_end_index(document, index) {
         Some(eq_end_index) => {
 
             if eq_end_index + 1 < document.len() &&
@@ -170,7 +171,7 @@ pub fn get_version_info_end_index(document: &[u16], mut index: usize) -> Option<
 // '1.' [0-9]+
 pub fn get_version_num_end_index(document: &[u16], mut index: usize) -> Option<usize> {
 
-    if index + 2 > document.len()
+    if index + 2 >= document.len()
         || document[index] != '1' as u16
         || document[index + 1] != '.' as u16
         || !is_numeric(&document[index + 2])
@@ -184,7 +185,7 @@ pub fn get_version_num_end_index(document: &[u16], mut index: usize) -> Option<u
         index += 1;
     }
 
-    Some(index)
+    Some(index - 1)
 }
 
 // https://www.w3.org/TR/xml/#NT-SDDecl
@@ -197,7 +198,7 @@ pub fn get_sd_decl_end_index(document: &[u16], mut index: usize) -> Option<usize
 
     index = skip_whitespaces(document, index);
 
-    if index >= document.len() || &document[index..(index + 10)] != &into_v16("standalone") {
+    if index + 10 >= document.len() || document[index..(index + 10)] != into_v16("standalone") {
         return None;
     }
 
@@ -208,16 +209,18 @@ pub fn get_sd_decl_end_index(document: &[u16], mut index: usize) -> Option<usize
         Some(i) => i
     };
 
-    if &document[(index + 1)..(index + 5)] == &into_v16("\"no\"")
-        || &document[(index + 1)..(index + 5)] == &into_v16("'no'")
-    {
-        Some(index + 5)
+    if index + 5 <= document.len() && (
+        document[(index + 1)..(index + 5)] == into_v16("\"no\"")
+        || document[(index + 1)..(index + 5)] == into_v16("'no'")
+    ) {
+        Some(index + 4)
     }
 
-    else if &document[(index + 1)..(index + 6)] == &into_v16("\"yes\"")
-        || &document[(index + 1)..(index + 6)] == &into_v16("'yes'")
-    {
-        Some(index + 6)
+    else if index + 6 <= document.len() && (
+        document[(index + 1)..(index + 6)] == into_v16("\"yes\"")
+        || document[(index + 1)..(index + 6)] == into_v16("'yes'")
+    ) {
+        Some(index + 5)
     }
 
     else {
@@ -236,7 +239,7 @@ pub fn get_encoding_decl_end_index(document: &[u16], mut index: usize) -> Option
 
     index = skip_whitespaces(document, index);
 
-    if index >= document.len()|| &document[index..(index + 8)] != &into_v16("encoding") {
+    if index + 8 >= document.len() || document[index..(index + 8)] != into_v16("encoding") {
         return None;
     }
 
@@ -249,7 +252,7 @@ pub fn get_encoding_decl_end_index(document: &[u16], mut index: usize) -> Option
 
     if index + 1 >= document.len()
         || document[index + 1] != '\'' as u16
-        || document[index + 1] != '"' as u16
+        && document[index + 1] != '"' as u16
     {
         None
     }
@@ -290,8 +293,9 @@ pub fn get_encoding_name_end_index(document: &[u16], mut index: usize) -> Option
     index += 1;
 
     while index < document.len() && (
-        !is_alpha_low(&document[index])
-        || !is_alpha_cap(&document[index])
+        is_alpha_low(&document[index])
+        || is_alpha_cap(&document[index])
+        || is_numeric(&document[index])
         || document[index] == '.' as u16
         || document[index] == '_' as u16
         || document[index] == '-' as u16
@@ -299,7 +303,7 @@ pub fn get_encoding_name_end_index(document: &[u16], mut index: usize) -> Option
         index += 1;
     }
 
-    Some(index)
+    Some(index - 1)
 }
 
 // https://www.w3.org/TR/xml/#NT-doctypedecl
@@ -312,33 +316,344 @@ pub fn get_doctype_decl_end_index(document: &[u16], mut index: usize) -> Option<
 
     index = skip_whitespaces(document, index + 10);
 
-    match get_name_end_index(document, index + 1) {
-        Some(name_end_index) => if name_end_index + 1 >= document.len() {
-            None
-        } else if document[name_end_index + 1] == '>' as u16 {
-            Some(name_end_index + 1)
-        } else if document[name_end_index + 1] == '[' as u16 {
+    let name_end_index = get_name_end_index(document, index)?;
+    index = skip_whitespaces(document, name_end_index + 1);
+
+    if index > name_end_index + 1 {
 
-            let internal_subset_end_index = match get_internal_subset_end_index(document, name_end_index + 2) {
-                None => { return None; }
-                Some(i) => i
-            };
+        if let Some(external_id_end_index) = get_external_id_end_index(document, index) {
+            index = skip_whitespaces(document, external_id_end_index + 1);
+        }
 
-            todo!()
-        } else if is_whitespace(&document[name_end_index + 1]) {
-            todo!()
-        } else {
+    }
+
+    if index >= document.len() {
+        None
+    } else if document[index] == '>' as u16 {
+        Some(index)
+    } else if document[index] == '[' as u16 {
+        let internal_subset_end_index = get_internal_subset_end_index(document, index + 1)?;
+        index = skip_whitespaces(document, internal_subset_end_index + 1);
+
+        if index < document.len() && document[index] == '>' as u16 {
+            Some(index)
+        }
+
+        else {
             None
-        },
-        None => None
+        }
+
+    } else {
+        None
+    }
+
+}
+
+// https://www.w3.org/TR/xml/#NT-ExternalID
+// 'SYSTEM' whitespace system_literal | 'PUBLIC' whitespace pubid_literal whitespace system_literal
+pub fn get_external_id_end_index(document: &[u16], index: usize) -> Option<usize> {
+
+    if index + 7 >= document.len() {
+        return None;
+    }
+
+    if document[index..(index + 6)] == into_v16("SYSTEM") && is_whitespace(&document[index + 6]) {
+        get_system_literal_end_index(document, skip_whitespaces(document, index + 6))
+    }
+
+    else if document[index..(index + 6)] == into_v16("PUBLIC") && is_whitespace(&document[index + 6]) {
+        let pubid_literal_end_index = get_pubid_literal_end_index(document, skip_whitespaces(document, index + 6))?;
+        let system_literal_index = skip_whitespaces(document, pubid_literal_end_index + 1);
+
+        if system_literal_index == pubid_literal_end_index + 1 {
+            return None;
+        }
+
+        get_system_literal_end_index(document, system_literal_index)
+    }
+
+    else {
+        None
+    }
+
+}
+
+// https://www.w3.org/TR/xml/#NT-SystemLiteral
+// ('"' [^"]* '"') | ("'" [^']* "'")
+pub fn get_system_literal_end_index(document: &[u16], index: usize) -> Option<usize> {
+
+    if index >= document.len() || document[index] != '"' as u16 && document[index] != '\'' as u16 {
+        return None;
+    }
+
+    let quote = document[index];
+    let mut curr_index = index + 1;
+
+    while curr_index < document.len() {
+
+        if document[curr_index] == quote {
+            return Some(curr_index);
+        }
+
+        curr_index += 1;
+    }
+
+    None
+}
+
+// https://www.w3.org/TR/xml/#NT-PubidLiteral
+// '"' PubidChar* '"' | "'" (PubidChar - "'")* "'"
+pub fn get_pubid_literal_end_index(document: &[u16], index: usize) -> Option<usize> {
+    let end_index = get_system_literal_end_index(document, index)?;
+
+    if document[(index + 1)..end_index].iter().all(is_pubid_char) {
+        Some(end_index)
+    }
+
+    else {
+        None
     }
 
 }
 
 // https://www.w3.org/TR/xml/#NT-intSubset
-// (markupdecl | DeclSep)*
+// (markup_decl | whitespace | pe_reference)*
+// It returns the index of `]` that closes the internal subset.
 pub fn get_internal_subset_end_index(document: &[u16], mut index: usize) -> Option<usize> {
-    todo!()
+
+    loop {
+        index = skip_whitespaces(document, index);
+
+        if index >= document.len() {
+            return None;
+        }
+
+        if document[index] == ']' as u16 {
+            return Some(index);
+        }
+
+        index = match get_markup_decl_end_index(document, index) {
+            Some(markup_decl_end_index) => markup_decl_end_index + 1,
+            None => get_pe_reference_end_index(document, index)? + 1
+        };
+    }
+
+}
+
+// https://www.w3.org/TR/xml/#NT-markupdecl
+// element_decl | attlist_decl | entity_decl | notation_decl | processing_instruction | comment
+// Attribute-list declarations are parsed (see `parse_attlist_decl`), and the other declarations are only skipped:
+// they end at the first `>` that is not in a quoted literal.
+pub fn get_markup_decl_end_index(document: &[u16], index: usize) -> Option<usize> {
+
+    if let Some(end_index) = get_comment_end_index(document, index) {
+        return Some(end_index);
+    }
+
+    if let Some(end_index) = get_processing_instruction_end_index(document, index) {
+        return Some(end_index);
+    }
+
+    if let Some((_, end_index)) = parse_attlist_decl(document, index) {
+        return Some(end_index);
+    }
+
+    for keyword in ["<!ELEMENT", "<!ENTITY", "<!NOTATION"] {
+        let keyword_end_index = index + keyword.len();
+
+        if keyword_end_index < document.len()
+            && document[index..keyword_end_index] == into_v16(keyword)
+            && is_whitespace(&document[keyword_end_index])
+        {
+            return get_decl_close_index(document, keyword_end_index);
+        }
+
+    }
+
+    None
+}
+
+// the index of the first `>` that is not in a quoted literal
+fn get_decl_close_index(document: &[u16], mut index: usize) -> Option<usize> {
+
+    while index < document.len() {
+
+        if document[index] == '>' as u16 {
+            return Some(index);
+        }
+
+        else if document[index] == '"' as u16 || document[index] == '\'' as u16 {
+            index = get_system_literal_end_index(document, index)?;
+        }
+
+        index += 1;
+    }
+
+    None
+}
+
+// https://www.w3.org/TR/xml/#NT-PEReference
+// '%' name ';'
+pub fn get_pe_reference_end_index(document: &[u16], index: usize) -> Option<usize> {
+
+    if index >= document.len() || document[index] != '%' as u16 {
+        return None;
+    }
+
+    let name_end_index = get_name_end_index(document, index + 1)?;
+
+    if name_end_index + 1 < document.len() && document[name_end_index + 1] == ';' as u16 {
+        Some(name_end_index + 1)
+    }
+
+    else {
+        None
+    }
+
+}
+
+// (element name, Vec<(attribute name, attribute type)>)
+pub type AttlistDecl = (String, Vec<(String, String)>);
+
+// https://www.w3.org/TR/xml/#NT-AttlistDecl
+// '<!ATTLIST' whitespace name att_def* whitespace? '>'
+// att_def: whitespace name whitespace att_type whitespace default_decl
+// It returns the element name, the attribute names with their types, and the end index.
+// The type of an enumeration (`(a | b)`) is `ENUMERATION`, and `NOTATION (a | b)` is `NOTATION`.
+pub fn parse_attlist_decl(document: &[u16], index: usize) -> Option<(AttlistDecl, usize)> {
+
+    if index + 10 >= document.len() || document[index..(index + 9)] != into_v16("<!ATTLIST") || !is_whitespace(&document[index + 9]) {
+        return None;
+    }
+
+    let mut index = skip_whitespaces(document, index + 9);
+    let element_name_end_index = get_name_end_index(document, index)?;
+    let element_name = from_v16(&document[index..(element_name_end_index + 1)]);
+    let mut attributes = vec![];
+    index = element_name_end_index + 1;
+
+    loop {
+        let att_def_index = skip_whitespaces(document, index);
+
+        if att_def_index >= document.len() {
+            return None;
+        }
+
+        if document[att_def_index] == '>' as u16 {
+            return Some(((element_name, attributes), att_def_index));
+        }
+
+        if att_def_index == index {
+            return None;
+        }
+
+        let (att_def, att_def_end_index) = parse_att_def(document, att_def_index)?;
+        attributes.push(att_def);
+        index = att_def_end_index + 1;
+    }
+
+}
+
+// https://www.w3.org/TR/xml/#NT-AttDef
+// name whitespace att_type whitespace default_decl
+fn parse_att_def(document: &[u16], index: usize) -> Option<((String, String), usize)> {
+    let name_end_index = get_name_end_index(document, index)?;
+    let name = from_v16(&document[index..(name_end_index + 1)]);
+    let mut index = skip_whitespaces(document, name_end_index + 1);
+
+    if index == name_end_index + 1 || index >= document.len() {
+        return None;
+    }
+
+    // https://www.w3.org/TR/xml/#NT-AttType
+    let att_type = if document[index] == '(' as u16 {
+        index = get_enumeration_end_index(document, index)?;
+        String::from("ENUMERATION")
+    }
+
+    else {
+        let type_end_index = get_name_end_index(document, index)?;
+        let att_type = from_v16(&document[index..(type_end_index + 1)]);
+        index = type_end_index;
+
+        match att_type.as_str() {
+            "CDATA" | "ID" | "IDREF" | "IDREFS" | "ENTITY" | "ENTITIES" | "NMTOKEN" | "NMTOKENS" => {}
+            "NOTATION" => {
+                let enumeration_index = skip_whitespaces(document, index + 1);
+
+                if enumeration_index == index + 1 {
+                    return None;
+                }
+
+                index = get_enumeration_end_index(document, enumeration_index)?;
+            }
+            _ => {
+                return None;
+            }
+        }
+
+        att_type
+    };
+
+    let default_decl_index = skip_whitespaces(document, index + 1);
+
+    if default_decl_index == index + 1 || default_decl_index >= document.len() {
+        return None;
+    }
+
+    index = default_decl_index;
+
+    // https://www.w3.org/TR/xml/#NT-DefaultDecl
+    // '#REQUIRED' | '#IMPLIED' | (('#FIXED' whitespace)? att_value)
+    if document[index] == '#' as u16 {
+        let keyword_end_index = get_name_end_index(document, index + 1)?;
+
+        match from_v16(&document[(index + 1)..(keyword_end_index + 1)]).as_str() {
+            "REQUIRED" | "IMPLIED" => {
+                return Some(((name, att_type), keyword_end_index));
+            }
+            "FIXED" => {
+                index = skip_whitespaces(document, keyword_end_index + 1);
+
+                if index == keyword_end_index + 1 {
+                    return None;
+                }
+
+            }
+            _ => {
+                return None;
+            }
+        }
+
+    }
+
+    let value_end_index = get_system_literal_end_index(document, index)?;
+
+    Some(((name, att_type), value_end_index))
+}
+
+// '(' whitespace? name_token (whitespace? '|' whitespace? name_token)* whitespace? ')'
+// The tokens are not checked.
+fn get_enumeration_end_index(document: &[u16], mut index: usize) -> Option<usize> {
+
+    if index >= document.len() || document[index] != '(' as u16 {
+        return None;
+    }
+
+    while index < document.len() {
+
+        if document[index] == ')' as u16 {
+            return Some(index);
+        }
+
+        else if document[index] == '>' as u16 {
+            return None;
+        }
+
+        index += 1;
+    }
+
+    None
 }
 
 // it assumes that get_prolog_end_index(document, index) returns Some(..) for this args
@@ -385,24 +700,102 @@ pub fn parse_prolog(document: &[u16], mut index: usize) -> (Prolog, usize) {  //
     )
 }
 
-pub fn parse_xml_decl(document: &[u16], index: usize) -> XMLDecl {
-    todo!()
+// it assumes that get_xml_decl_end_index(document, index - 5) returns Some(..)
+pub fn parse_xml_decl(document: &[u16], mut index: usize) -> XMLDecl {
+    let version_info_end_index = get_version_info_end_index(document, index).unwrap();
+    let version_num = from_v16(get_quoted_value(document, version_info_end_index));
+    let mut encoding = None;
+    let mut standalone = None;
+    index = version_info_end_index;
+
+    if let Some(encoding_decl_end_index) = get_encoding_decl_end_index(document, index + 1) {
+        encoding = Some(from_v16(get_quoted_value(document, encoding_decl_end_index)));
+        index = encoding_decl_end_index;
+    }
+
+    if let Some(sd_decl_end_index) = get_sd_decl_end_index(document, index + 1) {
+        standalone = Some(get_quoted_value(document, sd_decl_end_index) == into_v16("yes"));
+    }
+
+    XMLDecl::new(version_num, encoding, standalone)
+}
+
+// document[end_index] is a closing quote
+fn get_quoted_value(document: &[u16], end_index: usize) -> &[u16] {
+    let quote = document[end_index];
+    let mut begin_index = end_index - 1;
+
+    while document[begin_index] != quote {
+        begin_index -= 1;
+    }
+
+    &document[(begin_index + 1)..end_index]
 }
 
+// it assumes that get_doctype_decl_end_index(document, index - 9) returns Some(..)
 pub fn parse_doctype_decl(document: &[u16], index: usize) -> DocTypeDecl {
     #[cfg(test)]
     assert!(is_whitespace(&document[index]));
 
-    let name_end_index = get_name_end_index(document, index + 1).unwrap();
-    let name = document[(index + 1)..(name_end_index + 1)].to_vec();
+    let index = skip_whitespaces(document, index);
+    let name_end_index = get_name_end_index(document, index).unwrap();
+    let mut result = DocTypeDecl::new(from_v16(&document[index..(name_end_index + 1)]));
+
+    let mut index = name_end_index + 1;
+
+    while document[index] != '[' as u16 && document[index] != '>' as u16 {
+
+        if document[index] == '"' as u16 || document[index] == '\'' as u16 {
+            index = get_system_literal_end_index(document, index).unwrap();
+        }
+
+        index += 1;
+    }
+
+    if document[index] == '>' as u16 {
+        return result;
+    }
+
+    let internal_subset_begin_index = index + 1;
+    let internal_subset_end_index = get_internal_subset_end_index(document, internal_subset_begin_index).unwrap();
+    index = internal_subset_begin_index;
+
+    while index < internal_subset_end_index {
+        index = skip_whitespaces(document, index);
+
+        if let Some(((element_name, attributes), end_index)) = parse_attlist_decl(document, index) {
+
+            for (attribute_name, attribute_type) in attributes.into_iter() {
+                result.add_attribute_type(&element_name, attribute_name, attribute_type);
+            }
+
+            index = end_index + 1;
+        }
+
+        else if let Some(end_index) = get_markup_decl_end_index(document, index) {
+            index = end_index + 1;
+        }
+
+        else if let Some(end_index) = get_pe_reference_end_index(document, index) {
+            index = end_index + 1;
+        }
 
-    DocTypeDecl::new(from_v16(&name))
+        else {
+            break;
+        }
+
+    }
+
+    result.internal_subset = Some(from_v16(&document[internal_subset_begin_index..internal_subset_end_index]));
+
+    result
 }
 
 #[cfg(test)]
 mod tests {
     use crate::parse::*;
     use crate::testbench::*;
+    use crate::utils::into_v16;
 
     #[test]
     fn prolog_test() {
@@ -412,6 +805,12 @@ mod tests {
                 ("  ", Some(1)),
                 ("  end", Some(1)),
                 ("<!DOCTYPE html>", Some(14)),
+                ("<!DOCTYPE  a >", Some(13)),
+                ("<!DOCTYPE html SYSTEM 'about:legacy-compat'>", Some(43)),
+                ("<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Strict//EN\" \"http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd\">", Some(108)),
+                ("<!DOCTYPE html PUBLIC \"{invalid}\" \"a.dtd\">", None),
+                ("<!DOCTYPE html PUBLIC \"a\">", None),
+                ("<!DOCTYPE html", None),
                 (" <!DOCTYPE html>", Some(15)),
                 ("<!DOCTYPE html> ", Some(15)),
                 (" <!DOCTYPE html> ", Some(16)),
@@ -420,15 +819,81 @@ mod tests {
         );
     }
 
+    #[test]
+    fn xml_decl_test() {
+        get_xxx_end_index(
+            vec![
+                ("not a xml_decl", None),
+                ("<?xml version=\"1.0\"?>", Some(20)),
+                ("<?xml version='1.0' ?>", Some(21)),
+                ("<?xml version=\"1.0\" encoding=\"UTF-8\"?>", Some(37)),
+                ("<?xml version=\"1.0\" encoding='ISO-8859-1' standalone=\"yes\"?>", Some(59)),
+                ("<?xml version=\"1.0\" standalone='no'?>", Some(36)),
+                ("<?xml version=\"1.0\" encoding=\"\"?>", None),
+                ("<?xml encoding=\"UTF-8\"?>", None),
+                ("<?xml version=\"1.0\">", None),
+            ],
+            get_xml_decl_end_index
+        );
+    }
+
+    #[test]
+    fn parse_xml_decl_test() {
+        let document = into_v16("<?xml version=\"1.0\" encoding='windows-1252' standalone=\"yes\" ?>");
+        let xml_decl = parse_xml_decl(&document, 5);
+
+        assert_eq!(xml_decl.version_num, "1.0");
+        assert_eq!(xml_decl.encoding, Some(String::from("windows-1252")));
+        assert_eq!(xml_decl.standalone, Some(true));
+    }
+
     #[test]
     fn doctype_test() {
         get_xxx_end_index(
             vec![
                 ("not a doctype", None),
                 ("<!DOCTYPE html>", Some(14)),
+                ("<!DOCTYPE  a >", Some(13)),
+                ("<!DOCTYPE html SYSTEM 'about:legacy-compat'>", Some(43)),
+                ("<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Strict//EN\" \"http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd\">", Some(108)),
+                ("<!DOCTYPE html PUBLIC \"{invalid}\" \"a.dtd\">", None),
+                ("<!DOCTYPE html PUBLIC \"a\">", None),
+                ("<!DOCTYPE html", None),
+                ("<!DOCTYPE r [<!ELEMENT r ANY>]>", Some(30)),
+                ("<!DOCTYPE r SYSTEM 'r.dtd' [ %p; <!-- ] --> ]>", Some(45)),
+                ("<!DOCTYPE r [<!ENTITY e '>'>]>", Some(29)),
+                ("<!DOCTYPE r [<!ATTLIST r a CDATA #IMPLIED>]>", Some(43)),
+                ("<!DOCTYPE r [<!ATTLIST r a TOKEN #IMPLIED>]>", None),
+                ("<!DOCTYPE r [<!ATTLIST r a CDATA>]>", None),
+                ("<!DOCTYPE r [<!ELEMENT r ANY>", None),
+                ("<!DOCTYPE r [<r/>]>", None),
             ],
             get_doctype_decl_end_index
         );
     }
 
+    #[test]
+    fn attlist_decl_test() {
+        let document = into_v16("<!ATTLIST r\n  id ID #REQUIRED\n  class NMTOKENS 'a  b'\n  title CDATA #FIXED \"x>y\"\n  size (s | m | l) 'm'\n  fmt NOTATION (gif) #IMPLIED>");
+        let ((element_name, attributes), end_index) = parse_attlist_decl(&document, 0).unwrap();
+
+        assert_eq!(element_name, "r");
+        assert_eq!(end_index, document.len() - 1);
+        assert_eq!(
+            attributes,
+            vec![("id", "ID"), ("class", "NMTOKENS"), ("title", "CDATA"), ("size", "ENUMERATION"), ("fmt", "NOTATION")].into_iter().map(
+                |(name, att_type)| (name.to_string(), att_type.to_string())
+            ).collect::<Vec<(String, String)>>()
+        );
+
+        let document = into_v16("<!DOCTYPE r [<!ATTLIST r a NMTOKENS #IMPLIED a CDATA #IMPLIED> <!ENTITY e 'x'> <!ATTLIST s b ID #IMPLIED>]>");
+        let doctype_decl = parse_doctype_decl(&document, 9);
+
+        // the first declaration is binding
+        assert_eq!(doctype_decl.get_attribute_type("r", "a"), Some("NMTOKENS"));
+        assert_eq!(doctype_decl.get_attribute_type("s", "b"), Some("ID"));
+        assert_eq!(doctype_decl.get_attribute_type("s", "a"), None);
+        assert_eq!(doctype_decl.to_string(), "<!DOCTYPE r [<!ATTLIST r a NMTOKENS #IMPLIED a CDATA #IMPLIED> <!ENTITY e 'x'> <!ATTLIST s b ID #IMPLIED>]>");
+    }
+
 }
\ No newline at end of file
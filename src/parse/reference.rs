@@ -1,6 +1,6 @@
 use super::get_name_end_index;
 use crate::predicate::{is_numeric, is_hexadecimal, is_valid_char_u32};
-use crate::utils::{to_int_dec, to_int_hex};
+use crate::utils::{from_v16, to_int_dec, to_int_hex};
 
 // https://www.w3.org/TR/xml/#NT-Reference
 // entity_reference | char_reference
@@ -92,11 +92,50 @@ pub fn get_char_reference_end_index(document: &[u16], mut index: usize) -> Optio
 
 }
 
+// `reference` is `'&' name ';'` or a char reference
+// it returns None if `reference` is not a char reference nor a predefined entity
+// https://www.w3.org/TR/xml/#sec-predefined-ent
+pub fn expand_reference(reference: &[u16]) -> Option<Vec<u16>> {
+
+    if reference.len() < 3 {
+        return None;
+    }
+
+    let body = &reference[1..(reference.len() - 1)];
+
+    let code_point = if body[0] == '#' as u16 && body.get(1) == Some(&('x' as u16)) {
+        to_int_hex(&body[2..])
+    } else if body[0] == '#' as u16 {
+        to_int_dec(&body[1..])
+    } else {
+        match from_v16(body).as_str() {
+            "lt" => Some('<' as u32),
+            "gt" => Some('>' as u32),
+            "amp" => Some('&' as u32),
+            "apos" => Some('\'' as u32),
+            "quot" => Some('"' as u32),
+            _ => None
+        }
+    };
+
+    match code_point {
+        Some(n) => match char::from_u32(n) {
+            Some(c) => {
+                let mut buffer = [0; 2];
+                Some(c.encode_utf16(&mut buffer).to_vec())
+            },
+            None => None
+        },
+        None => None
+    }
+
+}
 
 #[cfg(test)]
 mod tests {
     use crate::parse::*;
     use crate::testbench::*;
+    use crate::utils::into_v16;
 
     #[test]
     fn entity_reference_test() {
@@ -123,6 +162,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn expand_reference_test() {
+        let test_cases = vec![
+            ("&lt;", Some("<")),
+            ("&amp;", Some("&")),
+            ("&#123;", Some("{")),
+            ("&#x10fa;", Some("\u{10fa}")),
+            ("&#x1F600;", Some("😀")),
+            ("&#xd800;", None),
+            ("&nbsp;", None),
+            ("&#;", None),
+            ("&#x;", None),
+        ];
+
+        for (case, answer) in test_cases.into_iter() {
+            assert_eq!(expand_reference(&into_v16(case)), answer.map(into_v16));
+        }
+
+    }
+
     #[test]
     fn char_reference_test() {
         get_xxx_end_index(
@@ -245,7 +245,7 @@ pub fn parse_element(document: &[u16], index: usize) -> Option<(RawElement, usiz
         Some(tag_end_index) => {
             let ((name, attributes), _) = parse_tag(document, index);
 
-            return Some((RawElement::new(name, attributes, true, vec![]), tag_end_index));
+            return Some((RawElement::new(name, attributes, true, vec![], GString::new(index, tag_end_index + 1), None), tag_end_index));
         },
         None => {}
     }
@@ -257,13 +257,20 @@ pub fn parse_element(document: &[u16], index: usize) -> Option<(RawElement, usiz
             let mut contents = vec![];
 
             while let Some((content, content_end_index)) = parse_content(document, curr_index) {
-                contents.push(Box::new(content));
+                contents.push(content);
                 curr_index = content_end_index + 1;
             }
 
             match get_end_tag_end_index(document, curr_index) {
                 Some(end_tag_end_index) if get_end_tag_name(document, curr_index) == name.to_vec() => {
-                    return Some((RawElement::new(name, attributes, false, contents), end_tag_end_index));
+                    return Some((
+                        RawElement::new(
+                            name, attributes, false, contents,
+                            GString::new(index, start_tag_end_index + 1),
+                            Some(GString::new(curr_index, end_tag_end_index + 1))
+                        ),
+                        end_tag_end_index
+                    ));
                 },
                 None if is_malformed_tag(document, curr_index) => {
                     raise_error(
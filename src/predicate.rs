@@ -47,6 +47,14 @@ pub fn is_whitespace(c: &u16) -> bool {
     *c == 0xd
 }
 
+// https://www.w3.org/TR/xml/#NT-PubidChar
+#[inline]
+pub fn is_pubid_char(c: &u16) -> bool {
+    *c == ' ' as u16 || *c == '\r' as u16 || *c == '\n' as u16
+    || is_alpha_low(c) || is_alpha_cap(c) || is_numeric(c)
+    || "-'()+,./:=?;!*#@$_%".encode_utf16().any(|p| p == *c)
+}
+
 #[inline]
 pub fn is_alpha_low(c: &u16) -> bool {
     'a' as u16 <= *c && *c <= 'z' as u16
@@ -0,0 +1,268 @@
+use crate::err::HxmlError;
+use crate::node::element::{Content, Element};
+use crate::node::memory;
+use crate::node::prolog::Prolog;
+use crate::serialize::{get_attributes, write_attribute, write_content};
+use std::io::Write;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Indent {
+    Spaces(usize),
+    Tabs,
+}
+
+/// How to write elements without contents
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SelfClosing {
+
+    /// `<a/>` if it was `<a/>`, `<a></a>` if it was `<a></a>`
+    Keep,
+
+    /// `<a/>`
+    Compact,
+
+    /// `<a />`
+    Spaced,
+
+    /// `<a></a>`
+    Expanded,
+}
+
+#[derive(Clone, Debug)]
+pub struct PrettyOptions {
+    pub indent: Indent,
+
+    /// If a start tag is longer than this, its attributes are written one per line (if `wrap_attributes` is set).
+    /// Contents are never broken to fit in this width.
+    pub max_line_width: usize,
+    pub wrap_attributes: bool,
+    pub self_closing: SelfClosing,
+}
+
+impl Default for PrettyOptions {
+
+    fn default() -> Self {
+        PrettyOptions {
+            indent: Indent::Spaces(2),
+            max_line_width: 100,
+            wrap_attributes: true,
+            self_closing: SelfClosing::Keep,
+        }
+    }
+
+}
+
+/// It re-indents the subtree.
+///
+/// Whitespaces are significant (so it writes the subtree as it is) inside
+/// - elements with `xml:space="preserve"`, and their descendants
+/// - elements with mixed content: char data (except whitespaces), CDATA sections or references
+///
+/// In other elements, char data that are only whitespaces are removed, and each child is written on its own line.
+pub fn pretty_element(element: &Element, options: &PrettyOptions) -> String {
+    let mut result = vec![];
+    write_element(element, options, 0, false, &mut result).unwrap();
+
+    String::from_utf8(result).unwrap()
+}
+
+pub fn pretty_document(prolog: &Option<Prolog>, root: &Element, options: &PrettyOptions) -> String {
+    let mut result = vec![];
+
+    if let Some(prolog) = prolog {
+
+        if let Some(xml_decl) = &prolog.xml_decl {
+            writeln!(result, "{}", xml_decl.to_string()).unwrap();
+        }
+
+        if let Some(doctype_decl) = &prolog.doctype_decl {
+            writeln!(result, "{}", doctype_decl.to_string()).unwrap();
+        }
+
+    }
+
+    write_element(root, options, 0, false, &mut result).unwrap();
+    result.push(b'\n');
+
+    String::from_utf8(result).unwrap()
+}
+
+// Contents are streamed to `w` with `write_content`: no intermediate strings are built for the subtrees.
+fn write_element<W: Write>(element: &Element, options: &PrettyOptions, depth: usize, preserve_space: bool, w: &mut W) -> Result<(), HxmlError> {
+    let preserve_space = match element.attributes.iter().find(|att| att.name == "xml:space") {
+        Some(att) if att.value == "preserve" => true,
+        Some(att) if att.value == "default" => false,
+        _ => preserve_space
+    };
+
+    let indent = get_indent(options, depth);
+    let start_tag = get_start_tag(element, options, &indent, depth);
+
+    if preserve_space || is_mixed_content(element) {
+        w.write_all(start_tag.as_bytes())?;
+
+        if element.contents.is_empty() {
+            return write_empty_element_end(element, options, w);
+        }
+
+        w.write_all(b">")?;
+
+        for content in element.contents.iter() {
+            write_content(content, false, w)?;
+        }
+
+        write!(w, "</{}>", element.tag_name)?;
+        return Ok(());
+    }
+
+    let contents = element.contents.iter().filter(
+        |content| !matches!(content, Content::CharData(_))
+    ).collect::<Vec<&Content>>();
+
+    w.write_all(start_tag.as_bytes())?;
+
+    if contents.is_empty() {
+        return write_empty_element_end(element, options, w);
+    }
+
+    w.write_all(b">")?;
+
+    for content in contents.into_iter() {
+        write!(w, "\n{}", get_indent(options, depth + 1))?;
+
+        match content {
+            Content::Element(pointer) => {
+                write_element(memory::get(pointer.ptr), options, depth + 1, preserve_space, w)?;
+            },
+            _ => {
+                write_content(content, false, w)?;
+            }
+        }
+
+    }
+
+    write!(w, "\n{}</{}>", indent, element.tag_name)?;
+
+    Ok(())
+}
+
+// `<name att="value"`, without `>`
+fn get_start_tag(element: &Element, options: &PrettyOptions, indent: &str, depth: usize) -> String {
+    let attributes = get_attributes(element).into_iter().map(
+        |(name, value)| {
+            let mut attribute = String::new();
+            write_attribute(&name, &value, &mut attribute);
+
+            attribute
+        }
+    ).collect::<Vec<String>>();
+
+    let one_line = [vec![format!("<{}", element.tag_name)], attributes.clone()].concat().join(" ");
+
+    // +2 for `/>`
+    if !options.wrap_attributes || attributes.len() < 2 || indent.chars().count() + one_line.chars().count() + 2 <= options.max_line_width {
+        return one_line;
+    }
+
+    let attribute_indent = get_indent(options, depth + 1);
+    let mut result = format!("<{}", element.tag_name);
+
+    for attribute in attributes.iter() {
+        result.push('\n');
+        result.push_str(&attribute_indent);
+        result.push_str(attribute);
+    }
+
+    result
+}
+
+fn write_empty_element_end<W: Write>(element: &Element, options: &PrettyOptions, w: &mut W) -> Result<(), HxmlError> {
+
+    match options.self_closing {
+        SelfClosing::Keep if element.empty_element => { w.write_all(b"/>")?; },
+        SelfClosing::Compact => { w.write_all(b"/>")?; },
+        SelfClosing::Spaced => { w.write_all(b" />")?; },
+        _ => { write!(w, "></{}>", element.tag_name)?; }
+    }
+
+    Ok(())
+}
+
+fn is_mixed_content(element: &Element) -> bool {
+    element.contents.iter().any(
+        |content| match content {
+            Content::CharData(char_data) => char_data.chars().any(|c| !c.is_ascii_whitespace()),
+            Content::CDSect(_) | Content::Reference(_) => true,
+            _ => false
+        }
+    )
+}
+
+fn get_indent(options: &PrettyOptions, depth: usize) -> String {
+
+    match options.indent {
+        Indent::Spaces(n) => " ".repeat(n * depth),
+        Indent::Tabs => "\t".repeat(depth),
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{dom, into_dom, Element};
+    use crate::pretty::*;
+
+    #[test]
+    fn pretty_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        into_dom("<?xml version='1.0'?><root>   <a/><b></b>\n<c x=\"1\">text <i>i</i> text</c><pre xml:space=\"preserve\">  <d>  </d> </pre><!-- comment --><e aaaaaaaaaa=\"1\" bbbbbbbbbb=\"2\" cccccccccc=\"3\"><f/></e></root>".to_string()).unwrap();
+
+        let options = PrettyOptions {
+            max_line_width: 40,
+            ..PrettyOptions::default()
+        };
+
+        assert_eq!(
+            dom::to_pretty_string(&options),
+"<?xml version='1.0'?>
+<root>
+  <a/>
+  <b></b>
+  <c x=\"1\">text <i>i</i> text</c>
+  <pre xml:space=\"preserve\">  <d>  </d> </pre>
+  <!-- comment -->
+  <e
+    aaaaaaaaaa=\"1\"
+    bbbbbbbbbb=\"2\"
+    cccccccccc=\"3\">
+    <f/>
+  </e>
+</root>
+"
+        );
+
+        let options = PrettyOptions {
+            indent: Indent::Tabs,
+            self_closing: SelfClosing::Spaced,
+            wrap_attributes: false,
+            ..PrettyOptions::default()
+        };
+        let e = dom::get_element_by_tag_name(None, "e".to_string()).unwrap();
+
+        assert_eq!(
+            e.to_pretty_string(&options),
+            "<e aaaaaaaaaa=\"1\" bbbbbbbbbb=\"2\" cccccccccc=\"3\">\n\t<f />\n</e>"
+        );
+
+        // it doesn't change the tree, except whitespaces between elements
+        let pretty = dom::get_root().to_pretty_string(&PrettyOptions::default());
+        let root = dom::get_root();
+        let reparsed = Element::from_string(pretty).unwrap();
+        let pre = dom::get_element_by_tag_name(Some(reparsed.get_children()), "pre".to_string()).unwrap();
+        assert!(pre.is_same_tree(dom::get_element_by_tag_name(Some(root.get_children()), "pre".to_string()).unwrap()));
+
+        drop(lock);
+    }
+
+}
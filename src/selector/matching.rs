@@ -0,0 +1,379 @@
+use super::{AttributeOperator, Combinator, ComplexSelector, CompoundSelector, PseudoClass, SimpleSelector};
+use crate::dom;
+use crate::index;
+use crate::order;
+use crate::node::element::{Content, Element};
+use crate::node::memory;
+use crate::node::pointer::ElementPtr;
+use std::collections::{HashMap, HashSet};
+
+// positions of elements among their siblings
+// The children of a parent are read once when one of them is matched against `:nth-child` or a sibling combinator,
+// so a query doesn't scan the same children for every candidate.
+#[derive(Default)]
+pub struct SiblingCache {
+
+    // element -> (index among the element children of its parent, index among the children of the same type)
+    positions: HashMap<usize, (usize, usize)>,
+
+    // parent -> (element children, the number of children of each type)
+    parents: HashMap<usize, (Vec<ElementPtr>, HashMap<String, usize>)>,
+}
+
+impl SiblingCache {
+
+    // None if the element doesn't have a parent
+    fn get_position(&mut self, element: ElementPtr) -> Option<(usize, usize)> {
+        let parent = element.get_parent()?;
+
+        if !self.positions.contains_key(&element.ptr) {
+            let children = parent.get_children();
+            let mut type_counts: HashMap<String, usize> = HashMap::new();
+
+            for (index, child) in children.iter().enumerate() {
+                let count = type_counts.entry(memory::get(child.ptr).tag_name.clone()).or_insert(0);
+                self.positions.insert(child.ptr, (index, *count));
+                *count += 1;
+            }
+
+            self.parents.insert(parent.ptr, (children, type_counts));
+        }
+
+        self.positions.get(&element.ptr).copied()
+    }
+
+    // the element children of the parent of `element`, and the number of its siblings of the same type (including itself)
+    fn get_siblings(&mut self, element: ElementPtr, el: &Element) -> Option<(&[ElementPtr], usize)> {
+        let parent = element.get_parent()?;
+        self.get_position(element)?;
+        let (children, type_counts) = self.parents.get(&parent.ptr)?;
+
+        Some((children, type_counts[&el.tag_name]))
+    }
+
+}
+
+pub fn matches_selector_list(element: ElementPtr, selectors: &[ComplexSelector]) -> bool {
+    matches_selector_list_with_cache(element, selectors, &mut SiblingCache::default())
+}
+
+fn matches_selector_list_with_cache(element: ElementPtr, selectors: &[ComplexSelector], cache: &mut SiblingCache) -> bool {
+    selectors.iter().any(|selector| matches_complex_selector(element, selector, selector.compounds.len() - 1, cache))
+}
+
+// If `scope` is None, it searches the entire document (including the root). Otherwise, it searches the descendants of `scope`.
+// Candidates are taken from the indices when every selector in the list has an id, an indexed `[name=value]`, a class or a type selector in its subject.
+pub fn query_selector_all(selectors: &[ComplexSelector], scope: Option<ElementPtr>) -> Vec<ElementPtr> {
+    let root = dom::get_root();
+    let mut cache = SiblingCache::default();
+
+    let mut candidates = vec![];
+
+    for selector in selectors.iter() {
+
+        match get_indexed_candidates(&selector.compounds[selector.compounds.len() - 1]) {
+            Some(mut elements) => {
+                candidates.append(&mut elements);
+            }
+
+            // walks the tree
+            None => {
+                let mut result = vec![];
+
+                match scope {
+                    Some(scope) => {
+                        collect_descendants(scope, &mut result);
+                    }
+                    None => {
+                        result.push(root);
+                        collect_descendants(root, &mut result);
+                    }
+                }
+
+                return result.into_iter().filter(|element| matches_selector_list_with_cache(*element, selectors, &mut cache)).collect();
+            }
+        }
+
+    }
+
+    let mut visited = HashSet::with_capacity(candidates.len());
+    let mut result = Vec::with_capacity(candidates.len());
+
+    for candidate in candidates.into_iter() {
+
+        if !visited.insert(candidate.ptr) || !memory::get(candidate.ptr).is_alive {
+            continue;
+        }
+
+        let is_in_scope = match scope {
+            Some(scope) => order::is_descendant(candidate, scope),
+            None => candidate == root || order::is_descendant(candidate, root),
+        };
+
+        if is_in_scope && matches_selector_list_with_cache(candidate, selectors, &mut cache) {
+            result.push(candidate);
+        }
+
+    }
+
+    order::sort(&mut result);
+
+    result
+}
+
+fn get_indexed_candidates(compound: &CompoundSelector) -> Option<Vec<ElementPtr>> {
+
+    for simple_selector in compound.simple_selectors.iter() {
+
+        if let SimpleSelector::Id(id) = simple_selector {
+            return Some(dom::get_elements_by_id(None, id.to_string()));
+        }
+
+    }
+
+    // `[name=value]`, if there's an index on `name` (`dom::create_index`)
+    for simple_selector in compound.simple_selectors.iter() {
+
+        if let SimpleSelector::Attribute { name, matcher: Some((AttributeOperator::Equal, value)) } = simple_selector {
+
+            if let Some(elements) = index::get_by_attribute(name, value) {
+                return Some(elements);
+            }
+
+        }
+
+    }
+
+    for simple_selector in compound.simple_selectors.iter() {
+
+        if let SimpleSelector::Class(class) = simple_selector {
+            return Some(dom::get_elements_by_class_name(None, class.to_string()));
+        }
+
+    }
+
+    compound.tag_name.as_ref().map(|tag_name| dom::get_elements_by_tag_name(None, tag_name.to_string()))
+}
+
+// matches `selector.compounds[..(index + 1)]`, with `element` as the subject of `selector.compounds[index]`
+fn matches_complex_selector(element: ElementPtr, selector: &ComplexSelector, index: usize, cache: &mut SiblingCache) -> bool {
+
+    if !matches_compound_selector(element, &selector.compounds[index], cache) {
+        return false;
+    }
+
+    if index == 0 {
+        return true;
+    }
+
+    match selector.combinators[index - 1] {
+        Combinator::Child => match element.get_parent() {
+            Some(parent) => matches_complex_selector(parent, selector, index - 1, cache),
+            None => false
+        },
+        Combinator::Descendant => {
+            let mut curr_element = element.get_parent();
+
+            while let Some(ancestor) = curr_element {
+
+                if matches_complex_selector(ancestor, selector, index - 1, cache) {
+                    return true;
+                }
+
+                curr_element = ancestor.get_parent();
+            }
+
+            false
+        },
+        Combinator::NextSibling => match cache.get_position(element) {
+            Some((position, _)) if position > 0 => {
+                let previous = cache.parents[&element.get_parent().unwrap().ptr].0[position - 1];
+
+                matches_complex_selector(previous, selector, index - 1, cache)
+            },
+            _ => false
+        },
+        Combinator::SubsequentSibling => match cache.get_position(element) {
+            Some((position, _)) => {
+                let parent = element.get_parent().unwrap();
+
+                // from the nearest one, and it stops at the first match
+                (0..position).rev().any(
+                    |sibling_index| {
+                        let sibling = cache.parents[&parent.ptr].0[sibling_index];
+
+                        matches_complex_selector(sibling, selector, index - 1, cache)
+                    }
+                )
+            },
+            None => false
+        },
+    }
+
+}
+
+fn matches_compound_selector(element: ElementPtr, compound: &CompoundSelector, cache: &mut SiblingCache) -> bool {
+    let el = memory::get(element.ptr);
+
+    if let Some(tag_name) = &compound.tag_name {
+
+        if tag_name != &el.tag_name {
+            return false;
+        }
+
+    }
+
+    compound.simple_selectors.iter().all(|simple_selector| matches_simple_selector(element, el, simple_selector, cache))
+}
+
+fn matches_simple_selector(element: ElementPtr, el: &Element, simple_selector: &SimpleSelector, cache: &mut SiblingCache) -> bool {
+
+    match simple_selector {
+        SimpleSelector::Id(id) => el.id.as_ref() == Some(id),
+        SimpleSelector::Class(class) => el.classes.contains(class),
+        SimpleSelector::Attribute { name, matcher } => match el.get_attribute(name.to_string()) {
+            Some(value) => match matcher {
+                Some((operator, expected)) => matches_attribute_value(&value, *operator, expected),
+                None => true
+            },
+            None => false
+        },
+        SimpleSelector::PseudoClass(pseudo_class) => matches_pseudo_class(element, el, pseudo_class, cache),
+    }
+
+}
+
+fn matches_attribute_value(value: &str, operator: AttributeOperator, expected: &str) -> bool {
+
+    match operator {
+        AttributeOperator::Equal => value == expected,
+        AttributeOperator::Includes => !expected.is_empty() && value.split_ascii_whitespace().any(|word| word == expected),
+        AttributeOperator::DashMatch => value == expected || value.starts_with(&format!("{}-", expected)),
+        AttributeOperator::Prefix => !expected.is_empty() && value.starts_with(expected),
+        AttributeOperator::Suffix => !expected.is_empty() && value.ends_with(expected),
+        AttributeOperator::Substring => !expected.is_empty() && value.contains(expected),
+    }
+
+}
+
+// https://www.w3.org/TR/selectors-3/#structural-pseudos
+// `:nth-child` and the others require a parent element, so they don't match the root element.
+fn matches_pseudo_class(element: ElementPtr, el: &Element, pseudo_class: &PseudoClass, cache: &mut SiblingCache) -> bool {
+
+    match pseudo_class {
+        PseudoClass::Root => el.get_parent().is_none(),
+
+        // comments don't count
+        PseudoClass::Empty => el.contents.iter().all(
+            |content| match content {
+                Content::Comment(_) => true,
+                Content::CharData(char_data) | Content::CDSect(char_data) => char_data.is_empty(),
+                _ => false
+            }
+        ),
+        PseudoClass::Not(compound) => !matches_compound_selector(element, compound, cache),
+        _ => {
+            let (position, type_position) = match cache.get_position(element) {
+                Some(position) => position,
+                None => {
+                    return false;
+                }
+            };
+            let (siblings, type_count) = cache.get_siblings(element, el).unwrap();
+
+            match pseudo_class {
+                PseudoClass::OnlyChild => siblings.len() == 1,
+                PseudoClass::OnlyOfType => type_count == 1,
+                PseudoClass::NthChild(a, b) => is_nth(*a, *b, position as i64 + 1),
+                PseudoClass::NthLastChild(a, b) => is_nth(*a, *b, (siblings.len() - position) as i64),
+                PseudoClass::NthOfType(a, b) => is_nth(*a, *b, type_position as i64 + 1),
+                PseudoClass::NthLastOfType(a, b) => is_nth(*a, *b, (type_count - type_position) as i64),
+                PseudoClass::Root | PseudoClass::Empty | PseudoClass::Not(_) => unreachable!(),
+            }
+        },
+    }
+
+}
+
+// is there a non-negative integer n such that `a * n + b == position`?
+fn is_nth(a: i64, b: i64, position: i64) -> bool {
+
+    if a == 0 {
+        position == b
+    }
+
+    else {
+        (position - b) % a == 0 && (position - b) / a >= 0
+    }
+
+}
+
+// pre-order
+fn collect_descendants(element: ElementPtr, buffer: &mut Vec<ElementPtr>) {
+
+    for child in element.get_children().into_iter() {
+        buffer.push(child);
+        collect_descendants(child, buffer);
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dom;
+
+    #[test]
+    fn query_selector_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        crate::into_dom("<html lang=\"en-US\">
+<body>
+  <div id=\"main\" class=\"box wide\">
+    <h1>Title</h1>
+    <p class=\"a\" data-x=\"foo bar\">1</p>
+    <p>2</p>
+    <span/>
+    <p class=\"a\">3<!-- c --></p>
+    <ul><li>a</li><li>b</li><li>c</li><li>d</li><li>e</li></ul>
+  </div>
+  <div class=\"box\"><p class=\"a\"></p><!-- c --></div>
+</body>
+</html>".to_string()).unwrap();
+
+        let texts = |selectors: &str| dom::query_selector_all(selectors).unwrap().iter().map(
+            |element| format!("{}:{}", element.get_tag_name(), element.get_inner_string())
+        ).collect::<Vec<String>>();
+
+        assert_eq!(texts("#main > p"), vec!["p:1", "p:2", "p:3<!-- c -->"]);
+        assert_eq!(texts("div p.a"), vec!["p:1", "p:3<!-- c -->", "p:"]);
+        assert_eq!(texts("p.a, h1"), vec!["h1:Title", "p:1", "p:3<!-- c -->", "p:"]);
+        assert_eq!(texts("h1 + p"), vec!["p:1"]);
+        assert_eq!(texts("h1 ~ p:not(.a)"), vec!["p:2"]);
+        assert_eq!(texts("[data-x~=bar]"), vec!["p:1"]);
+        assert_eq!(texts("[data-x^='foo '][data-x$=r][data-x*=\"o b\"]"), vec!["p:1"]);
+        assert_eq!(dom::query_selector_all("[lang|=en]").unwrap(), vec![dom::get_root()]);
+        assert_eq!(texts("li:nth-child(2n+1)"), vec!["li:a", "li:c", "li:e"]);
+        assert_eq!(texts("li:nth-last-child(-n+2)"), vec!["li:d", "li:e"]);
+        assert_eq!(texts("#main > :first-of-type"), vec!["h1:Title", "p:1", "span:", "ul:<li>a</li><li>b</li><li>c</li><li>d</li><li>e</li>"]);
+        assert_eq!(texts("#main p:last-of-type"), vec!["p:3<!-- c -->"]);
+        assert_eq!(texts(".box :empty"), vec!["span:", "p:"]);
+        assert_eq!(texts(".box > :only-child"), vec!["p:"]);
+        assert_eq!(texts(":root > body > .wide > *:only-of-type:not(ul)"), vec!["h1:Title", "span:"]);
+        assert!(dom::query_selector_all("p:hover").is_err());
+
+        // structural pseudo-classes need a parent element
+        assert!(dom::get_root().matches(":root").unwrap());
+        assert!(!dom::get_root().matches(":first-child").unwrap());
+        assert!(!dom::get_root().matches(":only-of-type").unwrap());
+        assert_eq!(texts(":only-child").len(), 2);
+
+        let main = dom::get_element_by_id(None, "main".to_string()).unwrap();
+        assert_eq!(main.query_selector("div p").unwrap().unwrap().get_inner_string(), "1");
+        assert_eq!(main.query_selector_all("p.a").unwrap().len(), 2);
+        assert_eq!(main.query_selector(".box").unwrap(), None);
+        assert!(main.matches("body > div.wide").unwrap());
+
+        drop(lock);
+    }
+
+}
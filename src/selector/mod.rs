@@ -0,0 +1,95 @@
+// CSS selectors
+// https://www.w3.org/TR/selectors-3/
+
+mod matching;
+mod parse;
+
+pub use matching::{matches_selector_list, query_selector_all};
+pub use parse::parse_selector_list;
+
+// `div > p.a + span`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ComplexSelector {
+
+    // `compounds[i]` and `compounds[i + 1]` are combined by `combinators[i]`
+    // the last compound is the subject of the selector
+    pub compounds: Vec<CompoundSelector>,
+    pub combinators: Vec<Combinator>,
+}
+
+// `p.a#b[c]:first-child`
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompoundSelector {
+
+    // None for `*`, or when the type selector is omitted
+    pub tag_name: Option<String>,
+    pub simple_selectors: Vec<SimpleSelector>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Combinator {
+
+    // `a b`
+    Descendant,
+
+    // `a > b`
+    Child,
+
+    // `a + b`
+    NextSibling,
+
+    // `a ~ b`
+    SubsequentSibling,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SimpleSelector {
+    Id(String),
+    Class(String),
+
+    // `[name]`, `[name=value]`, `[name^=value]`...
+    Attribute {
+        name: String,
+        matcher: Option<(AttributeOperator, String)>,
+    },
+    PseudoClass(PseudoClass),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AttributeOperator {
+
+    // `=`
+    Equal,
+
+    // `~=`: one of the whitespace-separated words
+    Includes,
+
+    // `|=`: the value, or the value followed by `-`
+    DashMatch,
+
+    // `^=`
+    Prefix,
+
+    // `$=`
+    Suffix,
+
+    // `*=`
+    Substring,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PseudoClass {
+    Root,
+    Empty,
+    OnlyChild,
+    OnlyOfType,
+
+    // `an + b`
+    // `:first-child` is `:nth-child(0n + 1)`, and `:last-of-type` is `:nth-last-of-type(0n + 1)`
+    NthChild(i64, i64),
+    NthLastChild(i64, i64),
+    NthOfType(i64, i64),
+    NthLastOfType(i64, i64),
+
+    Not(Box<CompoundSelector>),
+}
@@ -0,0 +1,503 @@
+use super::{AttributeOperator, Combinator, ComplexSelector, CompoundSelector, PseudoClass, SimpleSelector};
+use crate::err::HxmlError;
+
+// https://www.w3.org/TR/selectors-3/#w3cselgrammar
+// selector_list: complex_selector (whitespace* ',' whitespace* complex_selector)*
+pub fn parse_selector_list(selectors: &str) -> Result<Vec<ComplexSelector>, HxmlError> {
+    let mut parser = Parser {
+        selectors: selectors.to_string(),
+        chars: selectors.chars().collect(),
+        index: 0,
+    };
+    let mut result = vec![];
+
+    loop {
+        parser.skip_whitespaces();
+        result.push(parser.parse_complex_selector()?);
+        parser.skip_whitespaces();
+
+        match parser.peek() {
+            Some(',') => {
+                parser.index += 1;
+            }
+            None => {
+                return Ok(result);
+            }
+            Some(c) => {
+                return Err(parser.error(&format!("unexpected `{}`", c)));
+            }
+        }
+
+    }
+
+}
+
+struct Parser {
+    selectors: String,
+    chars: Vec<char>,
+    index: usize,
+}
+
+impl Parser {
+
+    // compound_selector (combinator compound_selector)*
+    fn parse_complex_selector(&mut self) -> Result<ComplexSelector, HxmlError> {
+        let mut compounds = vec![self.parse_compound_selector()?];
+        let mut combinators = vec![];
+
+        loop {
+            let has_whitespace = self.skip_whitespaces();
+
+            let combinator = match self.peek() {
+                None | Some(',') | Some(')') => {
+                    break;
+                }
+                Some('>') => Combinator::Child,
+                Some('+') => Combinator::NextSibling,
+                Some('~') => Combinator::SubsequentSibling,
+                _ if has_whitespace => {
+                    combinators.push(Combinator::Descendant);
+                    compounds.push(self.parse_compound_selector()?);
+                    continue;
+                }
+                Some(c) => {
+                    return Err(self.error(&format!("unexpected `{}`", c)));
+                }
+            };
+
+            self.index += 1;
+            self.skip_whitespaces();
+            combinators.push(combinator);
+            compounds.push(self.parse_compound_selector()?);
+        }
+
+        Ok(ComplexSelector { compounds, combinators })
+    }
+
+    // (type_selector | '*')? (id | class | attribute | pseudo_class)*
+    fn parse_compound_selector(&mut self) -> Result<CompoundSelector, HxmlError> {
+        let begin_index = self.index;
+        let mut tag_name = None;
+        let mut simple_selectors = vec![];
+
+        if self.peek() == Some('*') {
+            self.index += 1;
+        }
+
+        else if self.is_ident_start() {
+            tag_name = Some(self.parse_ident()?);
+        }
+
+        loop {
+
+            match self.peek() {
+                Some('#') => {
+                    self.index += 1;
+                    simple_selectors.push(SimpleSelector::Id(self.parse_ident()?));
+                }
+                Some('.') => {
+                    self.index += 1;
+                    simple_selectors.push(SimpleSelector::Class(self.parse_ident()?));
+                }
+                Some('[') => {
+                    self.index += 1;
+                    simple_selectors.push(self.parse_attribute_selector()?);
+                }
+                Some(':') => {
+                    self.index += 1;
+                    simple_selectors.push(SimpleSelector::PseudoClass(self.parse_pseudo_class()?));
+                }
+                _ => {
+                    break;
+                }
+            }
+
+        }
+
+        if self.index == begin_index {
+            return Err(self.error("expected a selector"));
+        }
+
+        Ok(CompoundSelector { tag_name, simple_selectors })
+    }
+
+    // after '['
+    // whitespace* ident whitespace* (operator whitespace* (ident | string) whitespace*)? ']'
+    fn parse_attribute_selector(&mut self) -> Result<SimpleSelector, HxmlError> {
+        self.skip_whitespaces();
+        let name = self.parse_ident()?;
+        self.skip_whitespaces();
+
+        let operator = match self.peek() {
+            Some(']') => {
+                self.index += 1;
+                return Ok(SimpleSelector::Attribute { name, matcher: None });
+            }
+            Some('=') => {
+                self.index += 1;
+                AttributeOperator::Equal
+            }
+            Some(c) if self.peek_at(1) == Some('=') => {
+                let operator = match c {
+                    '~' => AttributeOperator::Includes,
+                    '|' => AttributeOperator::DashMatch,
+                    '^' => AttributeOperator::Prefix,
+                    '$' => AttributeOperator::Suffix,
+                    '*' => AttributeOperator::Substring,
+                    _ => {
+                        return Err(self.error(&format!("unknown operator `{}=`", c)));
+                    }
+                };
+                self.index += 2;
+
+                operator
+            }
+            _ => {
+                return Err(self.error("expected an attribute operator or `]`"));
+            }
+        };
+
+        self.skip_whitespaces();
+
+        let value = match self.peek() {
+            Some('"') | Some('\'') => self.parse_string()?,
+            _ => self.parse_ident()?
+        };
+
+        self.skip_whitespaces();
+        self.expect(']')?;
+
+        Ok(SimpleSelector::Attribute { name, matcher: Some((operator, value)) })
+    }
+
+    // after ':'
+    fn parse_pseudo_class(&mut self) -> Result<PseudoClass, HxmlError> {
+
+        if self.peek() == Some(':') {
+            return Err(self.error("pseudo-elements are not supported"));
+        }
+
+        let name = self.parse_ident()?.to_ascii_lowercase();
+
+        let result = match name.as_str() {
+            "root" => PseudoClass::Root,
+            "empty" => PseudoClass::Empty,
+            "first-child" => PseudoClass::NthChild(0, 1),
+            "last-child" => PseudoClass::NthLastChild(0, 1),
+            "only-child" => PseudoClass::OnlyChild,
+            "first-of-type" => PseudoClass::NthOfType(0, 1),
+            "last-of-type" => PseudoClass::NthLastOfType(0, 1),
+            "only-of-type" => PseudoClass::OnlyOfType,
+            "nth-child" | "nth-last-child" | "nth-of-type" | "nth-last-of-type" => {
+                self.expect('(')?;
+                let (a, b) = self.parse_nth()?;
+                self.expect(')')?;
+
+                match name.as_str() {
+                    "nth-child" => PseudoClass::NthChild(a, b),
+                    "nth-last-child" => PseudoClass::NthLastChild(a, b),
+                    "nth-of-type" => PseudoClass::NthOfType(a, b),
+                    _ => PseudoClass::NthLastOfType(a, b),
+                }
+
+            }
+            "not" => {
+                self.expect('(')?;
+                self.skip_whitespaces();
+                let selector = self.parse_compound_selector()?;
+                self.skip_whitespaces();
+                self.expect(')')?;
+
+                PseudoClass::Not(Box::new(selector))
+            }
+            _ => {
+                return Err(self.error(&format!("unsupported pseudo-class `:{}`", name)));
+            }
+        };
+
+        Ok(result)
+    }
+
+    // https://www.w3.org/TR/css-syntax-3/#anb-microsyntax
+    // 'odd' | 'even' | integer | integer? 'n' (('+' | '-') integer)?
+    fn parse_nth(&mut self) -> Result<(i64, i64), HxmlError> {
+        let mut argument = String::new();
+
+        while let Some(c) = self.peek() {
+
+            if c == ')' {
+                break;
+            }
+
+            if !is_whitespace(c) {
+                argument.push(c.to_ascii_lowercase());
+            }
+
+            self.index += 1;
+        }
+
+        let parse_int = |s: &str| match s {
+            "" | "+" => Some(1),
+            "-" => Some(-1),
+            _ => s.parse::<i64>().ok()
+        };
+
+        let result = match argument.as_str() {
+            "odd" => Some((2, 1)),
+            "even" => Some((2, 0)),
+            _ => match argument.find('n') {
+                Some(n_index) => match (parse_int(&argument[..n_index]), &argument[(n_index + 1)..]) {
+                    (Some(a), "") => Some((a, 0)),
+                    (Some(a), b) if b.starts_with('+') || b.starts_with('-') => {
+                        b.parse::<i64>().ok().map(|b| (a, b))
+                    }
+                    _ => None
+                },
+                None => argument.parse::<i64>().ok().map(|b| (0, b))
+            }
+        };
+
+        match result {
+            Some(result) => Ok(result),
+            None => Err(self.error(&format!("invalid argument `{}`", argument)))
+        }
+
+    }
+
+    // https://www.w3.org/TR/css-syntax-3/#consume-name
+    // Escapes are expanded, so `svg\:rect` is `svg:rect`.
+    fn parse_ident(&mut self) -> Result<String, HxmlError> {
+
+        if !self.is_ident_start() {
+            return Err(self.error("expected an identifier"));
+        }
+
+        let mut result = String::new();
+
+        while let Some(c) = self.peek() {
+
+            if c == '\\' {
+                self.index += 1;
+                result.push(self.parse_escape()?);
+            }
+
+            else if is_ident_char(c) {
+                self.index += 1;
+                result.push(c);
+            }
+
+            else {
+                break;
+            }
+
+        }
+
+        Ok(result)
+    }
+
+    // https://www.w3.org/TR/css-syntax-3/#consume-string-token
+    fn parse_string(&mut self) -> Result<String, HxmlError> {
+        let quote = self.peek().unwrap();
+        let mut result = String::new();
+        self.index += 1;
+
+        loop {
+
+            match self.peek() {
+                Some(c) if c == quote => {
+                    self.index += 1;
+                    return Ok(result);
+                }
+                Some('\\') => {
+                    self.index += 1;
+
+                    // an escaped newline is ignored
+                    if self.peek() == Some('\n') {
+                        self.index += 1;
+                    }
+
+                    else {
+                        result.push(self.parse_escape()?);
+                    }
+
+                }
+                Some(c) => {
+                    self.index += 1;
+                    result.push(c);
+                }
+                None => {
+                    return Err(self.error("unterminated string"));
+                }
+            }
+
+        }
+
+    }
+
+    // after '\'
+    // 1 ~ 6 hex digits followed by an optional whitespace, or any other character
+    fn parse_escape(&mut self) -> Result<char, HxmlError> {
+        let mut hex = String::new();
+
+        while let Some(c) = self.peek() {
+
+            if hex.len() < 6 && c.is_ascii_hexdigit() {
+                hex.push(c);
+                self.index += 1;
+            }
+
+            else {
+                break;
+            }
+
+        }
+
+        if !hex.is_empty() {
+
+            match self.peek() {
+                Some(c) if is_whitespace(c) => {
+                    self.index += 1;
+                }
+                _ => {}
+            }
+
+            return Ok(
+                char::from_u32(u32::from_str_radix(&hex, 16).unwrap())
+                    .filter(|c| *c != '\0')
+                    .unwrap_or('\u{fffd}')
+            );
+        }
+
+        match self.peek() {
+            Some(c) => {
+                self.index += 1;
+                Ok(c)
+            }
+            None => Err(self.error("unexpected end of the selector"))
+        }
+
+    }
+
+    fn is_ident_start(&self) -> bool {
+
+        match self.peek() {
+            Some('-') => match self.peek_at(1) {
+                Some(c) => c == '\\' || c == '-' || is_ident_char(c) && !c.is_ascii_digit(),
+                None => false
+            },
+            Some('\\') => true,
+            Some(c) => is_ident_char(c) && !c.is_ascii_digit(),
+            None => false
+        }
+
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), HxmlError> {
+
+        if self.peek() == Some(c) {
+            self.index += 1;
+            Ok(())
+        }
+
+        else {
+            Err(self.error(&format!("expected `{}`", c)))
+        }
+
+    }
+
+    // it returns true if there were whitespaces
+    fn skip_whitespaces(&mut self) -> bool {
+        let begin_index = self.index;
+
+        while let Some(c) = self.peek() {
+
+            if !is_whitespace(c) {
+                break;
+            }
+
+            self.index += 1;
+        }
+
+        self.index > begin_index
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.index + offset).copied()
+    }
+
+    fn error(&self, message: &str) -> HxmlError {
+        HxmlError::new(format!("Invalid selector {:?} at {}: {}", self.selectors, self.index, message), usize::MAX)
+    }
+
+}
+
+// https://www.w3.org/TR/css-syntax-3/#ident-code-point
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '_' || c as u32 >= 0x80
+}
+
+fn is_whitespace(c: char) -> bool {
+    c == ' ' || c == '\t' || c == '\n' || c == '\r' || c == '\x0C'
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::selector::*;
+
+    #[test]
+    fn parse_test() {
+        let selectors = parse_selector_list("div > p.a#b, svg\\:rect[x^='1' ] ~ *:nth-child(-2n + 3):not(.c)").unwrap();
+
+        assert_eq!(selectors, vec![
+            ComplexSelector {
+                compounds: vec![
+                    CompoundSelector { tag_name: Some("div".to_string()), simple_selectors: vec![] },
+                    CompoundSelector {
+                        tag_name: Some("p".to_string()),
+                        simple_selectors: vec![SimpleSelector::Class("a".to_string()), SimpleSelector::Id("b".to_string())],
+                    },
+                ],
+                combinators: vec![Combinator::Child],
+            },
+            ComplexSelector {
+                compounds: vec![
+                    CompoundSelector {
+                        tag_name: Some("svg:rect".to_string()),
+                        simple_selectors: vec![SimpleSelector::Attribute {
+                            name: "x".to_string(),
+                            matcher: Some((AttributeOperator::Prefix, "1".to_string())),
+                        }],
+                    },
+                    CompoundSelector {
+                        tag_name: None,
+                        simple_selectors: vec![
+                            SimpleSelector::PseudoClass(PseudoClass::NthChild(-2, 3)),
+                            SimpleSelector::PseudoClass(PseudoClass::Not(Box::new(CompoundSelector {
+                                tag_name: None,
+                                simple_selectors: vec![SimpleSelector::Class("c".to_string())],
+                            }))),
+                        ],
+                    },
+                ],
+                combinators: vec![Combinator::SubsequentSibling],
+            },
+        ]);
+
+        for (nth, answer) in [("odd", (2, 1)), ("even", (2, 0)), ("3", (0, 3)), ("n", (1, 0)), ("-n+ 6", (-1, 6)), ("+5n-2", (5, -2))] {
+            assert_eq!(
+                parse_selector_list(&format!(":nth-of-type({})", nth)).unwrap()[0].compounds[0].simple_selectors[0],
+                SimpleSelector::PseudoClass(PseudoClass::NthOfType(answer.0, answer.1))
+            );
+        }
+
+        for invalid in ["", "a,", "a >", "a[b", "a[b=]", "a:foo", "a::before", ":nth-child(2n+)", "#1a", "a | b"] {
+            assert!(parse_selector_list(invalid).is_err(), "{}", invalid);
+        }
+
+    }
+
+}
@@ -0,0 +1,738 @@
+// Serializing Rust values into elements, with serde. It's behind the `serde` feature.
+// It's the reverse of the `de` module, with the same conventions:
+//   - A field `@name` is an attribute `name`, and `$text` is the text of the element.
+//   - A field `$value` is written as child elements named by their own types or variants (usually enums).
+//   - Other fields are child elements of their names. A sequence is repeated elements, and `None` is nothing.
+// The root element is named by `SerializeOptions::root`, or the name of the type (`#[serde(rename)]` applies).
+//
+// A value is first serialized into a `Value`, then checked and converted into a `Node`, and then allocated.
+// Nothing is allocated if it fails.
+
+use crate::err::HxmlError;
+use crate::node::attribute::Attribute;
+use crate::node::element::{Content, Element};
+use crate::node::pointer::ElementPtr;
+use crate::pretty::PrettyOptions;
+use crate::serialize::{check_chars, check_name};
+use crate::dom;
+use serde::ser::{self, Serialize};
+use std::fmt::Display;
+
+#[derive(Clone, Debug, Default)]
+pub struct SerializeOptions {
+
+    /// the name of the root element
+    /// If it's None, the name of the type is used: values that don't have names (eg. `Vec`, `i32`) need this.
+    pub root: Option<String>,
+
+    /// `to_string_with_options` writes the result with `ElementPtr::to_pretty_string` if it's set.
+    pub pretty: Option<PrettyOptions>,
+}
+
+/// It serializes `value` into an XML string. See the `ser` module for the mapping.
+pub fn to_string<T: Serialize>(value: &T) -> Result<String, HxmlError> {
+    to_string_with_options(value, &SerializeOptions::default())
+}
+
+/// It doesn't touch the current document: the elements are built, written and deleted.
+pub fn to_string_with_options<T: Serialize>(value: &T, options: &SerializeOptions) -> Result<String, HxmlError> {
+    let element = to_element_with_options(value, options)?;
+
+    let result = match &options.pretty {
+        Some(pretty_options) => Ok(element.to_pretty_string(pretty_options)),
+        None => element.to_xml()
+    };
+
+    dom::delete(element);
+
+    result
+}
+
+/// It serializes `value` into a new element, which doesn't have a parent.
+pub fn to_element<T: Serialize>(value: &T) -> Result<ElementPtr, HxmlError> {
+    to_element_with_options(value, &SerializeOptions::default())
+}
+
+/// `SerializeOptions::pretty` is ignored.
+pub fn to_element_with_options<T: Serialize>(value: &T, options: &SerializeOptions) -> Result<ElementPtr, HxmlError> {
+    let value = match value.serialize(ValueSerializer) {
+        Ok(value) => value,
+        Err(e) => {
+            return Err(HxmlError::new(e.0, usize::MAX));
+        }
+    };
+
+    let root = match (&options.root, &value) {
+        (Some(root), _) => root.to_string(),
+        (None, Value::Struct(Some(name), _)) | (None, Value::Variant(name, _)) => name.to_string(),
+        (None, _) => {
+            return Err(HxmlError::new(String::from("the value doesn't have a name: set `SerializeOptions::root`"), usize::MAX));
+        }
+    };
+
+    let node = match value {
+
+        // the variant is the root element, if it's not renamed
+        Value::Variant(name, inner) if options.root.is_none() => into_node(&name, *inner, "")?,
+        _ => into_node(&root, value, "")?,
+    };
+
+    Ok(allocate_node(node))
+}
+
+#[derive(Debug)]
+pub(crate) struct SeError(String);
+
+impl Display for SeError {
+
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "{}", self.0)
+    }
+
+}
+
+impl std::error::Error for SeError {}
+
+impl ser::Error for SeError {
+
+    fn custom<T: Display>(message: T) -> Self {
+        SeError(message.to_string())
+    }
+
+}
+
+// serde's data model, reduced to what matters for XML
+enum Value {
+
+    // `()`, `None` and unit structs
+    Unit,
+    Text(String),
+    Seq(Vec<Value>),
+
+    // structs and maps: (the name of the type, fields)
+    Struct(Option<String>, Vec<(String, Value)>),
+
+    // (the name of the variant, the value): a unit variant has `Value::Unit`
+    Variant(String, Box<Value>),
+}
+
+// an element that's checked, but not allocated yet
+struct Node {
+    tag_name: String,
+    attributes: Vec<Attribute>,
+    contents: Vec<NodeContent>,
+}
+
+enum NodeContent {
+    Element(Node),
+    Text(String),
+}
+
+fn allocate_node(node: Node) -> ElementPtr {
+    let contents = node.contents.into_iter().map(
+        |content| match content {
+            NodeContent::Element(child) => Content::Element(allocate_node(child)),
+            NodeContent::Text(text) => Content::CharData(text),
+        }
+    ).collect::<Vec<Content>>();
+
+    let empty_element = contents.is_empty();
+    let result = Element::new(node.tag_name, node.attributes, empty_element, contents);
+
+    for child in result.get_children().into_iter() {
+        child.set_parent(result);
+    }
+
+    result
+}
+
+// `path` is the path of the parent, for errors
+fn into_node(tag_name: &str, value: Value, path: &str) -> Result<Node, HxmlError> {
+    check_name(tag_name)?;
+
+    let path = format!("{}/{}", path, tag_name);
+    let mut result = Node {
+        tag_name: tag_name.to_string(),
+        attributes: vec![],
+        contents: vec![],
+    };
+
+    match value {
+        Value::Unit => {}
+        Value::Text(text) => {
+            check_chars(&text)?;
+            result.contents.push(NodeContent::Text(text));
+        }
+
+        // `<a>1 2 3</a>` or `<a><b/><c/></a>`
+        Value::Seq(items) => {
+
+            if items.iter().all(|item| matches!(item, Value::Text(_))) {
+                let text = into_text(Value::Seq(items), &path)?;
+                result.contents.push(NodeContent::Text(text));
+            }
+
+            else {
+                push_children(&mut result, items, &path)?;
+            }
+
+        }
+        Value::Struct(_, fields) => {
+
+            for (name, value) in fields.into_iter() {
+                push_field(&mut result, &name, value, &path)?;
+            }
+
+        }
+
+        // `<shape><circle r="1"/></shape>`, and `<level>info</level>` for a unit variant
+        Value::Variant(name, inner) => match *inner {
+            Value::Unit => {
+                result.contents.push(NodeContent::Text(name));
+            }
+            inner => {
+                result.contents.push(NodeContent::Element(into_node(&name, inner, &path)?));
+            }
+        },
+    }
+
+    Ok(result)
+}
+
+// child elements named by their own types or variants, for `$value` and sequences in elements
+fn push_children(node: &mut Node, items: Vec<Value>, path: &str) -> Result<(), HxmlError> {
+
+    for item in items.into_iter() {
+
+        match item {
+            Value::Unit => {}
+            Value::Text(text) => {
+                check_chars(&text)?;
+                node.contents.push(NodeContent::Text(text));
+            }
+            Value::Variant(name, inner) => {
+                node.contents.push(NodeContent::Element(into_node(&name, *inner, path)?));
+            }
+            Value::Struct(Some(name), fields) => {
+                node.contents.push(NodeContent::Element(into_node(&name, Value::Struct(None, fields), path)?));
+            }
+            Value::Seq(items) => {
+                push_children(node, items, path)?;
+            }
+            Value::Struct(None, _) => {
+                return Err(HxmlError::new(format!("a map in a sequence doesn't have a name at `{}`", path), usize::MAX));
+            }
+        }
+
+    }
+
+    Ok(())
+}
+
+fn push_field(node: &mut Node, name: &str, value: Value, path: &str) -> Result<(), HxmlError> {
+
+    if let Some(attribute) = name.strip_prefix('@') {
+        check_name(attribute)?;
+
+        match value {
+            Value::Unit => {}
+            value => {
+                let value = into_text(value, &format!("{}/{}", path, name))?;
+                node.attributes.push(Attribute::new(attribute.to_string(), value));
+            }
+        }
+
+    }
+
+    else if name == "$text" {
+
+        match value {
+            Value::Unit => {}
+            value => {
+                let text = into_text(value, &format!("{}/text()", path))?;
+                node.contents.push(NodeContent::Text(text));
+            }
+        }
+
+    }
+
+    else if name == "$value" {
+
+        match value {
+            Value::Seq(items) => {
+                push_children(node, items, path)?;
+            }
+            value => {
+                push_children(node, vec![value], path)?;
+            }
+        }
+
+    }
+
+    else {
+
+        match value {
+            Value::Unit => {}
+            Value::Seq(items) => {
+
+                for item in items.into_iter() {
+                    node.contents.push(NodeContent::Element(into_node(name, item, path)?));
+                }
+
+            }
+            value => {
+                node.contents.push(NodeContent::Element(into_node(name, value, path)?));
+            }
+        }
+
+    }
+
+    Ok(())
+}
+
+// texts, unit variants, and sequences of them separated by spaces
+fn into_text(value: Value, path: &str) -> Result<String, HxmlError> {
+    let result = match value {
+        Value::Text(text) => text,
+        Value::Variant(name, inner) if matches!(*inner, Value::Unit) => name,
+        Value::Seq(items) => {
+            let mut texts = vec![];
+
+            for item in items.into_iter() {
+                texts.push(into_text(item, path)?);
+            }
+
+            texts.join(" ")
+        }
+        _ => {
+            return Err(HxmlError::new(format!("expected a text, found an element at `{}`", path), usize::MAX));
+        }
+    };
+
+    check_chars(&result)?;
+
+    Ok(result)
+}
+
+struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = SeError;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = StructSerializer;
+    type SerializeStructVariant = StructSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, SeError> {
+        Ok(Value::Text(v.to_string()))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, SeError> {
+        Ok(Value::Text(v.to_string()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, SeError> {
+        Ok(Value::Text(v.to_string()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, SeError> {
+        Ok(Value::Text(v.to_string()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, SeError> {
+        Ok(Value::Text(v.to_string()))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Value, SeError> {
+        Ok(Value::Text(v.to_string()))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, SeError> {
+        Ok(Value::Text(v.to_string()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, SeError> {
+        Ok(Value::Text(v.to_string()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, SeError> {
+        Ok(Value::Text(v.to_string()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, SeError> {
+        Ok(Value::Text(v.to_string()))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Value, SeError> {
+        Ok(Value::Text(v.to_string()))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, SeError> {
+        Ok(Value::Text(v.to_string()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, SeError> {
+        Ok(Value::Text(v.to_string()))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, SeError> {
+        Ok(Value::Text(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, SeError> {
+        Ok(Value::Text(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, SeError> {
+
+        match String::from_utf8(v.to_vec()) {
+            Ok(s) => Ok(Value::Text(s)),
+            Err(_) => Err(SeError(String::from("bytes must be valid UTF-8"))),
+        }
+
+    }
+
+    fn serialize_none(self) -> Result<Value, SeError> {
+        Ok(Value::Unit)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Value, SeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, SeError> {
+        Ok(Value::Unit)
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Value, SeError> {
+        Ok(Value::Struct(Some(name.to_string()), vec![]))
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<Value, SeError> {
+        Ok(Value::Variant(variant.to_string(), Box::new(Value::Unit)))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, _name: &'static str, value: &T) -> Result<Value, SeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self, _name: &'static str, _index: u32, variant: &'static str, value: &T) -> Result<Value, SeError> {
+        Ok(Value::Variant(variant.to_string(), Box::new(value.serialize(self)?)))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, SeError> {
+        Ok(SeqSerializer { variant: None, items: Vec::with_capacity(len.unwrap_or(0)) })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, SeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SeqSerializer, SeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _index: u32, variant: &'static str, len: usize) -> Result<SeqSerializer, SeError> {
+        Ok(SeqSerializer { variant: Some(variant.to_string()), items: Vec::with_capacity(len) })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer, SeError> {
+        Ok(MapSerializer { fields: vec![], key: None })
+    }
+
+    fn serialize_struct(self, name: &'static str, _len: usize) -> Result<StructSerializer, SeError> {
+        Ok(StructSerializer { name: name.to_string(), is_variant: false, fields: vec![] })
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _index: u32, variant: &'static str, _len: usize) -> Result<StructSerializer, SeError> {
+        Ok(StructSerializer { name: variant.to_string(), is_variant: true, fields: vec![] })
+    }
+}
+
+struct SeqSerializer {
+
+    // for tuple variants
+    variant: Option<String>,
+    items: Vec<Value>,
+}
+
+impl SeqSerializer {
+
+    fn push<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), SeError> {
+        self.items.push(value.serialize(ValueSerializer)?);
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, SeError> {
+
+        match self.variant {
+            Some(variant) => Ok(Value::Variant(variant, Box::new(Value::Seq(self.items)))),
+            None => Ok(Value::Seq(self.items)),
+        }
+
+    }
+
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Value;
+    type Error = SeError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), SeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, SeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Value;
+    type Error = SeError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), SeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, SeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Value;
+    type Error = SeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), SeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, SeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer {
+    type Ok = Value;
+    type Error = SeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), SeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, SeError> {
+        self.finish()
+    }
+}
+
+// Keys are written as tag names, so they must be texts.
+struct MapSerializer {
+    fields: Vec<(String, Value)>,
+    key: Option<String>,
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Value;
+    type Error = SeError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), SeError> {
+
+        match key.serialize(ValueSerializer)? {
+            Value::Text(key) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => Err(SeError(String::from("a key of a map must be a text"))),
+        }
+
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), SeError> {
+
+        match self.key.take() {
+            Some(key) => {
+                self.fields.push((key, value.serialize(ValueSerializer)?));
+                Ok(())
+            }
+            None => Err(SeError(String::from("a value is serialized before its key"))),
+        }
+
+    }
+
+    fn end(self) -> Result<Value, SeError> {
+        Ok(Value::Struct(None, self.fields))
+    }
+}
+
+struct StructSerializer {
+
+    // the name of the type, or the variant
+    name: String,
+    is_variant: bool,
+    fields: Vec<(String, Value)>,
+}
+
+impl StructSerializer {
+
+    fn push<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<(), SeError> {
+        self.fields.push((key.to_string(), value.serialize(ValueSerializer)?));
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, SeError> {
+
+        if self.is_variant {
+            Ok(Value::Variant(self.name, Box::new(Value::Struct(None, self.fields))))
+        }
+
+        else {
+            Ok(Value::Struct(Some(self.name), self.fields))
+        }
+
+    }
+
+}
+
+impl ser::SerializeStruct for StructSerializer {
+    type Ok = Value;
+    type Error = SeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<(), SeError> {
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<Value, SeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for StructSerializer {
+    type Ok = Value;
+    type Error = SeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<(), SeError> {
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<Value, SeError> {
+        self.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{dom, from_str, into_dom, to_element, to_string, to_string_with_options, PrettyOptions, SerializeOptions};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    #[serde(rename = "config")]
+    struct Config {
+        #[serde(rename = "@version")]
+        version: u32,
+        #[serde(rename = "@tags")]
+        tags: Vec<String>,
+        debug: Option<bool>,
+        #[serde(rename = "server")]
+        servers: Vec<Server>,
+        level: Level,
+        shape: Shape,
+        #[serde(rename = "$value")]
+        blocks: Vec<Block>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Server {
+        #[serde(rename = "@host")]
+        host: String,
+        port: u16,
+        #[serde(rename = "$text")]
+        description: String,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Level {
+        Debug,
+        Info,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Shape {
+        Circle { #[serde(rename = "@r")] r: f64 },
+        Point,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Block {
+        P(String),
+        Hr,
+    }
+
+    #[test]
+    fn serialize_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        into_dom("<doc/>".to_string()).unwrap();
+
+        let config = Config {
+            version: 2,
+            tags: vec!["x".to_string(), "y".to_string()],
+            debug: None,
+            servers: vec![
+                Server { host: "a&b".to_string(), port: 80, description: "<main>".to_string() },
+                Server { host: "c".to_string(), port: 8080, description: String::new() },
+            ],
+            level: Level::Info,
+            shape: Shape::Circle { r: 1.5 },
+            blocks: vec![Block::P("text".to_string()), Block::Hr],
+        };
+
+        let xml = to_string(&config).unwrap();
+        assert_eq!(
+            xml,
+            concat!(
+                "<config version=\"2\" tags=\"x y\">",
+                "<server host=\"a&amp;b\"><port>80</port>&lt;main></server>",
+                "<server host=\"c\"><port>8080</port></server>",
+                "<level>info</level><shape><circle r=\"1.5\"/></shape><p>text</p><hr/></config>",
+            )
+        );
+
+        // round trip, and the document is not affected
+        assert_eq!(from_str::<Config>(&xml).unwrap(), config);
+        assert_eq!(dom::to_string(), "<doc/>");
+        dom::check_indices().unwrap();
+
+        let pretty = to_string_with_options(
+            &config.servers,
+            &SerializeOptions { root: Some("servers".to_string()), pretty: Some(PrettyOptions::default()) }
+        ).unwrap();
+        assert_eq!(
+            pretty,
+            "<servers>\n  <Server host=\"a&amp;b\"><port>80</port>&lt;main></Server>\n  <Server host=\"c\">\n    <port>8080</port>\n  </Server>\n</servers>"
+        );
+
+        let element = to_element(&Shape::Point).unwrap();
+        assert_eq!(element.to_string(), "<point/>");
+        assert_eq!(element.get_parent(), None);
+        dom::delete(element);
+
+        // errors
+        assert!(to_string(&vec![1, 2]).is_err());
+        assert!(to_string_with_options(&1, &SerializeOptions { root: Some("1".to_string()), pretty: None }).is_err());
+        assert!(to_string_with_options(&"\u{1}", &SerializeOptions { root: Some("a".to_string()), pretty: None }).is_err());
+        assert_eq!(dom::get_all_elements().len(), 1);
+
+        drop(lock);
+    }
+
+}
@@ -0,0 +1,436 @@
+use crate::err::HxmlError;
+use crate::html::{is_boolean_attribute, is_raw_text_element, is_void_element};
+use crate::node::element::{Content, Element};
+use crate::node::memory;
+use crate::parse::{expand_reference, get_entity_reference_end_index, get_name_end_index, get_reference_end_index};
+use crate::predicate::is_valid_char;
+use crate::utils::into_v16;
+use std::io::Write;
+
+// If `strict` is set, it refuses to write things that cannot be parsed back into the same tree:
+// invalid names, comments with `--`, invalid references and characters that XML doesn't allow.
+// Otherwise, it writes them as they are.
+// It streams the output to `w`: no intermediate strings are built for the subtrees.
+pub fn write_element<W: Write>(element: &Element, strict: bool, w: &mut W) -> Result<(), HxmlError> {
+
+    if strict {
+        check_name(&element.tag_name)?;
+    }
+
+    match &element.source_start_tag {
+        // `ParseOptions::preserve_format`
+        Some(start_tag) => {
+
+            if !element.empty_element {
+                w.write_all(start_tag.as_bytes())?;
+            }
+
+            else if element.contents.is_empty() {
+                w.write_all(start_tag.as_bytes())?;
+                return Ok(());
+            }
+
+            // `<a/>` that has contents now
+            else {
+                w.write_all(start_tag[..(start_tag.len() - 2)].trim_end().as_bytes())?;
+                w.write_all(b">")?;
+            }
+
+        }
+        None => {
+            write!(w, "<{}", element.tag_name)?;
+
+            for (name, value) in get_attributes(element).into_iter() {
+
+                if strict {
+                    check_name(&name)?;
+                    check_chars(&value)?;
+                }
+
+                let quote = pick_quote(&value);
+                write!(w, " {}={}{}{}", name, quote, escape_attribute_value(&value, quote), quote)?;
+            }
+
+            if element.empty_element && element.contents.is_empty() {
+                w.write_all(b"/>")?;
+                return Ok(());
+            }
+
+            w.write_all(b">")?;
+        }
+    }
+
+    for content in element.contents.iter() {
+
+        match content {
+            // `ParseOptions::preserve_format` is meant to be used without the end-of-line normalization,
+            // so `\r` of such an element is written as it was.
+            Content::CharData(char_data) if element.source_start_tag.is_some() => {
+
+                if strict {
+                    check_chars(char_data)?;
+                }
+
+                w.write_all(escape_char_data_impl(char_data, false).as_bytes())?;
+            }
+            _ => {
+                write_content(content, strict, w)?;
+            }
+        }
+
+    }
+
+    match &element.source_end_tag {
+        Some(end_tag) => {
+            w.write_all(end_tag.as_bytes())?;
+        }
+        None => {
+            write!(w, "</{}>", element.tag_name)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn write_content<W: Write>(content: &Content, strict: bool, w: &mut W) -> Result<(), HxmlError> {
+
+    match content {
+        Content::Element(pointer) => {
+            write_element(memory::get(pointer.ptr), strict, w)?;
+        },
+        Content::CharData(char_data) => {
+
+            if strict {
+                check_chars(char_data)?;
+            }
+
+            w.write_all(escape_char_data(char_data).as_bytes())?;
+        },
+        Content::CDSect(cd_sect) => {
+
+            if strict {
+                check_chars(cd_sect)?;
+            }
+
+            write!(w, "<![CDATA[{}]]>", cd_sect.replace("]]>", "]]]]><![CDATA[>"))?;
+        },
+        Content::Comment(comment) => {
+
+            if strict && (comment.contains("--") || comment.ends_with('-')) {
+                return Err(HxmlError::new(format!("A comment cannot contain `--` or end with `-`: {:?}", comment), usize::MAX));
+            }
+
+            if strict {
+                check_chars(comment)?;
+            }
+
+            write!(w, "<!--{}-->", comment)?;
+        },
+        Content::Reference(reference) => {
+            let reference_v16 = into_v16(&format!("&{};", reference));
+
+            if strict && get_reference_end_index(&reference_v16, 0) != Some(reference_v16.len() - 1) {
+                return Err(HxmlError::new(format!("Invalid reference: &{};", reference), usize::MAX));
+            }
+
+            write!(w, "&{};", reference)?;
+        }
+    }
+
+    Ok(())
+}
+
+// HTML syntax
+// - void elements are written without an end tag (`<br>`), and their contents are dropped
+// - other elements are never self-closed (`<div></div>`)
+// - boolean attributes whose values are empty or the same as their names are minimized (`<input checked>`)
+// - contents of `script` and `style` are not escaped
+// - CDATA sections are written as escaped text, because HTML doesn't have them (except in `script` and `style`)
+pub fn write_html_element<W: Write>(element: &Element, w: &mut W) -> Result<(), HxmlError> {
+    write!(w, "<{}", element.tag_name)?;
+
+    for (name, value) in get_attributes(element).into_iter() {
+
+        if is_boolean_attribute(&name) && (value.is_empty() || value.eq_ignore_ascii_case(&name)) {
+            write!(w, " {}", name)?;
+        }
+
+        else {
+            let quote = pick_quote(&value);
+            write!(w, " {}={}{}{}", name, quote, escape_attribute_value(&value, quote), quote)?;
+        }
+
+    }
+
+    w.write_all(b">")?;
+
+    if is_void_element(&element.tag_name) {
+        return Ok(());
+    }
+
+    let is_raw_text = is_raw_text_element(&element.tag_name);
+
+    // the HTML parser ends a raw text element at the first `</script` (or `</style`), whatever follows it
+    if is_raw_text {
+        let end_tag = format!("</{}", element.tag_name.to_ascii_lowercase());
+        let mut raw_text = String::new();
+
+        for content in element.contents.iter() {
+
+            match content {
+                Content::CharData(text) | Content::CDSect(text) => {
+                    raw_text.push_str(text);
+                }
+                Content::Reference(reference) => {
+                    raw_text.push_str(&expand_raw_reference(reference));
+                }
+                _ => {}
+            }
+
+        }
+
+        if raw_text.to_ascii_lowercase().contains(&end_tag) {
+            return Err(HxmlError::new(format!("<{}> cannot contain `{}` in HTML", element.tag_name, end_tag), usize::MAX));
+        }
+
+    }
+
+    // the HTML parser ignores a newline right after `<pre>` and `<textarea>`
+    match element.contents.first() {
+        Some(Content::CharData(char_data)) if char_data.starts_with('\n') && ["pre", "textarea", "listing"].contains(&element.tag_name.to_ascii_lowercase().as_str()) => {
+            w.write_all(b"\n")?;
+        }
+        _ => {}
+    }
+
+    for content in element.contents.iter() {
+
+        match content {
+            Content::Element(pointer) => {
+                write_html_element(memory::get(pointer.ptr), w)?;
+            }
+            Content::CharData(text) | Content::CDSect(text) if is_raw_text => {
+                w.write_all(text.as_bytes())?;
+            }
+            Content::Reference(reference) if is_raw_text => {
+                w.write_all(expand_raw_reference(reference).as_bytes())?;
+            }
+            Content::CDSect(cd_sect) => {
+                w.write_all(escape_char_data(cd_sect).as_bytes())?;
+            }
+            _ => {
+                write_content(content, false, w)?;
+            }
+        }
+
+    }
+
+    write!(w, "</{}>", element.tag_name)?;
+
+    Ok(())
+}
+
+// References in raw text elements are not parsed by HTML, so they're expanded.
+// The ones that cannot be expanded (eg. `&nbsp;`) are written as they are.
+fn expand_raw_reference(reference: &str) -> String {
+
+    match expand_reference(&into_v16(&format!("&{};", reference))) {
+        Some(expanded) => String::from_utf16_lossy(&expanded),
+        None => format!("&{};", reference)
+    }
+
+}
+
+// name="value"
+pub fn write_attribute(name: &str, value: &str, buffer: &mut String) {
+    let quote = pick_quote(value);
+    buffer.push_str(name);
+    buffer.push('=');
+    buffer.push(quote);
+    buffer.push_str(&escape_attribute_value(value, quote));
+    buffer.push(quote);
+}
+
+// (name, value), in the original order
+pub fn get_attributes(element: &Element) -> Vec<(String, String)> {
+    element.attributes.iter().map(
+        |att| (att.name.clone(), att.value.clone())
+    ).collect()
+}
+
+// it uses single quotes only if the value has `"` but not `'`
+pub fn pick_quote(value: &str) -> char {
+
+    if value.contains('"') && !value.contains('\'') {
+        '\''
+    }
+
+    else {
+        '"'
+    }
+
+}
+
+// `>` is escaped only when it follows `]]`, because `]]>` is not allowed in char data.
+// `\r` is always escaped, otherwise the end-of-line normalization would turn it into `\n` when it's parsed again.
+// It doesn't depend on the options of the last parse: the same tree is always written the same way.
+pub fn escape_char_data(char_data: &str) -> String {
+    escape_char_data_impl(char_data, true)
+}
+
+fn escape_char_data_impl(char_data: &str, escape_cr: bool) -> String {
+    let mut result = String::with_capacity(char_data.len());
+
+    for c in char_data.chars() {
+
+        match c {
+            '<' => { result.push_str("&lt;"); },
+            '&' => { result.push_str("&amp;"); },
+            '>' if result.ends_with("]]") => { result.push_str("&gt;"); },
+            '\r' if escape_cr => { result.push_str("&#xD;"); },
+            _ => { result.push(c); }
+        }
+
+    }
+
+    result
+}
+
+// It escapes `value` so that it can be written inside `quote`.
+// Whitespace characters other than spaces are written as char references, otherwise the attribute-value normalization would turn them into spaces.
+// `&` followed by a reference to an entity that is not predefined is left as it is: the parser doesn't expand such references.
+pub fn escape_attribute_value(value: &str, quote: char) -> String {
+    let value_v16 = into_v16(value);
+    let mut result = String::with_capacity(value.len());
+    let mut index = 0;
+
+    for c in value.chars() {
+
+        match c {
+            '<' => { result.push_str("&lt;"); },
+            '"' if quote == '"' => { result.push_str("&quot;"); },
+            '\'' if quote == '\'' => { result.push_str("&apos;"); },
+            '\t' => { result.push_str("&#x9;"); },
+            '\n' => { result.push_str("&#xA;"); },
+            '\r' => { result.push_str("&#xD;"); },
+            '&' => match get_entity_reference_end_index(&value_v16, index) {
+                Some(end_index) if expand_reference(&value_v16[index..(end_index + 1)]).is_none() => {
+                    result.push(c);
+                },
+                _ => { result.push_str("&amp;"); }
+            },
+            _ => { result.push(c); }
+        }
+
+        index += c.len_utf16();
+    }
+
+    result
+}
+
+pub fn check_name(name: &str) -> Result<(), HxmlError> {
+    let name_v16 = into_v16(name);
+
+    if !name_v16.is_empty() && get_name_end_index(&name_v16, 0) == Some(name_v16.len() - 1) {
+        Ok(())
+    }
+
+    else {
+        Err(HxmlError::new(format!("Invalid name: {:?}", name), usize::MAX))
+    }
+
+}
+
+pub fn check_chars(s: &str) -> Result<(), HxmlError> {
+
+    for c in s.encode_utf16() {
+
+        // surrogates are fine: they're parts of characters beyond U+FFFF
+        if !is_valid_char(&c) && !(0xd800..=0xdfff).contains(&c) {
+            return Err(HxmlError::new(format!("U+{:04X} is not allowed in XML: {:?}", c, s), usize::MAX));
+        }
+
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dom;
+    use crate::serialize::*;
+
+    #[test]
+    fn escape_test() {
+        assert_eq!(escape_char_data("a < b && c > d ]]> e\r\n"), "a &lt; b &amp;&amp; c > d ]]&gt; e&#xD;\n");
+        assert_eq!(escape_attribute_value("a \"quoted\" <x> & y", '"'), "a &quot;quoted&quot; &lt;x> &amp; y");
+        assert_eq!(escape_attribute_value("a \"quoted\" 'x'", '\''), "a \"quoted\" &apos;x&apos;");
+        assert_eq!(escape_attribute_value("a\nb\tc", '"'), "a&#xA;b&#x9;c");
+        assert_eq!(escape_attribute_value("&nbsp;&amp;", '"'), "&nbsp;&amp;amp;");
+        assert_eq!(escape_attribute_value("😀&", '"'), "😀&amp;");
+        assert_eq!(pick_quote("a \"quoted\" <x>"), '\'');
+        assert_eq!(pick_quote("\"a\" 'b'"), '"');
+    }
+
+    #[test]
+    fn html_test() {
+        let lock = crate::dom::LOCK.lock().unwrap();
+
+        let document = "<?xml version='1.0'?><!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Strict//EN\" \"http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd\"><html><head><script src=\"a.js\"/><script><![CDATA[if (a < b && c) {}]]></script><style>p > a { }</style></head><body><br/><img src=\"a.png\" alt=\"\"/><input type=\"checkbox\" checked=\"checked\" disabled=\"\" value=\"\"/><div/><p>1 &lt; 2 <![CDATA[<b>]]></p><pre>
+
+x</pre></body></html>";
+
+        crate::into_dom(document.to_string()).unwrap();
+        assert_eq!(
+            crate::dom::to_html().unwrap(),
+            "<!DOCTYPE html><html><head><script src=\"a.js\"></script><script>if (a < b && c) {}</script><style>p > a { }</style></head><body><br><img src=\"a.png\" alt=\"\"><input type=\"checkbox\" checked disabled value=\"\"><div></div><p>1 &lt; 2 &lt;b></p><pre>
+
+\nx</pre></body></html>"
+        );
+
+        // the XML serializer is not affected
+        assert!(crate::dom::to_string().contains("<br/>"));
+
+        // references in raw text elements are expanded
+        crate::into_dom("<html><script>if (a &lt; b &amp;&amp; c) {} &#x3E; &nbsp;</script></html>".to_string()).unwrap();
+        assert_eq!(crate::dom::to_html().unwrap(), "<html><script>if (a < b && c) {} > &nbsp;</script></html>");
+
+        // a raw text element cannot be closed early
+        crate::into_dom("<html><script>a = \"&lt;/SCRIPT>\";</script></html>".to_string()).unwrap();
+        assert!(crate::dom::to_html().is_err());
+        crate::into_dom("<html><style><![CDATA[</style>]]></style></html>".to_string()).unwrap();
+        assert!(crate::dom::get_root().to_html().is_err());
+
+        drop(lock);
+    }
+
+    #[test]
+    fn serializer_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        crate::into_dom("<a><b>&lt;&#123;&nbsp;</b></a>".to_string()).unwrap();
+        let root = dom::get_root();
+        root.set_attribute("title".to_string(), "a \"quoted\" <x>".to_string());
+        root.set_attribute("alt".to_string(), "\"a\" & 'b'\n".to_string());
+        root.add_contents(vec![crate::Content::new_char_data("1 < 2 && ]]> \r\n".to_string())]);
+        root.add_contents(vec![
+            crate::Content::new_cd_sect("]]>]]>".to_string()),
+            crate::Content::new_comment(" comment ".to_string()),
+        ]);
+
+        let xml = root.to_xml().unwrap();
+        assert_eq!(xml, "<a title='a \"quoted\" &lt;x>' alt=\"&quot;a&quot; &amp; 'b'&#xA;\"><b>&lt;&#123;&nbsp;</b>1 &lt; 2 &amp;&amp; ]]&gt; &#xD;\n<![CDATA[]]]]><![CDATA[>]]]]><![CDATA[>]]><!-- comment --></a>");
+
+        let reparsed = crate::Element::from_string(xml).unwrap();
+        assert!(root.is_same_tree(reparsed));
+        assert_eq!(reparsed.get_attribute("title".to_string()), Some("a \"quoted\" <x>".to_string()));
+
+        root.add_contents(vec![crate::Content::new_comment("a -- b".to_string())]);
+        assert!(root.to_xml().is_err());
+        assert!(root.to_string().ends_with("<!--a -- b--></a>"));
+        assert!(!root.is_same_tree(reparsed));
+
+        drop(lock);
+    }
+
+}
@@ -107,9 +107,7 @@ fn xml_dom_test() {
         )
     ).collect();
 
-    let lock = unsafe {
-        dom::LOCK.lock().unwrap()
-    };
+    let lock = dom::LOCK.lock().unwrap();
 
     for (xml, tags, ids) in testcases.clone().into_iter() {
         parse_valid_xml(xml, tags, ids);
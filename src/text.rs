@@ -0,0 +1,301 @@
+use crate::html::{is_block_element, is_hidden_element};
+use crate::node::element::{Content, Element};
+use crate::node::memory;
+use crate::parse::expand_reference;
+use crate::utils::{from_v16, into_v16};
+
+// https://dom.spec.whatwg.org/#dom-node-textcontent
+// Char data and CDATA sections of the descendants, in document order. Comments are skipped.
+// References to predefined entities and characters are expanded, and the others (eg. `&nbsp;`) are written as they are.
+pub fn get_text_content(element: &Element) -> String {
+    let mut result = String::new();
+    push_text_content(element, &mut result);
+
+    result
+}
+
+fn push_text_content(element: &Element, buffer: &mut String) {
+
+    for content in element.contents.iter() {
+
+        match content {
+            Content::Element(child) => {
+                push_text_content(memory::get(child.ptr), buffer);
+            }
+            Content::CharData(s) | Content::CDSect(s) => {
+                buffer.push_str(s);
+            }
+            Content::Reference(r) => {
+                buffer.push_str(&expand_reference_string(r));
+            }
+            Content::Comment(_) => {}
+        }
+
+    }
+
+}
+
+// Char data, CDATA sections and references of the element itself, without the descendants.
+pub fn get_own_text(element: &Element) -> String {
+    let mut result = String::new();
+
+    for content in element.contents.iter() {
+
+        match content {
+            Content::CharData(s) | Content::CDSect(s) => {
+                result.push_str(s);
+            }
+            Content::Reference(r) => {
+                result.push_str(&expand_reference_string(r));
+            }
+            Content::Element(_) | Content::Comment(_) => {}
+        }
+
+    }
+
+    result
+}
+
+fn expand_reference_string(reference: &str) -> String {
+
+    match expand_reference(&into_v16(&format!("&{};", reference))) {
+        Some(expanded) => from_v16(&expanded),
+        None => format!("&{};", reference)
+    }
+
+}
+
+enum Item {
+    Text(String),
+
+    // the number of line breaks required between the texts around it
+    // They're collapsed with adjacent ones, and dropped at the start and the end.
+    RequiredLineBreaks(usize),
+
+    // a text that is written as it is (`<br>`, `<pre>`, tabs between table cells)
+    Preserved(String),
+}
+
+// https://html.spec.whatwg.org/multipage/dom.html#the-innertext-idl-attribute
+// It approximates the rendered text without CSS: block elements are on their own lines (`<p>` has a blank line around it),
+// `<br>` is a line break, cells in a row are separated by tabs, hidden elements (`<script>`, `<style>`...) are skipped,
+// and whitespaces are collapsed except in `<pre>`, `<textarea>` and `<listing>`.
+pub fn get_inner_text(element: &Element) -> String {
+    let mut items = vec![];
+    push_inner_text_items(element, false, &mut items);
+
+    let mut result = String::new();
+    let mut line_breaks = 0;
+    let mut is_line_start = true;
+
+    for item in items.into_iter() {
+
+        let (text, is_preserved) = match item {
+            Item::RequiredLineBreaks(n) => {
+                line_breaks = line_breaks.max(n);
+                continue;
+            }
+            Item::Text(s) => (s, false),
+            Item::Preserved(s) => (s, true),
+        };
+
+        // spaces at the start of a line or after a space are not rendered
+        let text = if !is_preserved && (line_breaks > 0 || is_line_start || result.ends_with(' ') || result.ends_with('\t')) {
+            text.trim_start_matches(' ').to_string()
+        } else {
+            text
+        };
+
+        if text.is_empty() {
+            continue;
+        }
+
+        if line_breaks > 0 && !result.is_empty() {
+            trim_end_spaces(&mut result);
+            result.push_str(&"\n".repeat(line_breaks));
+        }
+
+        line_breaks = 0;
+
+        if is_preserved && text.starts_with('\n') {
+            trim_end_spaces(&mut result);
+        }
+
+        result.push_str(&text);
+        is_line_start = text.ends_with('\n');
+    }
+
+    trim_end_spaces(&mut result);
+
+    result
+}
+
+fn push_inner_text_items(element: &Element, is_preformatted: bool, items: &mut Vec<Item>) {
+
+    // cells are separated by tabs, except after the last one in the row
+    let last_cell = element.contents.iter().rev().find_map(|content| match content {
+        Content::Element(child) if is_cell(&child.get_tag_name().to_ascii_lowercase()) => Some(*child),
+        _ => None
+    });
+
+    for content in element.contents.iter() {
+
+        match content {
+            Content::Element(child) => {
+                let child = memory::get(child.ptr);
+                let tag_name = child.tag_name.to_ascii_lowercase();
+
+                if is_hidden_element(&tag_name) {
+                    continue;
+                }
+
+                match tag_name.as_str() {
+                    "br" => {
+                        items.push(Item::Preserved(String::from("\n")));
+                        continue;
+                    }
+                    "p" => {
+                        items.push(Item::RequiredLineBreaks(2));
+                    }
+
+                    // cells are separated by tabs
+                    "td" | "th" => {}
+                    _ if is_block_element(&tag_name) => {
+                        items.push(Item::RequiredLineBreaks(1));
+                    }
+                    _ => {}
+                }
+
+                let is_preformatted = is_preformatted || tag_name == "pre" || tag_name == "textarea" || tag_name == "listing";
+                push_inner_text_items(child, is_preformatted, items);
+
+                match tag_name.as_str() {
+                    "p" => {
+                        items.push(Item::RequiredLineBreaks(2));
+                    }
+                    "td" | "th" if last_cell != Some(child.pointer) => {
+                        items.push(Item::Preserved(String::from("\t")));
+                    }
+                    "td" | "th" => {}
+                    _ if is_block_element(&tag_name) => {
+                        items.push(Item::RequiredLineBreaks(1));
+                    }
+                    _ => {}
+                }
+
+            }
+            Content::CharData(s) | Content::CDSect(s) => {
+                items.push(text_item(s.to_string(), is_preformatted));
+            }
+            Content::Reference(r) => {
+                items.push(text_item(expand_reference_string(r), is_preformatted));
+            }
+            Content::Comment(_) => {}
+        }
+
+    }
+
+}
+
+fn is_cell(tag_name: &str) -> bool {
+    tag_name == "td" || tag_name == "th"
+}
+
+fn text_item(text: String, is_preformatted: bool) -> Item {
+
+    if is_preformatted {
+        Item::Preserved(text)
+    }
+
+    else {
+        Item::Text(collapse_whitespaces(&text))
+    }
+
+}
+
+// https://infra.spec.whatwg.org/#ascii-whitespace
+fn collapse_whitespaces(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut is_after_space = false;
+
+    for c in text.chars() {
+
+        if c == ' ' || c == '\t' || c == '\n' || c == '\r' || c == '\x0C' {
+
+            if !is_after_space {
+                result.push(' ');
+            }
+
+            is_after_space = true;
+        }
+
+        else {
+            result.push(c);
+            is_after_space = false;
+        }
+
+    }
+
+    result
+}
+
+fn trim_end_spaces(s: &mut String) {
+
+    while s.ends_with(' ') {
+        s.pop();
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{dom, into_dom, Content, Element};
+
+    #[test]
+    fn text_content_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        into_dom("<a>1 &lt; 2<!-- no --><b><![CDATA[ & 3]]>&#x34;</b>&nbsp;</a>".to_string()).unwrap();
+        let a = dom::get_root();
+        assert_eq!(a.text_content(), "1 < 2 & 34&nbsp;");
+
+        // invalid references that are added by hand are written as they are
+        let c = Element::new("c".to_string(), vec![], false, vec![Content::Reference("#".to_string())]);
+        assert_eq!(c.text_content(), "&#;");
+        dom::delete(c);
+
+        a.set_text_content("x < y".to_string());
+        assert_eq!(a.to_string(), "<a>x &lt; y</a>");
+        assert_eq!(dom::get_elements_by_tag_name(None, "b".to_string()), vec![]);
+        dom::check_indices().unwrap();
+
+        a.set_text_content(String::new());
+        assert_eq!(a.get_contents().len(), 0);
+
+        drop(lock);
+    }
+
+    #[test]
+    fn inner_text_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        let test_cases = vec![
+            ("<div>  a  <b> b </b>  c  </div>", "a b c"),
+            ("<div>a<div>b</div>c</div>", "a\nb\nc"),
+            ("<div><p>a</p><p>b</p>c</div>", "a\n\nb\n\nc"),
+            ("<div>a <br/> b<br/></div>", "a\nb\n"),
+            ("<div>a<script>var x;</script><style>p {}</style>b</div>", "ab"),
+            ("<div>a<pre>  b\n  c</pre></div>", "a\n  b\n  c"),
+            ("<table><tr><td>1</td> <th>2</th></tr><tr><td>3</td></tr></table>", "1\t2\n3"),
+            ("<div>\n  <ul>\n    <li>a</li>\n    <li>b</li>\n  </ul>\n</div>", "a\nb"),
+        ];
+
+        for (html, answer) in test_cases.into_iter() {
+            into_dom(html.to_string()).unwrap();
+            assert_eq!(dom::get_root().inner_text(), answer, "{}", html);
+        }
+
+        drop(lock);
+    }
+
+}
@@ -102,6 +102,31 @@ pub fn skip_whitespaces(s: &[u16], mut index: usize) -> usize {
     index
 }
 
+// https://www.w3.org/TR/xml/#sec-line-ends
+// `\r\n` and `\r` into `\n`
+pub fn normalize_line_endings(s: &[u16]) -> Vec<u16> {
+    let mut result = Vec::with_capacity(s.len());
+
+    for (index, c) in s.iter().enumerate() {
+
+        if *c == '\r' as u16 {
+
+            if index + 1 < s.len() && s[index + 1] == '\n' as u16 {
+                continue;
+            }
+
+            result.push('\n' as u16);
+        }
+
+        else {
+            result.push(*c);
+        }
+
+    }
+
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use crate::utils::*;
@@ -126,6 +151,11 @@ mod tests {
 
     }
 
+    #[test]
+    fn line_ending_test() {
+        assert_eq!(from_v16(&normalize_line_endings(&into_v16("a\r\nb\rc\n\r\n"))), "a\nb\nc\n\n");
+    }
+
     #[test]
     fn hexadecimal_test() {
         let test_cases = vec![
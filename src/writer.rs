@@ -0,0 +1,263 @@
+use crate::err::HxmlError;
+use crate::node::element::Content;
+use crate::node::memory;
+use crate::node::pointer::ElementPtr;
+use crate::node::prolog::XMLDecl;
+use crate::serialize::{check_chars, check_name, escape_attribute_value, pick_quote, write_content, write_element};
+use std::io::Write;
+
+/// It writes a document event by event, without building a tree.
+/// The output is always well-formed: every call that would break it returns an error and writes nothing.
+pub struct XmlWriter<W: Write> {
+    w: W,
+
+    /// names of the elements that are not closed yet
+    stack: Vec<String>,
+
+    /// names of the attributes of the start tag that is being written (`<a x='1'`, without `>`)
+    start_tag: Option<Vec<String>>,
+    written_anything: bool,
+    has_root: bool,
+}
+
+impl<W: Write> XmlWriter<W> {
+
+    pub fn new(w: W) -> Self {
+        XmlWriter {
+            w,
+            stack: vec![],
+            start_tag: None,
+            written_anything: false,
+            has_root: false,
+        }
+    }
+
+    /// It must be the first thing to write.
+    pub fn xml_decl(&mut self, encoding: Option<&str>, standalone: Option<bool>) -> Result<(), HxmlError> {
+
+        if self.written_anything {
+            return Err(HxmlError::new(String::from("The XML declaration must be at the beginning of the document"), usize::MAX));
+        }
+
+        let xml_decl = XMLDecl::new(String::from("1.0"), encoding.map(|e| e.to_string()), standalone);
+        self.w.write_all(xml_decl.to_string().as_bytes())?;
+        self.written_anything = true;
+
+        Ok(())
+    }
+
+    pub fn start_element(&mut self, name: &str) -> Result<(), HxmlError> {
+        check_name(name)?;
+
+        if self.stack.is_empty() && self.has_root {
+            return Err(HxmlError::new(format!("A document cannot have more than one root element: <{}>", name), usize::MAX));
+        }
+
+        self.close_start_tag()?;
+        write!(self.w, "<{}", name)?;
+
+        self.stack.push(name.to_string());
+        self.start_tag = Some(vec![]);
+        self.written_anything = true;
+        self.has_root = true;
+
+        Ok(())
+    }
+
+    /// It can only be called right after `start_element` or another `attribute`.
+    pub fn attribute(&mut self, name: &str, value: &str) -> Result<(), HxmlError> {
+        check_name(name)?;
+        check_chars(value)?;
+
+        match &mut self.start_tag {
+            Some(names) => {
+
+                if names.iter().any(|n| n == name) {
+                    return Err(HxmlError::new(format!("Duplicate attribute: {}", name), usize::MAX));
+                }
+
+                let quote = pick_quote(value);
+                write!(self.w, " {}={}{}{}", name, quote, escape_attribute_value(value, quote), quote)?;
+                names.push(name.to_string());
+
+                Ok(())
+            }
+            None => Err(HxmlError::new(format!("An attribute must follow a start tag: {}", name), usize::MAX))
+        }
+
+    }
+
+    /// An element without contents is written as `<a/>`.
+    pub fn end_element(&mut self) -> Result<(), HxmlError> {
+
+        match self.stack.pop() {
+            Some(name) => {
+
+                if self.start_tag.is_some() {
+                    self.start_tag = None;
+                    self.w.write_all(b"/>")?;
+                }
+
+                else {
+                    write!(self.w, "</{}>", name)?;
+                }
+
+                Ok(())
+            }
+            None => Err(HxmlError::new(String::from("There's no element to close"), usize::MAX))
+        }
+
+    }
+
+    /// Outside the root element, only whitespaces are allowed, and they're written as they are.
+    pub fn text(&mut self, text: &str) -> Result<(), HxmlError> {
+
+        if self.stack.is_empty() {
+
+            if !text.chars().all(|c| c == ' ' || c == '\t' || c == '\n' || c == '\r') {
+                return Err(HxmlError::new(format!("Text outside the root element: {:?}", text), usize::MAX));
+            }
+
+            // not escaped: character references (eg. `&#xD;`) are not allowed outside the root element
+            self.w.write_all(text.as_bytes())?;
+            self.written_anything = true;
+
+            return Ok(());
+        }
+
+        self.write_content(Content::CharData(text.to_string()))
+    }
+
+    pub fn cdata(&mut self, cd_sect: &str) -> Result<(), HxmlError> {
+
+        if self.stack.is_empty() {
+            return Err(HxmlError::new(String::from("A CDATA section outside the root element"), usize::MAX));
+        }
+
+        self.write_content(Content::CDSect(cd_sect.to_string()))
+    }
+
+    pub fn comment(&mut self, comment: &str) -> Result<(), HxmlError> {
+        self.write_content(Content::Comment(comment.to_string()))
+    }
+
+    /// It writes a subtree of the dom.
+    /// The subtree is serialized into a buffer first, so that nothing is written if it's not well-formed.
+    pub fn element(&mut self, element: ElementPtr) -> Result<(), HxmlError> {
+
+        if self.stack.is_empty() && self.has_root {
+            return Err(HxmlError::new(format!("A document cannot have more than one root element: <{}>", element.get_tag_name()), usize::MAX));
+        }
+
+        let mut buffer = vec![];
+        write_element(memory::get(element.ptr), true, &mut buffer)?;
+
+        self.close_start_tag()?;
+        self.w.write_all(&buffer)?;
+        self.written_anything = true;
+        self.has_root = true;
+
+        Ok(())
+    }
+
+    /// It fails if there are elements that are not closed, or there's no root element.
+    pub fn finish(mut self) -> Result<W, HxmlError> {
+
+        if !self.stack.is_empty() {
+            return Err(HxmlError::new(format!("Elements are not closed: {:?}", self.stack), usize::MAX));
+        }
+
+        if !self.has_root {
+            return Err(HxmlError::new(String::from("There's no root element"), usize::MAX));
+        }
+
+        self.w.flush()?;
+
+        Ok(self.w)
+    }
+
+    fn write_content(&mut self, content: Content) -> Result<(), HxmlError> {
+        // nothing is written if it fails
+        let mut buffer = vec![];
+        write_content(&content, true, &mut buffer)?;
+
+        self.close_start_tag()?;
+        self.w.write_all(&buffer)?;
+        self.written_anything = true;
+
+        Ok(())
+    }
+
+    fn close_start_tag(&mut self) -> Result<(), HxmlError> {
+
+        if self.start_tag.is_some() {
+            self.start_tag = None;
+            self.w.write_all(b">")?;
+        }
+
+        Ok(())
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dom;
+    use crate::writer::*;
+
+    #[test]
+    fn xml_writer_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        crate::into_dom("<b>x<!--y--></b>".to_string()).unwrap();
+
+        let mut writer = XmlWriter::new(vec![]);
+        writer.xml_decl(Some("UTF-8"), None).unwrap();
+        writer.comment(" generated ").unwrap();
+        writer.start_element("a").unwrap();
+        writer.attribute("title", "\"1\" < 2").unwrap();
+        assert!(writer.attribute("title", "3").is_err());
+        assert!(writer.attribute("1x", "3").is_err());
+        writer.start_element("empty").unwrap();
+        writer.end_element().unwrap();
+        assert!(writer.attribute("title", "3").is_err());
+        writer.text("a & b ]]>").unwrap();
+        writer.cdata("]]>").unwrap();
+        assert!(writer.comment("a -- b").is_err());
+        assert!(writer.text("\u{0}").is_err());
+        writer.element(dom::get_root()).unwrap();
+        writer.end_element().unwrap();
+        assert!(writer.start_element("c").is_err());
+        assert!(writer.text("text").is_err());
+        writer.text("\r\n").unwrap();
+        assert!(writer.end_element().is_err());
+
+        let output = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert_eq!(output, "<?xml version='1.0' encoding='UTF-8'?><!-- generated --><a title='\"1\" &lt; 2'><empty/>a &amp; b ]]&gt;<![CDATA[]]]]><![CDATA[>]]><b>x<!--y--></b></a>\r\n");
+
+        // elements that are not well-formed are not written at all
+        crate::into_dom("<b>x</b>".to_string()).unwrap();
+        dom::get_root().add_contents(vec![crate::Content::new_char_data("y".to_string()), crate::Content::new_comment("-".to_string())]);
+        let mut writer = XmlWriter::new(vec![]);
+        writer.start_element("a").unwrap();
+        assert!(writer.element(dom::get_root()).is_err());
+        writer.end_element().unwrap();
+        assert_eq!(writer.finish().unwrap(), b"<a/>");
+
+        crate::into_dom(output.clone()).unwrap();
+        let mut streamed = vec![];
+        dom::write_to(&mut streamed).unwrap();
+        assert_eq!(String::from_utf8(streamed).unwrap(), dom::to_string());
+
+        let writer = XmlWriter::new(vec![]);
+        assert!(writer.finish().is_err());
+
+        let mut writer = XmlWriter::new(vec![]);
+        writer.start_element("a").unwrap();
+        assert!(writer.xml_decl(None, None).is_err());
+        assert!(writer.finish().is_err());
+
+        drop(lock);
+    }
+
+}
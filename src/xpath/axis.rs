@@ -0,0 +1,429 @@
+use super::parse::Axis;
+use super::{XPathNode, XML_NAMESPACE};
+use crate::dom;
+use crate::node::element::Content;
+use crate::node::memory;
+use crate::node::pointer::ElementPtr;
+use std::collections::HashMap;
+
+// nodes on `axis` from `node`, in the order of the axis (reverse document order for reverse axes)
+pub fn get_axis_nodes(node: XPathNode, axis: Axis) -> Vec<XPathNode> {
+    let mut result = vec![];
+
+    match axis {
+        Axis::Ancestor => {
+            push_ancestors(node, &mut result);
+        }
+        Axis::AncestorOrSelf => {
+            result.push(node);
+            push_ancestors(node, &mut result);
+        }
+        Axis::Attribute => {
+
+            if let XPathNode::Element(element) = node {
+
+                for (index, attribute) in memory::get(element.ptr).attributes.iter().enumerate() {
+
+                    if attribute.name != "xmlns" && !attribute.name.starts_with("xmlns:") {
+                        result.push(XPathNode::Attribute(element, index));
+                    }
+
+                }
+
+            }
+
+        }
+        Axis::Child => {
+            result = get_children(node);
+        }
+        Axis::Descendant => {
+            push_descendants(node, &mut result);
+        }
+        Axis::DescendantOrSelf => {
+            result.push(node);
+            push_descendants(node, &mut result);
+        }
+        Axis::Following => match node {
+            XPathNode::Attribute(element, _) | XPathNode::Namespace(element, _, _) => {
+                push_descendants(XPathNode::Element(element), &mut result);
+                push_following(XPathNode::Element(element), &mut result);
+            }
+            _ => {
+                push_following(node, &mut result);
+            }
+        },
+        Axis::FollowingSibling => {
+            let (siblings, index) = get_siblings(node);
+
+            if !siblings.is_empty() {
+                result = siblings[(index + 1)..].to_vec();
+            }
+
+        }
+        Axis::Namespace => {
+
+            if let XPathNode::Element(element) = node {
+                result = get_namespaces(element);
+            }
+
+        }
+        Axis::Parent => {
+            result.extend(get_parent(node));
+        }
+        Axis::Preceding => match node {
+            XPathNode::Attribute(element, _) | XPathNode::Namespace(element, _, _) => {
+                push_preceding(XPathNode::Element(element), &mut result);
+            }
+            _ => {
+                push_preceding(node, &mut result);
+            }
+        },
+        Axis::PrecedingSibling => {
+            let (siblings, index) = get_siblings(node);
+
+            if !siblings.is_empty() {
+                result = siblings[..index].iter().rev().copied().collect();
+            }
+
+        }
+        Axis::Self_ => {
+            result.push(node);
+        }
+    }
+
+    result
+}
+
+pub fn get_parent(node: XPathNode) -> Option<XPathNode> {
+
+    match node {
+        XPathNode::Root => None,
+        XPathNode::Element(element) => match element.get_parent() {
+            Some(parent) => Some(XPathNode::Element(parent)),
+            None => Some(XPathNode::Root)
+        },
+        XPathNode::Attribute(element, _)
+        | XPathNode::Text(element, _, _)
+        | XPathNode::Comment(element, _)
+        | XPathNode::Namespace(element, _, _) => Some(XPathNode::Element(element)),
+    }
+
+}
+
+// Adjacent char data, CDATA sections and references are grouped into a text node.
+// Empty text nodes are skipped.
+pub fn get_children(node: XPathNode) -> Vec<XPathNode> {
+
+    let element = match node {
+        XPathNode::Root => {
+            return vec![XPathNode::Element(dom::get_root())];
+        }
+        XPathNode::Element(element) => element,
+        _ => {
+            return vec![];
+        }
+    };
+
+    let contents = &memory::get(element.ptr).contents;
+    let mut result = vec![];
+    let mut text_begin = None;
+    let mut is_text_empty = true;
+
+    for (index, content) in contents.iter().enumerate() {
+
+        match content {
+            Content::CharData(s) | Content::CDSect(s) => {
+
+                if text_begin.is_none() {
+                    text_begin = Some(index);
+                }
+
+                is_text_empty = is_text_empty && s.is_empty();
+                continue;
+            }
+            Content::Reference(_) => {
+
+                if text_begin.is_none() {
+                    text_begin = Some(index);
+                }
+
+                is_text_empty = false;
+                continue;
+            }
+            _ => {}
+        }
+
+        match text_begin {
+            Some(begin) if !is_text_empty => {
+                result.push(XPathNode::Text(element, begin, index));
+            }
+            _ => {}
+        }
+
+        text_begin = None;
+        is_text_empty = true;
+
+        match content {
+            Content::Element(child) => {
+                result.push(XPathNode::Element(*child));
+            }
+            Content::Comment(_) => {
+                result.push(XPathNode::Comment(element, index));
+            }
+            _ => unreachable!()
+        }
+
+    }
+
+    match text_begin {
+        Some(begin) if !is_text_empty => {
+            result.push(XPathNode::Text(element, begin, contents.len()));
+        }
+        _ => {}
+    }
+
+    result
+}
+
+// in-scope namespaces of `element`: the nearest declaration of each prefix wins, and `xmlns=""` undeclares the default namespace
+pub fn get_namespaces(element: ElementPtr) -> Vec<XPathNode> {
+    let mut result = vec![];
+    let mut prefixes = vec![];
+    let mut curr_element = Some(element);
+
+    while let Some(declaration) = curr_element {
+
+        for (index, attribute) in memory::get(declaration.ptr).attributes.iter().enumerate() {
+            let prefix = if attribute.name == "xmlns" {
+                ""
+            } else {
+                match attribute.name.strip_prefix("xmlns:") {
+                    Some(prefix) => prefix,
+                    None => { continue; }
+                }
+            };
+
+            if prefixes.contains(&prefix) {
+                continue;
+            }
+
+            prefixes.push(prefix);
+
+            if !attribute.value.is_empty() {
+                result.push(XPathNode::Namespace(element, declaration, index));
+            }
+
+        }
+
+        curr_element = declaration.get_parent();
+    }
+
+    if !prefixes.contains(&"xml") {
+        result.push(XPathNode::Namespace(element, element, usize::MAX));
+    }
+
+    result
+}
+
+// the namespace that `prefix` is bound to at `element` (the default namespace for an empty prefix)
+// it's empty if the prefix is not declared
+pub fn lookup_namespace_uri(element: ElementPtr, prefix: &str) -> String {
+    let mut curr_element = Some(element);
+
+    while let Some(declaration) = curr_element {
+
+        for attribute in memory::get(declaration.ptr).attributes.iter() {
+            let is_declaration = if prefix.is_empty() {
+                attribute.name == "xmlns"
+            } else {
+                attribute.name.strip_prefix("xmlns:") == Some(prefix)
+            };
+
+            if is_declaration {
+                return attribute.value.clone();
+            }
+
+        }
+
+        curr_element = declaration.get_parent();
+    }
+
+    if prefix == "xml" {
+        XML_NAMESPACE.to_string()
+    }
+
+    else {
+        String::new()
+    }
+
+}
+
+// (children of the parent, the index of `node` in them)
+// attribute and namespace nodes don't have siblings
+fn get_siblings(node: XPathNode) -> (Vec<XPathNode>, usize) {
+
+    match node {
+        XPathNode::Attribute(_, _) | XPathNode::Namespace(_, _, _) => (vec![], 0),
+        _ => match get_parent(node) {
+            Some(parent) => {
+                let siblings = get_children(parent);
+
+                // an element that is not in the document
+                match siblings.iter().position(|sibling| *sibling == node) {
+                    Some(index) => (siblings, index),
+                    None => (vec![], 0)
+                }
+
+            },
+            None => (vec![], 0)
+        }
+    }
+
+}
+
+fn push_ancestors(node: XPathNode, buffer: &mut Vec<XPathNode>) {
+    let mut curr_node = get_parent(node);
+
+    while let Some(parent) = curr_node {
+        buffer.push(parent);
+        curr_node = get_parent(parent);
+    }
+
+}
+
+// pre-order
+fn push_descendants(node: XPathNode, buffer: &mut Vec<XPathNode>) {
+
+    for child in get_children(node).into_iter() {
+        buffer.push(child);
+        push_descendants(child, buffer);
+    }
+
+}
+
+// following siblings of `node` and its ancestors, with their descendants
+fn push_following(node: XPathNode, buffer: &mut Vec<XPathNode>) {
+    let mut curr_node = node;
+
+    loop {
+        let (siblings, index) = get_siblings(curr_node);
+
+        if !siblings.is_empty() {
+
+            for sibling in siblings[(index + 1)..].iter() {
+                buffer.push(*sibling);
+                push_descendants(*sibling, buffer);
+            }
+
+        }
+
+        match get_parent(curr_node) {
+            Some(parent) => {
+                curr_node = parent;
+            }
+            None => {
+                return;
+            }
+        }
+
+    }
+
+}
+
+// preceding siblings of `node` and its ancestors, with their descendants, in reverse document order
+fn push_preceding(node: XPathNode, buffer: &mut Vec<XPathNode>) {
+    let mut curr_node = node;
+
+    loop {
+        let (siblings, index) = get_siblings(curr_node);
+
+        for sibling in siblings.iter().take(index).rev() {
+            let mut subtree = vec![*sibling];
+            push_descendants(*sibling, &mut subtree);
+            buffer.extend(subtree.into_iter().rev());
+        }
+
+        match get_parent(curr_node) {
+            Some(parent) => {
+                curr_node = parent;
+            }
+            None => {
+                return;
+            }
+        }
+
+    }
+
+}
+
+// It numbers the contents of the document in document order, so that nodes can be sorted without walking up the tree every time.
+pub struct DocumentOrder {
+
+    // (the parent element, the index in its contents) -> the number of the content
+    contents: HashMap<(usize, usize), usize>,
+
+    // element -> the number of the element
+    elements: HashMap<usize, usize>,
+}
+
+impl DocumentOrder {
+
+    pub fn new() -> Self {
+        let mut result = DocumentOrder {
+            contents: HashMap::new(),
+            elements: HashMap::new(),
+        };
+        let root = dom::get_root();
+        result.elements.insert(root.ptr, 1);
+        result.number_contents(root);
+
+        result
+    }
+
+    fn number_contents(&mut self, element: ElementPtr) {
+
+        for (index, content) in memory::get(element.ptr).contents.iter().enumerate() {
+            let number = self.contents.len() + 2;
+            self.contents.insert((element.ptr, index), number);
+
+            if let Content::Element(child) = content {
+                self.elements.insert(child.ptr, number);
+                self.number_contents(*child);
+            }
+
+        }
+
+    }
+
+    // (the number of the content, 0 for elements/texts/comments, 1 for namespaces, 2 for attributes, the index)
+    // the order of namespace nodes is implementation-defined
+    // the root node is (0, 0, 0), and nodes that are not in the document come last
+    pub fn get_key(&self, node: &XPathNode) -> (usize, usize, usize) {
+
+        match node {
+            XPathNode::Root => (0, 0, 0),
+            XPathNode::Element(element) => (self.get_element_number(*element), 0, 0),
+            XPathNode::Namespace(element, _, index) => (self.get_element_number(*element), 1, *index),
+            XPathNode::Attribute(element, index) => (self.get_element_number(*element), 2, *index),
+            XPathNode::Text(element, index, _) | XPathNode::Comment(element, index) => (
+                match self.contents.get(&(element.ptr, *index)) {
+                    Some(number) => *number,
+                    None => usize::MAX
+                },
+                0,
+                0
+            ),
+        }
+
+    }
+
+    fn get_element_number(&self, element: ElementPtr) -> usize {
+
+        match self.elements.get(&element.ptr) {
+            Some(number) => *number,
+            None => usize::MAX
+        }
+
+    }
+
+}
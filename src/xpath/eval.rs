@@ -0,0 +1,302 @@
+use super::axis::{get_axis_nodes, DocumentOrder};
+use super::parse::{Axis, Expr, NodeTest, Step};
+use super::{XPathNode, XPathValue, XML_NAMESPACE};
+use std::collections::{HashMap, HashSet};
+
+pub struct Evaluator<'a> {
+    pub(super) variables: &'a HashMap<String, XPathValue>,
+
+    // prefix -> namespace URI, for name tests
+    namespaces: &'a HashMap<String, String>,
+
+    // It's built when nodes have to be sorted for the first time.
+    document_order: Option<DocumentOrder>,
+}
+
+impl<'a> Evaluator<'a> {
+
+    pub fn new(variables: &'a HashMap<String, XPathValue>, namespaces: &'a HashMap<String, String>) -> Self {
+        Evaluator {
+            variables,
+            namespaces,
+            document_order: None,
+        }
+    }
+
+    // `position` and `size` are the context position and the context size, which start from 1
+    pub fn evaluate(&mut self, expr: &Expr, context: XPathNode, position: usize, size: usize) -> Result<XPathValue, String> {
+
+        match expr {
+            Expr::Or(left, right) => Ok(XPathValue::Boolean(
+                self.evaluate(left, context, position, size)?.to_boolean()
+                || self.evaluate(right, context, position, size)?.to_boolean()
+            )),
+            Expr::And(left, right) => Ok(XPathValue::Boolean(
+                self.evaluate(left, context, position, size)?.to_boolean()
+                && self.evaluate(right, context, position, size)?.to_boolean()
+            )),
+            Expr::Comparison(operator, left, right) => {
+                let left = self.evaluate(left, context, position, size)?;
+                let right = self.evaluate(right, context, position, size)?;
+
+                Ok(XPathValue::Boolean(compare(operator, &left, &right)))
+            },
+            Expr::Arithmetic(operator, left, right) => {
+                let left = self.evaluate(left, context, position, size)?.to_number();
+                let right = self.evaluate(right, context, position, size)?.to_number();
+
+                Ok(XPathValue::Number(match *operator {
+                    "+" => left + right,
+                    "-" => left - right,
+                    "*" => left * right,
+                    "div" => left / right,
+
+                    // truncating, like Java's `%`
+                    "mod" => left % right,
+                    _ => unreachable!()
+                }))
+            },
+            Expr::Negate(expr) => Ok(XPathValue::Number(-self.evaluate(expr, context, position, size)?.to_number())),
+            Expr::Union(left, right) => {
+                let mut left = self.evaluate_node_set(left, context, position, size)?;
+                let mut right = self.evaluate_node_set(right, context, position, size)?;
+                left.append(&mut right);
+
+                Ok(XPathValue::NodeSet(self.sort(left)))
+            },
+            Expr::Literal(literal) => Ok(XPathValue::String(literal.clone())),
+            Expr::Number(number) => Ok(XPathValue::Number(*number)),
+            Expr::Variable(name) => match self.variables.get(name) {
+                Some(value) => Ok(value.clone()),
+                None => Err(format!("undefined variable `${}`", name))
+            },
+            Expr::FunctionCall(name, arguments) => self.call_function(name, arguments, context, position, size),
+            Expr::Filter(expr, predicates) => {
+                let nodes = self.evaluate_node_set(expr, context, position, size)?;
+
+                Ok(XPathValue::NodeSet(self.apply_predicates(nodes, predicates)?))
+            },
+            Expr::Path(expr, steps) => {
+                let nodes = self.evaluate_node_set(expr, context, position, size)?;
+
+                Ok(XPathValue::NodeSet(self.evaluate_steps(nodes, steps)?))
+            },
+            Expr::LocationPath(is_absolute, steps) => {
+                let start = if *is_absolute { XPathNode::Root } else { context };
+
+                Ok(XPathValue::NodeSet(self.evaluate_steps(vec![start], steps)?))
+            },
+        }
+
+    }
+
+    pub(super) fn evaluate_node_set(&mut self, expr: &Expr, context: XPathNode, position: usize, size: usize) -> Result<Vec<XPathNode>, String> {
+
+        match self.evaluate(expr, context, position, size)? {
+            XPathValue::NodeSet(nodes) => Ok(nodes),
+            value => Err(format!("expected a node-set, found a {}", value.get_type_name()))
+        }
+
+    }
+
+    // `nodes` are in document order
+    fn evaluate_steps(&mut self, mut nodes: Vec<XPathNode>, steps: &[Step]) -> Result<Vec<XPathNode>, String> {
+
+        for step in steps.iter() {
+            let is_single_context = nodes.len() == 1;
+            let mut next_nodes = vec![];
+
+            for node in nodes.into_iter() {
+                next_nodes.append(&mut self.evaluate_step(node, step)?);
+            }
+
+            // nodes of an axis from a single node are already in (reverse) document order, without duplicates
+            nodes = if is_single_context {
+
+                if step.axis.is_reverse() {
+                    next_nodes.reverse();
+                }
+
+                next_nodes
+            } else {
+                self.sort(next_nodes)
+            };
+        }
+
+        Ok(nodes)
+    }
+
+    // the result is in the order of the axis
+    fn evaluate_step(&mut self, context: XPathNode, step: &Step) -> Result<Vec<XPathNode>, String> {
+        let namespace_uri = match &step.node_test {
+            NodeTest::Prefix(prefix) => self.resolve_prefix(prefix)?,
+            NodeTest::Name(name) => match name.find(':') {
+                Some(index) => self.resolve_prefix(&name[..index])?,
+                None => ""
+            },
+            _ => ""
+        };
+
+        let mut nodes = get_axis_nodes(context, step.axis);
+        nodes.retain(|node| matches_node_test(node, &step.node_test, step.axis, namespace_uri));
+
+        self.apply_predicates(nodes, &step.predicates)
+    }
+
+    // https://www.w3.org/TR/1999/REC-xpath-19991116/#node-tests
+    // Prefixes are resolved by the namespaces of the expression, not by the declarations in the document.
+    // `xml` is bound implicitly.
+    fn resolve_prefix(&self, prefix: &str) -> Result<&'a str, String> {
+
+        match self.namespaces.get(prefix) {
+            Some(uri) if !prefix.is_empty() => Ok(uri),
+            _ if prefix == "xml" => Ok(XML_NAMESPACE),
+            _ => Err(format!("the prefix `{}` is not declared", prefix))
+        }
+
+    }
+
+    // https://www.w3.org/TR/1999/REC-xpath-19991116/#predicates
+    // A number is compared with the context position, and anything else is converted to a boolean.
+    fn apply_predicates(&mut self, mut nodes: Vec<XPathNode>, predicates: &[Expr]) -> Result<Vec<XPathNode>, String> {
+
+        for predicate in predicates.iter() {
+            let size = nodes.len();
+            let mut filtered = Vec::with_capacity(size);
+
+            for (index, node) in nodes.into_iter().enumerate() {
+                let is_selected = match self.evaluate(predicate, node, index + 1, size)? {
+                    XPathValue::Number(n) => n == (index + 1) as f64,
+                    value => value.to_boolean()
+                };
+
+                if is_selected {
+                    filtered.push(node);
+                }
+
+            }
+
+            nodes = filtered;
+        }
+
+        Ok(nodes)
+    }
+
+    // It sorts the nodes in document order, and removes duplicates.
+    pub(super) fn sort(&mut self, nodes: Vec<XPathNode>) -> Vec<XPathNode> {
+
+        if nodes.len() < 2 {
+            return nodes;
+        }
+
+        if self.document_order.is_none() {
+            self.document_order = Some(DocumentOrder::new());
+        }
+
+        let document_order = self.document_order.as_ref().unwrap();
+        let mut visited = HashSet::with_capacity(nodes.len());
+        let mut result: Vec<XPathNode> = nodes.into_iter().filter(|node| visited.insert(get_identity(node))).collect();
+        result.sort_by_cached_key(|node| document_order.get_key(node));
+
+        result
+    }
+
+}
+
+// https://www.w3.org/TR/1999/REC-xpath-19991116/#node-tests
+// `*` and names match the principal node type of the axis only: attributes for the attribute axis, namespaces for the namespace axis, and elements for the others
+// Names are compared by their expanded names: `namespace_uri` is the namespace of the name test (empty if it doesn't have a prefix).
+// Namespace nodes don't have namespaces: their local names are their prefixes.
+fn matches_node_test(node: &XPathNode, node_test: &NodeTest, axis: Axis, namespace_uri: &str) -> bool {
+    let is_principal = match (node, axis) {
+        (XPathNode::Attribute(_, _), Axis::Attribute) => true,
+        (XPathNode::Namespace(_, _, _), Axis::Namespace) => true,
+        (XPathNode::Element(_), _) => axis != Axis::Attribute && axis != Axis::Namespace,
+        _ => false
+    };
+
+    match node_test {
+        NodeTest::Any => is_principal,
+        NodeTest::Prefix(_) => is_principal && node.get_namespace_uri() == namespace_uri,
+        NodeTest::Name(name) => {
+            let local_name = match name.find(':') {
+                Some(index) => &name[(index + 1)..],
+                None => name
+            };
+
+            is_principal && node.get_local_name() == local_name && node.get_namespace_uri() == namespace_uri
+        },
+        NodeTest::Node => true,
+        NodeTest::Text => matches!(node, XPathNode::Text(_, _, _)),
+        NodeTest::Comment => matches!(node, XPathNode::Comment(_, _)),
+        NodeTest::ProcessingInstruction(_) => false,
+    }
+
+}
+
+// https://www.w3.org/TR/1999/REC-xpath-19991116/#booleans
+fn compare(operator: &str, left: &XPathValue, right: &XPathValue) -> bool {
+
+    match (left, right) {
+        (XPathValue::NodeSet(left), XPathValue::NodeSet(right)) => {
+            let right: Vec<XPathValue> = right.iter().map(|node| XPathValue::String(node.get_string_value())).collect();
+
+            left.iter().any(|node| {
+                let left = XPathValue::String(node.get_string_value());
+                right.iter().any(|right| compare_values(operator, &left, right))
+            })
+        },
+        (XPathValue::NodeSet(nodes), XPathValue::Boolean(_)) => compare_values(operator, &XPathValue::Boolean(!nodes.is_empty()), right),
+        (XPathValue::Boolean(_), XPathValue::NodeSet(nodes)) => compare_values(operator, left, &XPathValue::Boolean(!nodes.is_empty())),
+        (XPathValue::NodeSet(nodes), _) => nodes.iter().any(
+            |node| compare_values(operator, &XPathValue::String(node.get_string_value()), right)
+        ),
+        (_, XPathValue::NodeSet(nodes)) => nodes.iter().any(
+            |node| compare_values(operator, left, &XPathValue::String(node.get_string_value()))
+        ),
+        _ => compare_values(operator, left, right),
+    }
+
+}
+
+// neither is a node-set
+fn compare_values(operator: &str, left: &XPathValue, right: &XPathValue) -> bool {
+
+    match operator {
+        "=" | "!=" => {
+            let is_equal = match (left, right) {
+                (XPathValue::Boolean(_), _) | (_, XPathValue::Boolean(_)) => left.to_boolean() == right.to_boolean(),
+                (XPathValue::Number(_), _) | (_, XPathValue::Number(_)) => left.to_number() == right.to_number(),
+                _ => left.to_string() == right.to_string(),
+            };
+
+            is_equal == (operator == "=")
+        },
+        _ => {
+            let (left, right) = (left.to_number(), right.to_number());
+
+            match operator {
+                "<" => left < right,
+                "<=" => left <= right,
+                ">" => left > right,
+                ">=" => left >= right,
+                _ => unreachable!()
+            }
+
+        }
+    }
+
+}
+
+fn get_identity(node: &XPathNode) -> (u8, usize, usize, usize) {
+
+    match node {
+        XPathNode::Root => (0, 0, 0, 0),
+        XPathNode::Element(element) => (1, element.ptr, 0, 0),
+        XPathNode::Attribute(element, index) => (2, element.ptr, *index, 0),
+        XPathNode::Text(element, begin, end) => (3, element.ptr, *begin, *end),
+        XPathNode::Comment(element, index) => (4, element.ptr, *index, 0),
+        XPathNode::Namespace(element, declaration, index) => (5, element.ptr, declaration.ptr, *index),
+    }
+
+}
@@ -0,0 +1,200 @@
+// https://www.w3.org/TR/1999/REC-xpath-19991116/#corelib
+
+use super::eval::Evaluator;
+use super::parse::Expr;
+use super::{axis, XPathNode, XPathValue};
+use crate::dom;
+
+impl<'a> Evaluator<'a> {
+
+    pub(super) fn call_function(&mut self, name: &str, arguments: &[Expr], context: XPathNode, position: usize, size: usize) -> Result<XPathValue, String> {
+
+        let (min_arguments, max_arguments) = match name {
+            "last" | "position" | "true" | "false" => (0, 0),
+            "local-name" | "namespace-uri" | "name" | "string" | "string-length" | "normalize-space" | "number" => (0, 1),
+            "count" | "id" | "boolean" | "not" | "lang" | "sum" | "floor" | "ceiling" | "round" => (1, 1),
+            "starts-with" | "contains" | "substring-before" | "substring-after" => (2, 2),
+            "substring" => (2, 3),
+            "translate" => (3, 3),
+            "concat" => (2, usize::MAX),
+            _ => {
+                return Err(format!("unknown function `{}()`", name));
+            }
+        };
+
+        if arguments.len() < min_arguments || arguments.len() > max_arguments {
+            return Err(format!("wrong number of arguments for `{}()`: {}", name, arguments.len()));
+        }
+
+        let mut values = Vec::with_capacity(arguments.len());
+
+        for argument in arguments.iter() {
+            values.push(self.evaluate(argument, context, position, size)?);
+        }
+
+        // functions with an optional argument take the context node by default
+        if values.is_empty() && max_arguments == 1 {
+            values.push(XPathValue::NodeSet(vec![context]));
+        }
+
+        let node_set_argument = |value: &XPathValue| match value {
+            XPathValue::NodeSet(nodes) => Ok(nodes.clone()),
+            _ => Err(format!("`{}()` expects a node-set", name))
+        };
+
+        let result = match name {
+
+            // node-set functions
+            "last" => XPathValue::Number(size as f64),
+            "position" => XPathValue::Number(position as f64),
+            "count" => XPathValue::Number(node_set_argument(&values[0])?.len() as f64),
+            "id" => {
+                let ids = match &values[0] {
+                    XPathValue::NodeSet(nodes) => nodes.iter().map(|node| node.get_string_value()).collect::<Vec<String>>().join(" "),
+                    value => value.to_string()
+                };
+                let mut nodes = vec![];
+
+                for id in ids.split_ascii_whitespace() {
+
+                    if let Some(element) = dom::get_element_by_id(None, id.to_string()) {
+                        nodes.push(XPathNode::Element(element));
+                    }
+
+                }
+
+                XPathValue::NodeSet(self.sort(nodes))
+            },
+            "local-name" | "namespace-uri" | "name" => {
+                let nodes = node_set_argument(&values[0])?;
+
+                XPathValue::String(match nodes.first() {
+                    Some(node) => match name {
+                        "local-name" => node.get_local_name(),
+                        "namespace-uri" => node.get_namespace_uri(),
+                        _ => node.get_name(),
+                    },
+                    None => String::new()
+                })
+            },
+
+            // string functions
+            "string" => XPathValue::String(values[0].to_string()),
+            "concat" => XPathValue::String(values.iter().map(|value| value.to_string()).collect::<Vec<String>>().concat()),
+            "starts-with" => XPathValue::Boolean(values[0].to_string().starts_with(&values[1].to_string())),
+            "contains" => XPathValue::Boolean(values[0].to_string().contains(&values[1].to_string())),
+            "substring-before" => {
+                let s = values[0].to_string();
+
+                XPathValue::String(match s.find(&values[1].to_string()) {
+                    Some(index) => s[..index].to_string(),
+                    None => String::new()
+                })
+            },
+            "substring-after" => {
+                let s = values[0].to_string();
+                let pattern = values[1].to_string();
+
+                XPathValue::String(match s.find(&pattern) {
+                    Some(index) => s[(index + pattern.len())..].to_string(),
+                    None => String::new()
+                })
+            },
+
+            // characters at positions p such that `round(start) <= p < round(start) + round(length)`
+            // NaN and infinities are handled by the comparisons
+            "substring" => {
+                let first = round(values[1].to_number());
+                let last = match values.get(2) {
+                    Some(length) => first + round(length.to_number()),
+                    None => f64::INFINITY
+                };
+
+                XPathValue::String(values[0].to_string().chars().enumerate().filter(
+                    |(index, _)| {
+                        let position = (*index + 1) as f64;
+                        first <= position && position < last
+                    }
+                ).map(|(_, c)| c).collect())
+            },
+            "string-length" => XPathValue::Number(values[0].to_string().chars().count() as f64),
+            "normalize-space" => XPathValue::String(
+                values[0].to_string().split([' ', '\t', '\n', '\r']).filter(
+                    |word| !word.is_empty()
+                ).collect::<Vec<&str>>().join(" ")
+            ),
+            "translate" => {
+                let from: Vec<char> = values[1].to_string().chars().collect();
+                let to: Vec<char> = values[2].to_string().chars().collect();
+
+                XPathValue::String(values[0].to_string().chars().filter_map(
+                    |c| match from.iter().position(|f| *f == c) {
+                        Some(index) => to.get(index).copied(),
+                        None => Some(c)
+                    }
+                ).collect())
+            },
+
+            // boolean functions
+            "boolean" => XPathValue::Boolean(values[0].to_boolean()),
+            "not" => XPathValue::Boolean(!values[0].to_boolean()),
+            "true" => XPathValue::Boolean(true),
+            "false" => XPathValue::Boolean(false),
+
+            // `xml:lang` of the nearest ancestor-or-self, ignoring case and suffixes (`lang('en')` matches `en-US`)
+            "lang" => {
+                let lang = values[0].to_string().to_ascii_lowercase();
+                let mut curr_node = Some(context);
+                let mut result = false;
+
+                while let Some(node) = curr_node {
+
+                    if let XPathNode::Element(element) = node {
+
+                        if let Some(value) = element.get_attribute("xml:lang".to_string()) {
+                            let value = value.to_ascii_lowercase();
+                            result = value == lang || value.starts_with(&format!("{}-", lang));
+                            break;
+                        }
+
+                    }
+
+                    curr_node = axis::get_parent(node);
+                }
+
+                XPathValue::Boolean(result)
+            },
+
+            // number functions
+            "number" => XPathValue::Number(values[0].to_number()),
+            "sum" => XPathValue::Number(node_set_argument(&values[0])?.iter().map(
+                |node| super::string_to_number(&node.get_string_value())
+            ).sum()),
+            "floor" => XPathValue::Number(values[0].to_number().floor()),
+            "ceiling" => XPathValue::Number(values[0].to_number().ceil()),
+            "round" => XPathValue::Number(round(values[0].to_number())),
+            _ => unreachable!()
+        };
+
+        Ok(result)
+    }
+
+}
+
+// https://www.w3.org/TR/1999/REC-xpath-19991116/#function-round
+// the closest integer, rounding halves towards positive infinity
+fn round(n: f64) -> f64 {
+
+    if n.is_nan() || n.is_infinite() || n == 0.0 {
+        n
+    }
+
+    else if (-0.5..0.0).contains(&n) {
+        -0.0
+    }
+
+    else {
+        (n + 0.5).floor()
+    }
+
+}
@@ -0,0 +1,301 @@
+// https://www.w3.org/TR/1999/REC-xpath-19991116/#exprlex
+
+use crate::predicate;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    LeftParenthesis,
+    RightParenthesis,
+    LeftBracket,
+    RightBracket,
+    Dot,
+    DotDot,
+    At,
+    Comma,
+    ColonColon,
+
+    // `*`, `prefix:*` or a QName
+    NameTest(String),
+
+    // `comment`, `text`, `processing-instruction` or `node`, followed by `(`
+    NodeType(String),
+
+    // a QName followed by `(`
+    FunctionName(String),
+
+    // a name followed by `::`
+    AxisName(String),
+    Literal(String),
+    Number(f64),
+    VariableReference(String),
+
+    // `and`, `or`, `mod`, `div`, `*`, `/`, `//`, `|`, `+`, `-`, `=`, `!=`, `<`, `<=`, `>`, `>=`
+    Operator(&'static str),
+}
+
+pub fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut result = vec![];
+    let mut index = 0;
+
+    while index < chars.len() {
+        let c = chars[index];
+
+        if is_whitespace(c) {
+            index += 1;
+            continue;
+        }
+
+        let next = chars.get(index + 1).copied();
+
+        let token = match c {
+            '(' => Token::LeftParenthesis,
+            ')' => Token::RightParenthesis,
+            '[' => Token::LeftBracket,
+            ']' => Token::RightBracket,
+            ',' => Token::Comma,
+            '@' => Token::At,
+            '.' if next == Some('.') => {
+                index += 1;
+                Token::DotDot
+            },
+            '.' if next.is_some_and(|n| n.is_ascii_digit()) => {
+                let (number, end_index) = read_number(&chars, index);
+                index = end_index - 1;
+                Token::Number(number)
+            },
+            '.' => Token::Dot,
+            ':' if next == Some(':') => {
+                index += 1;
+                Token::ColonColon
+            },
+            '"' | '\'' => match chars[(index + 1)..].iter().position(|q| *q == c) {
+                Some(length) => {
+                    let literal = chars[(index + 1)..(index + 1 + length)].iter().collect();
+                    index += length + 1;
+                    Token::Literal(literal)
+                },
+                None => {
+                    return Err(String::from("unterminated literal"));
+                }
+            },
+            '0'..='9' => {
+                let (number, end_index) = read_number(&chars, index);
+                index = end_index - 1;
+                Token::Number(number)
+            },
+            '$' => {
+                let (name, end_index) = read_qname(&chars, index + 1);
+
+                if name.is_empty() {
+                    return Err(String::from("expected a variable name after `$`"));
+                }
+
+                index = end_index - 1;
+                Token::VariableReference(name)
+            },
+            '/' if next == Some('/') => {
+                index += 1;
+                Token::Operator("//")
+            },
+            '/' => Token::Operator("/"),
+            '|' => Token::Operator("|"),
+            '+' => Token::Operator("+"),
+            '-' => Token::Operator("-"),
+            '=' => Token::Operator("="),
+            '!' if next == Some('=') => {
+                index += 1;
+                Token::Operator("!=")
+            },
+            '<' if next == Some('=') => {
+                index += 1;
+                Token::Operator("<=")
+            },
+            '<' => Token::Operator("<"),
+            '>' if next == Some('=') => {
+                index += 1;
+                Token::Operator(">=")
+            },
+            '>' => Token::Operator(">"),
+
+            // If there's a preceding token that is not `@`, `::`, `(`, `[`, `,` or an operator,
+            // `*` is the multiply operator and a name is an operator name.
+            '*' if is_operator_context(&result) => Token::Operator("*"),
+            '*' => Token::NameTest(String::from("*")),
+            _ if is_name_start_char(c) => {
+                let (name, end_index) = read_qname(&chars, index);
+                index = end_index - 1;
+
+                if is_operator_context(&result) {
+
+                    match name.as_str() {
+                        "and" => Token::Operator("and"),
+                        "or" => Token::Operator("or"),
+                        "mod" => Token::Operator("mod"),
+                        "div" => Token::Operator("div"),
+                        _ => {
+                            return Err(format!("expected an operator, found `{}`", name));
+                        }
+                    }
+
+                }
+
+                // `prefix:*`
+                else if chars.get(index + 1) == Some(&':') && chars.get(index + 2) == Some(&'*') && !name.contains(':') {
+                    index += 2;
+                    Token::NameTest(format!("{}:*", name))
+                }
+
+                else {
+                    let mut next_index = index + 1;
+
+                    while next_index < chars.len() && is_whitespace(chars[next_index]) {
+                        next_index += 1;
+                    }
+
+                    match (chars.get(next_index), chars.get(next_index + 1)) {
+                        (Some('('), _) if ["comment", "text", "processing-instruction", "node"].contains(&name.as_str()) => Token::NodeType(name),
+                        (Some('('), _) => Token::FunctionName(name),
+                        (Some(':'), Some(':')) => Token::AxisName(name),
+                        _ => Token::NameTest(name),
+                    }
+
+                }
+
+            },
+            _ => {
+                return Err(format!("unexpected `{}`", c));
+            }
+        };
+
+        result.push(token);
+        index += 1;
+    }
+
+    Ok(result)
+}
+
+fn is_operator_context(preceding_tokens: &[Token]) -> bool {
+
+    match preceding_tokens.last() {
+        None => false,
+        Some(token) => !matches!(
+            token,
+            Token::At | Token::ColonColon | Token::LeftParenthesis | Token::LeftBracket | Token::Comma | Token::Operator(_)
+        )
+    }
+
+}
+
+// digits ('.' digits?)? | '.' digits
+// it returns (number, the index after the number)
+fn read_number(chars: &[char], mut index: usize) -> (f64, usize) {
+    let begin_index = index;
+    let mut has_dot = false;
+
+    while index < chars.len() && (chars[index].is_ascii_digit() || chars[index] == '.' && !has_dot) {
+
+        if chars[index] == '.' {
+            has_dot = true;
+        }
+
+        index += 1;
+    }
+
+    let number: String = chars[begin_index..index].iter().collect();
+
+    (number.parse::<f64>().unwrap(), index)
+}
+
+// NCName (':' NCName)?
+// it returns (name, the index after the name)
+fn read_qname(chars: &[char], index: usize) -> (String, usize) {
+    let mut end_index = read_ncname(chars, index);
+
+    if end_index > index && chars.get(end_index) == Some(&':') && chars.get(end_index + 1).is_some_and(|c| is_name_start_char(*c)) {
+        end_index = read_ncname(chars, end_index + 1);
+    }
+
+    (chars[index..end_index].iter().collect(), end_index)
+}
+
+fn read_ncname(chars: &[char], mut index: usize) -> usize {
+
+    if index < chars.len() && is_name_start_char(chars[index]) {
+        index += 1;
+
+        while index < chars.len() && is_name_char(chars[index]) {
+            index += 1;
+        }
+
+    }
+
+    index
+}
+
+// https://www.w3.org/TR/xml-names/#NT-NCName
+// a name without ':'
+fn is_name_start_char(c: char) -> bool {
+    c != ':' && (c as u32 > 0xffff || predicate::is_name_start_char(&(c as u16)))
+}
+
+fn is_name_char(c: char) -> bool {
+    c != ':' && (c as u32 > 0xffff || predicate::is_name_char(&(c as u16)))
+}
+
+fn is_whitespace(c: char) -> bool {
+    c == ' ' || c == '\t' || c == '\n' || c == '\r'
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::xpath::lexer::*;
+
+    #[test]
+    fn tokenize_test() {
+        assert_eq!(
+            tokenize("//a:b[@c and 3 * position() div .5]/child::*|x:*").unwrap(),
+            vec![
+                Token::Operator("//"),
+                Token::NameTest("a:b".to_string()),
+                Token::LeftBracket,
+                Token::At,
+                Token::NameTest("c".to_string()),
+                Token::Operator("and"),
+                Token::Number(3.0),
+                Token::Operator("*"),
+                Token::FunctionName("position".to_string()),
+                Token::LeftParenthesis,
+                Token::RightParenthesis,
+                Token::Operator("div"),
+                Token::Number(0.5),
+                Token::RightBracket,
+                Token::Operator("/"),
+                Token::AxisName("child".to_string()),
+                Token::ColonColon,
+                Token::NameTest("*".to_string()),
+                Token::Operator("|"),
+                Token::NameTest("x:*".to_string()),
+            ]
+        );
+
+        assert_eq!(
+            tokenize("$v != 'it''s' or text ()").unwrap(),
+            vec![
+                Token::VariableReference("v".to_string()),
+                Token::Operator("!="),
+                Token::Literal("it".to_string()),
+                Token::Literal("s".to_string()),
+                Token::Operator("or"),
+                Token::NodeType("text".to_string()),
+                Token::LeftParenthesis,
+                Token::RightParenthesis,
+            ]
+        );
+
+        assert!(tokenize("'abc").is_err());
+        assert!(tokenize("a b").is_err());
+        assert!(tokenize("a # b").is_err());
+    }
+
+}
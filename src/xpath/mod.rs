@@ -0,0 +1,449 @@
+// XPath 1.0
+// https://www.w3.org/TR/1999/REC-xpath-19991116/
+
+mod axis;
+mod eval;
+mod function;
+mod lexer;
+mod parse;
+
+use crate::dom;
+use crate::err::HxmlError;
+use crate::node::element::Content;
+use crate::node::memory;
+use crate::node::pointer::ElementPtr;
+use eval::Evaluator;
+use parse::Expr;
+use std::collections::HashMap;
+
+/// A compiled XPath 1.0 expression.
+/// It doesn't hold any node, so it can be evaluated against any document.
+///
+/// Name tests compare expanded names (the namespace URI and the local name), as XPath 1.0 does.
+/// Prefixes in the expression are resolved by the namespaces given to `compile_with_namespaces`, not by the declarations in the document,
+/// and a name without a prefix matches nodes without a namespace only: `//a` doesn't match `<a xmlns="urn:x">`.
+#[derive(Clone, Debug)]
+pub struct XPath {
+    expression: String,
+    expr: Expr,
+
+    /// prefix -> namespace URI
+    namespaces: HashMap<String, String>,
+}
+
+impl XPath {
+
+    pub fn compile(expression: &str) -> Result<Self, HxmlError> {
+        XPath::compile_with_namespaces(expression, HashMap::new())
+    }
+
+    /// `namespaces` maps prefixes to namespace URIs. An empty prefix is ignored: it can't be used in a name test.
+    /// Evaluating a name test whose prefix is not in `namespaces` is an error.
+    pub fn compile_with_namespaces(expression: &str, namespaces: HashMap<String, String>) -> Result<Self, HxmlError> {
+        Ok(XPath {
+            expression: expression.to_string(),
+            expr: parse::parse(expression)?,
+            namespaces,
+        })
+    }
+
+    /// It evaluates the expression with `context` as the context node.
+    pub fn evaluate(&self, context: XPathNode) -> Result<XPathValue, HxmlError> {
+        self.evaluate_with_variables(context, &HashMap::new())
+    }
+
+    /// `$name` in the expression is bound to `variables[name]`.
+    pub fn evaluate_with_variables(&self, context: XPathNode, variables: &HashMap<String, XPathValue>) -> Result<XPathValue, HxmlError> {
+        let mut evaluator = Evaluator::new(variables, &self.namespaces);
+
+        match evaluator.evaluate(&self.expr, context, 1, 1) {
+            Ok(value) => Ok(value),
+            Err(e) => Err(HxmlError::new(format!("XPath {:?}: {}", self.expression, e), usize::MAX))
+        }
+
+    }
+
+    /// It fails if the result is not a node-set.
+    pub fn select_nodes(&self, context: XPathNode) -> Result<Vec<XPathNode>, HxmlError> {
+
+        match self.evaluate(context)? {
+            XPathValue::NodeSet(nodes) => Ok(nodes),
+            value => Err(HxmlError::new(format!("XPath {:?} evaluates to a {}, not a node-set", self.expression, value.get_type_name()), usize::MAX))
+        }
+
+    }
+
+    /// It's like `select_nodes`, but it returns the elements only.
+    pub fn select_elements(&self, context: XPathNode) -> Result<Vec<ElementPtr>, HxmlError> {
+        Ok(self.select_nodes(context)?.into_iter().filter_map(|node| node.as_element()).collect())
+    }
+
+    pub fn get_expression(&self) -> &str {
+        &self.expression
+    }
+
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum XPathValue {
+
+    /// in document order, without duplicates
+    NodeSet(Vec<XPathNode>),
+    Boolean(bool),
+    Number(f64),
+    String(String),
+}
+
+impl XPathValue {
+
+    // https://www.w3.org/TR/1999/REC-xpath-19991116/#function-boolean
+    pub fn to_boolean(&self) -> bool {
+
+        match self {
+            XPathValue::NodeSet(nodes) => !nodes.is_empty(),
+            XPathValue::Boolean(b) => *b,
+            XPathValue::Number(n) => *n != 0.0 && !n.is_nan(),
+            XPathValue::String(s) => !s.is_empty(),
+        }
+
+    }
+
+    // https://www.w3.org/TR/1999/REC-xpath-19991116/#function-number
+    pub fn to_number(&self) -> f64 {
+
+        match self {
+            XPathValue::Boolean(b) => if *b { 1.0 } else { 0.0 },
+            XPathValue::Number(n) => *n,
+            _ => string_to_number(&self.to_string()),
+        }
+
+    }
+
+    fn get_type_name(&self) -> &'static str {
+
+        match self {
+            XPathValue::NodeSet(_) => "node-set",
+            XPathValue::Boolean(_) => "boolean",
+            XPathValue::Number(_) => "number",
+            XPathValue::String(_) => "string",
+        }
+
+    }
+
+}
+
+// https://www.w3.org/TR/1999/REC-xpath-19991116/#function-string
+impl std::fmt::Display for XPathValue {
+
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+
+        match self {
+            XPathValue::NodeSet(nodes) => match nodes.first() {
+                Some(node) => write!(fmt, "{}", node.get_string_value()),
+                None => Ok(())
+            },
+            XPathValue::Boolean(b) => write!(fmt, "{}", b),
+            XPathValue::Number(n) => write!(fmt, "{}", number_to_string(*n)),
+            XPathValue::String(s) => write!(fmt, "{}", s),
+        }
+
+    }
+
+}
+
+/// A node of the XPath data model.
+/// Adjacent char data, CDATA sections and references are a single text node.
+/// The prolog (the XML declaration, the doctype, and the comments before the root element) is not a part of the tree.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum XPathNode {
+    Root,
+    Element(ElementPtr),
+
+    /// (the owner element, the index in its attributes)
+    /// `xmlns` and `xmlns:*` are not attributes: they're namespace nodes.
+    Attribute(ElementPtr, usize),
+
+    /// (the parent element, the index of its first content, the index after its last content)
+    Text(ElementPtr, usize, usize),
+
+    /// (the parent element, the index in its contents)
+    Comment(ElementPtr, usize),
+
+    /// (the element, the element that declares the namespace, the index of the declaration in its attributes)
+    /// the index is `usize::MAX` for the `xml` prefix, which is declared implicitly
+    Namespace(ElementPtr, ElementPtr, usize),
+}
+
+const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+
+impl XPathNode {
+
+    pub fn as_element(&self) -> Option<ElementPtr> {
+
+        match self {
+            XPathNode::Element(element) => Some(*element),
+            _ => None
+        }
+
+    }
+
+    // https://www.w3.org/TR/1999/REC-xpath-19991116/#data-model
+    pub fn get_string_value(&self) -> String {
+
+        match self {
+            XPathNode::Root => XPathNode::Element(dom::get_root()).get_string_value(),
+            XPathNode::Element(element) => {
+                let mut result = String::new();
+                push_text_descendants(*element, &mut result);
+
+                result
+            },
+            XPathNode::Attribute(element, index) => memory::get(element.ptr).attributes[*index].value.clone(),
+            XPathNode::Text(element, begin, end) => {
+                let mut result = String::new();
+
+                for content in memory::get(element.ptr).contents[*begin..*end].iter() {
+                    push_text(content, &mut result);
+                }
+
+                result
+            },
+            XPathNode::Comment(element, index) => match &memory::get(element.ptr).contents[*index] {
+                Content::Comment(comment) => comment.clone(),
+                _ => unreachable!()
+            },
+            XPathNode::Namespace(_, _, usize::MAX) => XML_NAMESPACE.to_string(),
+            XPathNode::Namespace(_, declaration, index) => memory::get(declaration.ptr).attributes[*index].value.clone(),
+        }
+
+    }
+
+    /// the qualified name of elements and attributes, and the prefix of namespace nodes
+    /// it's empty for the other nodes
+    pub fn get_name(&self) -> String {
+
+        match self {
+            XPathNode::Element(element) => memory::get(element.ptr).tag_name.clone(),
+            XPathNode::Attribute(element, index) => memory::get(element.ptr).attributes[*index].name.clone(),
+            XPathNode::Namespace(_, _, usize::MAX) => String::from("xml"),
+            XPathNode::Namespace(_, declaration, index) => {
+                let name = &memory::get(declaration.ptr).attributes[*index].name;
+
+                match name.strip_prefix("xmlns:") {
+                    Some(prefix) => prefix.to_string(),
+                    None => String::new()
+                }
+
+            },
+            _ => String::new()
+        }
+
+    }
+
+    pub fn get_local_name(&self) -> String {
+        let name = self.get_name();
+
+        match self {
+            XPathNode::Element(_) | XPathNode::Attribute(_, _) => match name.find(':') {
+                Some(index) => name[(index + 1)..].to_string(),
+                None => name
+            },
+            _ => name
+        }
+
+    }
+
+    /// The namespace is resolved by the `xmlns` attributes of the ancestors.
+    /// Attributes without prefixes don't have namespaces.
+    pub fn get_namespace_uri(&self) -> String {
+        let (element, name) = match self {
+            XPathNode::Element(element) => (*element, self.get_name()),
+            XPathNode::Attribute(element, _) => match self.get_name().find(':') {
+                Some(_) => (*element, self.get_name()),
+                None => { return String::new(); }
+            },
+            _ => { return String::new(); }
+        };
+
+        let prefix = match name.find(':') {
+            Some(index) => &name[..index],
+            None => ""
+        };
+
+        axis::lookup_namespace_uri(element, prefix)
+    }
+
+}
+
+fn push_text_descendants(element: ElementPtr, buffer: &mut String) {
+
+    for content in memory::get(element.ptr).contents.iter() {
+
+        match content {
+            Content::Element(child) => {
+                push_text_descendants(*child, buffer);
+            }
+            _ => {
+                push_text(content, buffer);
+            }
+        }
+
+    }
+
+}
+
+// references to unknown entities are written as they are
+fn push_text(content: &Content, buffer: &mut String) {
+
+    match content {
+        Content::CharData(s) | Content::CDSect(s) => {
+            buffer.push_str(s);
+        }
+        Content::Reference(reference) => {
+            let reference = format!("&{};", reference);
+
+            match crate::parse::expand_reference(&crate::utils::into_v16(&reference)) {
+                Some(expanded) => {
+                    buffer.push_str(&crate::utils::from_v16(&expanded));
+                }
+                None => {
+                    buffer.push_str(&reference);
+                }
+            }
+
+        }
+        _ => {}
+    }
+
+}
+
+// https://www.w3.org/TR/1999/REC-xpath-19991116/#NT-Number
+// whitespace* '-'? (digits ('.' digits?)? | '.' digits) whitespace*
+// anything else is NaN
+pub(crate) fn string_to_number(s: &str) -> f64 {
+    let s = s.trim_matches(|c| c == ' ' || c == '\t' || c == '\n' || c == '\r');
+    let digits = s.strip_prefix('-').unwrap_or(s);
+
+    let is_valid = !digits.is_empty() && digits != "."
+        && digits.chars().all(|c| c.is_ascii_digit() || c == '.')
+        && digits.chars().filter(|c| *c == '.').count() <= 1;
+
+    if is_valid {
+        s.parse::<f64>().unwrap_or(f64::NAN)
+    }
+
+    else {
+        f64::NAN
+    }
+
+}
+
+// https://www.w3.org/TR/1999/REC-xpath-19991116/#function-string
+pub(crate) fn number_to_string(n: f64) -> String {
+
+    if n.is_nan() {
+        String::from("NaN")
+    }
+
+    else if n.is_infinite() {
+        String::from(if n > 0.0 { "Infinity" } else { "-Infinity" })
+    }
+
+    // `-0` is `0`
+    else if n == 0.0 {
+        String::from("0")
+    }
+
+    else {
+        // `f64`'s `Display` doesn't use the exponential notation
+        n.to_string()
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dom;
+    use crate::xpath::*;
+
+    #[test]
+    fn xpath_test() {
+        let lock = dom::LOCK.lock().unwrap();
+
+        crate::into_dom("<catalog xmlns:x=\"urn:x\" xml:lang=\"en-US\">
+  <book id=\"b1\" price=\"10\"><title>A &amp; B</title><x:tag>1</x:tag></book>
+  <!-- c -->
+  <book id=\"b2\" price=\"25.5\"><title><![CDATA[C]]>D</title></book>
+  <book id=\"b3\" price=\"abc\"><title>E</title><x:tag>2</x:tag></book>
+</catalog>".to_string()).unwrap();
+
+        let mut namespaces = HashMap::new();
+        namespaces.insert("x".to_string(), "urn:x".to_string());
+        namespaces.insert("y".to_string(), "urn:x".to_string());
+
+        let strings = |expression: &str| match XPath::compile_with_namespaces(expression, namespaces.clone()).unwrap().evaluate(XPathNode::Root).unwrap() {
+            XPathValue::NodeSet(nodes) => nodes.iter().map(|node| node.get_string_value()).collect::<Vec<String>>(),
+            value => vec![value.to_string()],
+        };
+
+        assert_eq!(strings("//book/title"), vec!["A & B", "CD", "E"]);
+        assert_eq!(strings("/catalog/book[2]/@id"), vec!["b2"]);
+        assert_eq!(strings("//book[last()]/title"), vec!["E"]);
+        assert_eq!(strings("//book[@price > 15]/@id"), vec!["b2"]);
+        assert_eq!(strings("//book[x:tag]/@id"), vec!["b1", "b3"]);
+        assert_eq!(strings("//x:*"), vec!["1", "2"]);
+        assert_eq!(strings("//y:tag"), vec!["1", "2"]);
+        assert_eq!(strings("count(//tag) + count(//@xml:lang)"), vec!["1"]);
+        assert_eq!(strings("//title[. = 'CD']/../@id"), vec!["b2"]);
+        assert_eq!(strings("(//title)[position() mod 2 = 1]"), vec!["A & B", "E"]);
+        assert_eq!(strings("//book[3]/preceding-sibling::book[1]/@id"), vec!["b2"]);
+        assert_eq!(strings("//book[1]/following::title"), vec!["CD", "E"]);
+        assert_eq!(strings("//book[1]/following-sibling::comment()"), vec![" c "]);
+        assert_eq!(strings("//x:tag/ancestor::*[1]/@id | id('b2 b3')/@id"), vec!["b1", "b2", "b3"]);
+        assert_eq!(strings("count(//node()[self::text()])"), vec!["10"]);
+        assert_eq!(strings("sum(//book/@price)"), vec!["NaN"]);
+        assert_eq!(strings("sum(//book[number(@price) = @price]/@price)"), vec!["35.5"]);
+        assert_eq!(strings("name(//x:tag)"), vec!["x:tag"]);
+        assert_eq!(strings("local-name(//x:tag)"), vec!["tag"]);
+        assert_eq!(strings("namespace-uri(//x:tag)"), vec!["urn:x"]);
+        assert_eq!(strings("count(//book[1]/namespace::*)"), vec!["2"]);
+        assert_eq!(strings("boolean(//book[lang('en')])"), vec!["true"]);
+        assert_eq!(strings("concat(substring('12345', 1.5, 2.6), '|', substring('12345', 0, 3), '|', substring('12345', -1 div 0, 1 div 0))"), vec!["234|12|"]);
+        assert_eq!(strings("translate(normalize-space('  a  b c '), 'abc', 'AB')"), vec!["A B "]);
+        assert_eq!(strings("substring-before('2024-01-02', '-') + substring-after('a=1', '=') * 2"), vec!["2026"]);
+        assert_eq!(strings("round(-0.5) = 0 and round(2.5) = 3 and floor(-1.5) = -2 and ceiling(1.1) = 2"), vec!["true"]);
+        assert_eq!(strings("1 div 0"), vec!["Infinity"]);
+        assert_eq!(strings("7 mod -2"), vec!["1"]);
+        assert_eq!(strings("//book/@id = 'b3' and not(//book/@id != 'b1')"), vec!["false"]);
+        assert_eq!(strings("string-length(//title) + count(/)"), vec!["6"]);
+
+        let books = XPath::compile("book[@price < $max]").unwrap();
+        let root = XPathNode::Element(dom::get_root());
+        let mut variables = HashMap::new();
+        variables.insert("max".to_string(), XPathValue::Number(20.0));
+        assert_eq!(books.evaluate_with_variables(root, &variables).unwrap().to_string(), "A & B1");
+        assert!(books.evaluate(root).is_err());
+        assert_eq!(
+            dom::get_root().evaluate_xpath("count(book)").unwrap(),
+            XPathValue::Number(3.0)
+        );
+
+        // a compiled expression works on other documents
+        let titles = XPath::compile("//title").unwrap();
+        crate::into_dom("<a><title>x</title></a>".to_string()).unwrap();
+        assert_eq!(titles.select_elements(XPathNode::Root).unwrap(), vec![dom::get_element_by_tag_name(None, "title".to_string()).unwrap()]);
+
+        // a name without a prefix doesn't match elements in the default namespace
+        crate::into_dom("<a><b xmlns=\"urn:x\"><c/></b><b xmlns=\"\"/></a>".to_string()).unwrap();
+        assert_eq!(dom::evaluate_xpath("count(//b) + count(//c)").unwrap(), XPathValue::Number(1.0));
+        assert_eq!(XPath::compile_with_namespaces("count(//x:b/x:c)", namespaces.clone()).unwrap().evaluate(XPathNode::Root).unwrap(), XPathValue::Number(1.0));
+        assert!(dom::evaluate_xpath("//x:b").is_err());
+
+        assert!(dom::evaluate_xpath("foo(1)").is_err());
+        assert!(dom::evaluate_xpath("count(1)").is_err());
+        assert!(dom::evaluate_xpath("1/a").is_err());
+
+        drop(lock);
+    }
+
+}
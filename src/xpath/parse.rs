@@ -0,0 +1,550 @@
+// https://www.w3.org/TR/1999/REC-xpath-19991116/#location-paths
+// https://www.w3.org/TR/1999/REC-xpath-19991116/#section-Expressions
+
+use super::lexer::{tokenize, Token};
+use crate::err::HxmlError;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+
+    // `=`, `!=`, `<`, `<=`, `>`, `>=`
+    Comparison(&'static str, Box<Expr>, Box<Expr>),
+
+    // `+`, `-`, `*`, `div`, `mod`
+    Arithmetic(&'static str, Box<Expr>, Box<Expr>),
+    Negate(Box<Expr>),
+    Union(Box<Expr>, Box<Expr>),
+    Literal(String),
+    Number(f64),
+    Variable(String),
+    FunctionCall(String, Vec<Expr>),
+
+    // primary_expr predicate*
+    Filter(Box<Expr>, Vec<Expr>),
+
+    // filter_expr ('/' | '//') relative_location_path
+    Path(Box<Expr>, Vec<Step>),
+
+    // `/a/b` is absolute
+    LocationPath(bool, Vec<Step>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    pub axis: Axis,
+    pub node_test: NodeTest,
+    pub predicates: Vec<Expr>,
+}
+
+impl Step {
+
+    // `//` is `/descendant-or-self::node()/`
+    fn descendant_or_self() -> Self {
+        Step {
+            axis: Axis::DescendantOrSelf,
+            node_test: NodeTest::Node,
+            predicates: vec![],
+        }
+    }
+
+}
+
+// https://www.w3.org/TR/1999/REC-xpath-19991116/#axes
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Axis {
+    Ancestor,
+    AncestorOrSelf,
+    Attribute,
+    Child,
+    Descendant,
+    DescendantOrSelf,
+    Following,
+    FollowingSibling,
+    Namespace,
+    Parent,
+    Preceding,
+    PrecedingSibling,
+    Self_,
+}
+
+impl Axis {
+
+    fn from_name(name: &str) -> Option<Self> {
+
+        match name {
+            "ancestor" => Some(Axis::Ancestor),
+            "ancestor-or-self" => Some(Axis::AncestorOrSelf),
+            "attribute" => Some(Axis::Attribute),
+            "child" => Some(Axis::Child),
+            "descendant" => Some(Axis::Descendant),
+            "descendant-or-self" => Some(Axis::DescendantOrSelf),
+            "following" => Some(Axis::Following),
+            "following-sibling" => Some(Axis::FollowingSibling),
+            "namespace" => Some(Axis::Namespace),
+            "parent" => Some(Axis::Parent),
+            "preceding" => Some(Axis::Preceding),
+            "preceding-sibling" => Some(Axis::PrecedingSibling),
+            "self" => Some(Axis::Self_),
+            _ => None
+        }
+
+    }
+
+    // nodes of a reverse axis are numbered in reverse document order
+    pub fn is_reverse(&self) -> bool {
+
+        matches!(self, Axis::Ancestor | Axis::AncestorOrSelf | Axis::Preceding | Axis::PrecedingSibling)
+
+    }
+
+}
+
+// https://www.w3.org/TR/1999/REC-xpath-19991116/#node-tests
+#[derive(Clone, Debug, PartialEq)]
+pub enum NodeTest {
+
+    // `*`
+    Any,
+
+    // `prefix:*`
+    Prefix(String),
+    Name(String),
+
+    // `node()`
+    Node,
+    Text,
+    Comment,
+
+    // hxml doesn't keep processing instructions in the tree, so it matches nothing
+    ProcessingInstruction(Option<String>),
+}
+
+pub fn parse(expression: &str) -> Result<Expr, HxmlError> {
+    let error = |message: String| HxmlError::new(format!("Invalid XPath {:?}: {}", expression, message), usize::MAX);
+
+    let mut parser = Parser {
+        tokens: tokenize(expression).map_err(error)?,
+        index: 0,
+    };
+
+    let result = parser.parse_or_expr().map_err(error)?;
+
+    match parser.peek() {
+        Some(token) => Err(error(format!("unexpected {:?}", token))),
+        None => Ok(result)
+    }
+
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+}
+
+impl Parser {
+
+    // and_expr ('or' and_expr)*
+    fn parse_or_expr(&mut self) -> Result<Expr, String> {
+        let mut result = self.parse_and_expr()?;
+
+        while self.eat_operator("or") {
+            result = Expr::Or(Box::new(result), Box::new(self.parse_and_expr()?));
+        }
+
+        Ok(result)
+    }
+
+    // equality_expr ('and' equality_expr)*
+    fn parse_and_expr(&mut self) -> Result<Expr, String> {
+        let mut result = self.parse_equality_expr()?;
+
+        while self.eat_operator("and") {
+            result = Expr::And(Box::new(result), Box::new(self.parse_equality_expr()?));
+        }
+
+        Ok(result)
+    }
+
+    // relational_expr (('=' | '!=') relational_expr)*
+    fn parse_equality_expr(&mut self) -> Result<Expr, String> {
+        let mut result = self.parse_relational_expr()?;
+
+        while let Some(operator) = self.eat_one_of_operators(&["=", "!="]) {
+            result = Expr::Comparison(operator, Box::new(result), Box::new(self.parse_relational_expr()?));
+        }
+
+        Ok(result)
+    }
+
+    // additive_expr (('<' | '<=' | '>' | '>=') additive_expr)*
+    fn parse_relational_expr(&mut self) -> Result<Expr, String> {
+        let mut result = self.parse_additive_expr()?;
+
+        while let Some(operator) = self.eat_one_of_operators(&["<", "<=", ">", ">="]) {
+            result = Expr::Comparison(operator, Box::new(result), Box::new(self.parse_additive_expr()?));
+        }
+
+        Ok(result)
+    }
+
+    // multiplicative_expr (('+' | '-') multiplicative_expr)*
+    fn parse_additive_expr(&mut self) -> Result<Expr, String> {
+        let mut result = self.parse_multiplicative_expr()?;
+
+        while let Some(operator) = self.eat_one_of_operators(&["+", "-"]) {
+            result = Expr::Arithmetic(operator, Box::new(result), Box::new(self.parse_multiplicative_expr()?));
+        }
+
+        Ok(result)
+    }
+
+    // unary_expr (('*' | 'div' | 'mod') unary_expr)*
+    fn parse_multiplicative_expr(&mut self) -> Result<Expr, String> {
+        let mut result = self.parse_unary_expr()?;
+
+        while let Some(operator) = self.eat_one_of_operators(&["*", "div", "mod"]) {
+            result = Expr::Arithmetic(operator, Box::new(result), Box::new(self.parse_unary_expr()?));
+        }
+
+        Ok(result)
+    }
+
+    // '-'* union_expr
+    fn parse_unary_expr(&mut self) -> Result<Expr, String> {
+
+        if self.eat_operator("-") {
+            Ok(Expr::Negate(Box::new(self.parse_unary_expr()?)))
+        }
+
+        else {
+            self.parse_union_expr()
+        }
+
+    }
+
+    // path_expr ('|' path_expr)*
+    fn parse_union_expr(&mut self) -> Result<Expr, String> {
+        let mut result = self.parse_path_expr()?;
+
+        while self.eat_operator("|") {
+            result = Expr::Union(Box::new(result), Box::new(self.parse_path_expr()?));
+        }
+
+        Ok(result)
+    }
+
+    // location_path | filter_expr (('/' | '//') relative_location_path)?
+    fn parse_path_expr(&mut self) -> Result<Expr, String> {
+
+        match self.peek() {
+            Some(Token::VariableReference(_)) | Some(Token::LeftParenthesis) | Some(Token::Literal(_))
+            | Some(Token::Number(_)) | Some(Token::FunctionName(_)) => {
+                let primary_expr = self.parse_primary_expr()?;
+                let predicates = self.parse_predicates()?;
+
+                let filter_expr = if !predicates.is_empty() {
+                    Expr::Filter(Box::new(primary_expr), predicates)
+                } else {
+                    primary_expr
+                };
+
+                let mut steps = vec![];
+
+                if self.eat_operator("/") {
+                    self.parse_relative_location_path(&mut steps)?;
+                }
+
+                else if self.eat_operator("//") {
+                    steps.push(Step::descendant_or_self());
+                    self.parse_relative_location_path(&mut steps)?;
+                }
+
+                else {
+                    return Ok(filter_expr);
+                }
+
+                Ok(Expr::Path(Box::new(filter_expr), steps))
+            },
+            _ => self.parse_location_path()
+        }
+
+    }
+
+    // '/' relative_location_path? | '//' relative_location_path | relative_location_path
+    fn parse_location_path(&mut self) -> Result<Expr, String> {
+        let mut steps = vec![];
+
+        if self.eat_operator("/") {
+
+            if self.is_step_start() {
+                self.parse_relative_location_path(&mut steps)?;
+            }
+
+            Ok(Expr::LocationPath(true, steps))
+        }
+
+        else if self.eat_operator("//") {
+            steps.push(Step::descendant_or_self());
+            self.parse_relative_location_path(&mut steps)?;
+
+            Ok(Expr::LocationPath(true, steps))
+        }
+
+        else {
+            self.parse_relative_location_path(&mut steps)?;
+
+            Ok(Expr::LocationPath(false, steps))
+        }
+
+    }
+
+    // step (('/' | '//') step)*
+    fn parse_relative_location_path(&mut self, steps: &mut Vec<Step>) -> Result<(), String> {
+        steps.push(self.parse_step()?);
+
+        loop {
+
+            if self.eat_operator("/") {
+                steps.push(self.parse_step()?);
+            }
+
+            else if self.eat_operator("//") {
+                steps.push(Step::descendant_or_self());
+                steps.push(self.parse_step()?);
+            }
+
+            else {
+                return Ok(());
+            }
+
+        }
+
+    }
+
+    // axis_specifier node_test predicate* | '.' | '..'
+    fn parse_step(&mut self) -> Result<Step, String> {
+
+        match self.peek() {
+            Some(Token::Dot) => {
+                self.index += 1;
+
+                return Ok(Step { axis: Axis::Self_, node_test: NodeTest::Node, predicates: vec![] });
+            }
+            Some(Token::DotDot) => {
+                self.index += 1;
+
+                return Ok(Step { axis: Axis::Parent, node_test: NodeTest::Node, predicates: vec![] });
+            }
+            _ => {}
+        }
+
+        let axis = match self.peek().cloned() {
+            Some(Token::AxisName(name)) => {
+                self.index += 1;
+                self.expect(Token::ColonColon)?;
+
+                match Axis::from_name(&name) {
+                    Some(axis) => axis,
+                    None => {
+                        return Err(format!("unknown axis `{}`", name));
+                    }
+                }
+
+            }
+            Some(Token::At) => {
+                self.index += 1;
+                Axis::Attribute
+            }
+            _ => Axis::Child
+        };
+
+        let node_test = match self.next() {
+            Some(Token::NameTest(name)) if name == "*" => NodeTest::Any,
+            Some(Token::NameTest(name)) if name.ends_with(":*") => NodeTest::Prefix(name[..(name.len() - 2)].to_string()),
+            Some(Token::NameTest(name)) => NodeTest::Name(name),
+            Some(Token::NodeType(node_type)) => {
+                self.expect(Token::LeftParenthesis)?;
+
+                let node_test = match node_type.as_str() {
+                    "node" => NodeTest::Node,
+                    "text" => NodeTest::Text,
+                    "comment" => NodeTest::Comment,
+                    _ => match self.peek().cloned() {
+                        Some(Token::Literal(target)) => {
+                            self.index += 1;
+                            NodeTest::ProcessingInstruction(Some(target))
+                        }
+                        _ => NodeTest::ProcessingInstruction(None)
+                    }
+                };
+
+                self.expect(Token::RightParenthesis)?;
+
+                node_test
+            }
+            token => {
+                return Err(format!("expected a node test, found {:?}", token));
+            }
+        };
+
+        Ok(Step {
+            axis,
+            node_test,
+            predicates: self.parse_predicates()?,
+        })
+    }
+
+    // ('[' expr ']')*
+    fn parse_predicates(&mut self) -> Result<Vec<Expr>, String> {
+        let mut result = vec![];
+
+        while self.peek() == Some(&Token::LeftBracket) {
+            self.index += 1;
+            result.push(self.parse_or_expr()?);
+            self.expect(Token::RightBracket)?;
+        }
+
+        Ok(result)
+    }
+
+    // variable_reference | '(' expr ')' | literal | number | function_call
+    fn parse_primary_expr(&mut self) -> Result<Expr, String> {
+
+        match self.next() {
+            Some(Token::VariableReference(name)) => Ok(Expr::Variable(name)),
+            Some(Token::LeftParenthesis) => {
+                let result = self.parse_or_expr()?;
+                self.expect(Token::RightParenthesis)?;
+
+                Ok(result)
+            }
+            Some(Token::Literal(literal)) => Ok(Expr::Literal(literal)),
+            Some(Token::Number(number)) => Ok(Expr::Number(number)),
+            Some(Token::FunctionName(name)) => {
+                self.expect(Token::LeftParenthesis)?;
+                let mut arguments = vec![];
+
+                if self.peek() != Some(&Token::RightParenthesis) {
+                    arguments.push(self.parse_or_expr()?);
+
+                    while self.peek() == Some(&Token::Comma) {
+                        self.index += 1;
+                        arguments.push(self.parse_or_expr()?);
+                    }
+
+                }
+
+                self.expect(Token::RightParenthesis)?;
+
+                Ok(Expr::FunctionCall(name, arguments))
+            }
+            token => Err(format!("expected an expression, found {:?}", token))
+        }
+
+    }
+
+    fn is_step_start(&self) -> bool {
+
+        matches!(
+            self.peek(),
+            Some(Token::Dot) | Some(Token::DotDot) | Some(Token::AxisName(_)) | Some(Token::At)
+            | Some(Token::NameTest(_)) | Some(Token::NodeType(_))
+        )
+
+    }
+
+    fn eat_operator(&mut self, operator: &str) -> bool {
+
+        match self.peek() {
+            Some(Token::Operator(o)) if *o == operator => {
+                self.index += 1;
+                true
+            }
+            _ => false
+        }
+
+    }
+
+    fn eat_one_of_operators(&mut self, operators: &[&'static str]) -> Option<&'static str> {
+
+        operators.iter().find(|operator| self.eat_operator(operator)).copied()
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), String> {
+
+        match self.next() {
+            Some(t) if t == token => Ok(()),
+            t => Err(format!("expected {:?}, found {:?}", token, t))
+        }
+
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let result = self.tokens.get(self.index).cloned();
+        self.index += 1;
+
+        result
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::xpath::parse::*;
+
+    #[test]
+    fn parse_test() {
+        assert_eq!(
+            parse("//a[1]/@b | $x").unwrap(),
+            Expr::Union(
+                Box::new(Expr::LocationPath(true, vec![
+                    Step::descendant_or_self(),
+                    Step { axis: Axis::Child, node_test: NodeTest::Name("a".to_string()), predicates: vec![Expr::Number(1.0)] },
+                    Step { axis: Axis::Attribute, node_test: NodeTest::Name("b".to_string()), predicates: vec![] },
+                ])),
+                Box::new(Expr::Variable("x".to_string())),
+            )
+        );
+        assert_eq!(parse("--$x").unwrap(), Expr::Negate(Box::new(Expr::Negate(Box::new(Expr::Variable("x".to_string()))))));
+
+        assert_eq!(
+            parse("1 + 2 * 3 = 7 and not(false())").unwrap(),
+            Expr::And(
+                Box::new(Expr::Comparison(
+                    "=",
+                    Box::new(Expr::Arithmetic(
+                        "+",
+                        Box::new(Expr::Number(1.0)),
+                        Box::new(Expr::Arithmetic("*", Box::new(Expr::Number(2.0)), Box::new(Expr::Number(3.0)))),
+                    )),
+                    Box::new(Expr::Number(7.0)),
+                )),
+                Box::new(Expr::FunctionCall("not".to_string(), vec![Expr::FunctionCall("false".to_string(), vec![])])),
+            )
+        );
+
+        assert_eq!(parse("/").unwrap(), Expr::LocationPath(true, vec![]));
+        assert_eq!(
+            parse("id('a')//b").unwrap(),
+            Expr::Path(
+                Box::new(Expr::FunctionCall("id".to_string(), vec![Expr::Literal("a".to_string())])),
+                vec![
+                    Step::descendant_or_self(),
+                    Step { axis: Axis::Child, node_test: NodeTest::Name("b".to_string()), predicates: vec![] },
+                ]
+            )
+        );
+
+        for invalid in ["", "a/", "//", "a[", "foo::a", "f(1,)", "1 +", "(1", "a b", "a | -b"] {
+            assert!(parse(invalid).is_err(), "{}", invalid);
+        }
+
+    }
+
+}